@@ -1,13 +1,70 @@
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::TryInto;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::fd::AsFd;
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
 use crate::debugger_command::DebuggerCommand;
-use crate::dwarf_data::{DwarfData, Error as DwarfError};
-use crate::inferior::Inferior;
+use crate::dwarf_data::{
+    self, DwarfData, Error as DwarfError, EvalError as DwarfEvalError, Function, Line, Location,
+    ProgramState, Type, TypeKind, Variable, DEFAULT_DEBUG_FILE_DIRECTORY,
+};
+use crate::inferior::{
+    register_value, setfpregs, CallArg, CallOutcome, FrameState, Inferior, Launch, SyscallStop,
+};
+use nix::sys::ptrace::{getregs, setregs};
+use nix::sys::signal;
+use nix::unistd::isatty;
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 
-use crate::inferior::Status;
 use crate::disassembler::DisassembleObject;
+use crate::inferior::Status;
+use crate::output::{self, Json};
+use crate::pretty::{self, Printers, Value, ValueFormatter};
+use crate::printf::{self, Arg};
+use crate::procfs::{self, AuxvFormat};
+use crate::record::{self, Recorder};
+use crate::regex::Regex;
+use crate::source::SourceFiles;
+use crate::syscall::{self, Syscall};
+use crate::tui::{PaneLine, Tui};
+
+/// Loads the debugging information of `target`, or only its ELF symbols if the DWARF can't be
+/// read, warning about what is unavailable. Returns None if `target` can't be opened.
+fn load_debug_data(target: &str, debug_dirs: &[String]) -> Option<DwarfData> {
+    let debug_data = match DwarfData::from_file(target, debug_dirs) {
+        Ok(val) => val,
+        Err(DwarfError::ErrorOpeningFile) => {
+            output::error(&format!("Could not open file {}", target));
+            return None;
+        }
+        Err(DwarfError::DwarfFormatError(err)) => {
+            output::error(&format!(
+                "Could not load debugging symbols from {}: {:?}",
+                target, err
+            ));
+            // the ELF symbols still allow breaking on functions and naming them
+            DwarfData::from_symbols(target).ok()?
+        }
+    };
+    if let Some(debug_file) = debug_data.debug_file() {
+        output::print(&format!("Reading symbols from {}", debug_file.display()));
+    }
+    if !debug_data.has_debug_info() {
+        output::error(&format!(
+            "(No debugging symbols found in {}; source lines, variables and types are unavailable)",
+            target
+        ));
+    }
+    Some(debug_data)
+}
 
 fn parse_address(addr: &str) -> Option<usize> {
     let addr_without0x = if addr.to_lowercase().starts_with("*0x") {
@@ -19,36 +76,215 @@ fn parse_address(addr: &str) -> Option<usize> {
     usize::from_str_radix(addr_without0x, 16).ok()
 }
 
+/// Number of source lines printed by `list`.
+const LIST_SIZE: usize = 10;
+
+/// Registers shown in the TUI's register window.
+const TUI_REGISTERS: [&str; 18] = [
+    "rip", "rsp", "rbp", "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12",
+    "r13", "r14", "r15", "eflags",
+];
+
+/// Number of integer argument registers in the SysV x86-64 calling convention.
+const MAX_CALL_ARGS: usize = 6;
+
+/// How often an inferior running in the background is checked on while waiting for input.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Why commands that run the program other than `continue` are refused while recording.
+const RECORDING_ERROR: &str = "Cannot execute this command while recording.\n\
+                               Use \"record stop\" to stop recording and then try again.";
+
+/// Whether one of `catchpoints` stops on system call `nr`.
+fn caught(catchpoints: &[Vec<u64>], nr: u64) -> bool {
+    catchpoints
+        .iter()
+        .any(|syscalls| syscalls.is_empty() || syscalls.contains(&nr))
+}
+
+/// Reads a line from stdin, or returns `None` at the end of input.
+fn read_stdin_line() -> Option<String> {
+    let mut line = String::new();
+    match std::io::stdin().read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line),
+    }
+}
+
+/// Splits an expression of the form `func(arg, arg, ...)` into the function name and its
+/// (unparsed) arguments. Commas inside string and character literals are left alone.
+fn parse_call_expr(expr: &str) -> Option<(&str, Vec<String>)> {
+    let open = expr.find('(')?;
+    let name = expr[..open].trim();
+    let inner = expr[open + 1..].trim_end().strip_suffix(')')?;
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for c in inner.chars() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+                current.push(c);
+            }
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                current.push(c);
+            }
+            None if c == ',' => args.push(std::mem::take(&mut current).trim().to_string()),
+            None => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() || !args.is_empty() {
+        args.push(current.trim().to_string());
+    }
+    Some((name, args))
+}
+
+/// Resolves the escape sequences C programmers expect inside string and character literals.
+fn unescape(literal: &str) -> String {
+    let mut result = String::new();
+    let mut chars = literal.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('0') => result.push('\0'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Parses a single argument of a call expression: a decimal or hex integer, a character
+/// literal, or a string literal.
+fn parse_call_arg(arg: &str) -> Option<CallArg> {
+    if arg.len() >= 2 && arg.starts_with('"') && arg.ends_with('"') {
+        return Some(CallArg::Str(unescape(&arg[1..arg.len() - 1])));
+    }
+    if arg.len() >= 3 && arg.starts_with('\'') && arg.ends_with('\'') {
+        let mut chars = unescape(&arg[1..arg.len() - 1])
+            .chars()
+            .collect::<Vec<char>>();
+        if chars.len() == 1 {
+            return Some(CallArg::Int(chars.remove(0) as u64));
+        }
+        return None;
+    }
+    let (negative, digits) = match arg.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, arg),
+    };
+    let value = if digits.to_lowercase().starts_with("0x") {
+        u64::from_str_radix(&digits[2..], 16).ok()?
+    } else {
+        digits.parse::<u64>().ok()?
+    };
+    Some(CallArg::Int(if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    }))
+}
+
 #[derive(Clone)]
 pub struct BreakPoint {
     pub addr: usize,
     pub orig_byte: u8,
 }
 
+/// An expression the `display` command prints at every stop.
+struct Display {
+    number: usize,
+    expr: String,
+    /// For `display/i`, how many instructions to disassemble at the address `expr` gives
+    instructions: Option<usize>,
+}
+
+/// A suspended copy of the inferior made by the `checkpoint` command.
+struct Checkpoint {
+    id: usize,
+    inferior: Inferior,
+    line: Option<Line>,
+    /// Breakpoints that were inserted in the inferior's memory when it was copied
+    breakpoint_set: HashMap<usize, BreakPoint>,
+}
+
 pub struct Debugger {
     target: String,
     history_path: String,
-    readline: Editor<()>,
+    /// Shared with the thread that reads input while the inferior runs in the background
+    readline: Arc<Mutex<Editor<()>>>,
     inferior: Option<Inferior>,
+    /// Whether the inferior was resumed in the background by `run&` or `continue&`, and
+    /// hasn't been seen to stop yet
+    running: bool,
     debug_data: DwarfData,
-    breakpoints: Vec<usize>,
+    /// Addresses of the breakpoints by number; `None` for temporary breakpoints already hit
+    breakpoints: Vec<Option<usize>>,
+    /// Numbers of the breakpoints set by `tbreak`, which are deleted once hit
+    temporary_breakpoints: HashSet<usize>,
+    /// Commands to run when a breakpoint is hit, by breakpoint number
+    breakpoint_commands: HashMap<usize, Vec<String>>,
+    /// What is left of the command list of the breakpoint hit last, run before reading more
+    /// commands from the user
+    pending_commands: VecDeque<String>,
+    /// A line typed while the program ran in the background, held back until the command list
+    /// of the stop reported meanwhile has run
+    typed_ahead: Option<String>,
     breakpoint_set: HashMap<usize, BreakPoint>,
     disassemble: DisassembleObject,
+    checkpoints: Vec<Checkpoint>,
+    next_checkpoint_id: usize,
+    recorder: Option<Recorder>,
+    sources: SourceFiles,
+    /// File and last line printed by `list`, so that another `list` carries on from there
+    last_listed: Option<(String, usize)>,
+    tui: Option<Tui>,
+    /// Syscall numbers each `catch syscall` stops on; an empty list catches every syscall
+    catchpoints: Vec<Vec<u64>>,
+    /// How many of the calls inlined at the current instruction `step` has not entered yet;
+    /// they are left out of the backtrace
+    inline_skip: usize,
+    /// Pretty-printers `print` shows values with, by type name
+    printers: Printers,
+    /// Directories separate debug files are looked for in
+    debug_file_directories: Vec<String>,
+    /// Environment the inferior is started with, deet's own unless changed by
+    /// `set environment` and `unset environment`
+    environment: Vec<(String, String)>,
+    /// Working directory set by `set cwd`
+    cwd: Option<String>,
+    /// Terminal set by `tty` for the inferior to run on
+    tty: Option<String>,
+    /// Expressions printed at every stop, in the order they were added
+    displays: Vec<Display>,
+    next_display: usize,
 }
 
 impl Debugger {
     /// Initializes the debugger.
     pub fn new(target: &str) -> Debugger {
-        let debug_data = match DwarfData::from_file(target) {
-            Ok(val) => val,
-            Err(DwarfError::ErrorOpeningFile) => {
-                eprintln!("Could not open file {}", target);
-                std::process::exit(1);
-            }
-            Err(DwarfError::DwarfFormatError(err)) => {
-                eprintln!("Could not debugging symbols from {}: {:?}", target, err);
-                std::process::exit(1);
-            }
+        let debug_file_directories = vec![DEFAULT_DEBUG_FILE_DIRECTORY.to_string()];
+        let debug_data = match load_debug_data(target, &debug_file_directories) {
+            Some(val) => val,
+            None => std::process::exit(1),
         };
 
         let history_path = format!("{}/.deet_history", std::env::var("HOME").unwrap());
@@ -63,169 +299,473 @@ impl Debugger {
         Debugger {
             target: target.to_string(),
             history_path,
-            readline,
+            readline: Arc::new(Mutex::new(readline)),
             inferior: None,
+            running: false,
             debug_data,
             breakpoints,
+            temporary_breakpoints: HashSet::new(),
+            breakpoint_commands: HashMap::new(),
+            pending_commands: VecDeque::new(),
+            typed_ahead: None,
             breakpoint_set,
             disassemble,
+            checkpoints: Vec::new(),
+            next_checkpoint_id: 1,
+            recorder: None,
+            sources: SourceFiles::new(),
+            last_listed: None,
+            tui: None,
+            catchpoints: Vec::new(),
+            inline_skip: 0,
+            printers: Printers::new(),
+            debug_file_directories,
+            environment: std::env::vars().collect(),
+            cwd: None,
+            tty: None,
+            displays: Vec::new(),
+            next_display: 1,
         }
     }
 
     pub fn run(&mut self) {
         loop {
-            match self.get_next_command() {
-                DebuggerCommand::Run(args) => {
-                    self.to_kill();
+            let command = self.get_next_command();
+            // the inferior may have stopped while the command was being typed
+            self.poll_background(false);
+            if self.running && !command.works_while_running() {
+                output::error(
+                    "Cannot execute this command while the program is running.\n\
+                     Use the \"interrupt\" command to stop it and then try again.",
+                );
+                continue;
+            }
+            if self.recorder.is_some() && !command.works_while_recording() {
+                output::error(RECORDING_ERROR);
+                continue;
+            }
+            match command {
+                DebuggerCommand::Run(args) => self.start_inferior(&args, false, false),
 
-                    if let Some(inferior) = Inferior::new(
-                        &self.target,
-                        &args,
-                        &self.breakpoints,
-                        &mut self.breakpoint_set,
-                    ) {
-                        // Create the inferior
-                        self.inferior = Some(inferior);
-                        let inferior_object = self.inferior.as_mut().unwrap();
-                        let status = inferior_object.cont_exec(&self.breakpoint_set);
-                        match status {
-                            Ok(stat) => {
-                                println!("Child {}", stat);
-
-                                match stat {
-                                    Status::Stopped(_sig, _rip) => {
-                                        let ref mut debug_data_ref = self.debug_data;
-                                        let line = self
-                                            .inferior
-                                            .as_mut()
-                                            .unwrap()
-                                            .get_stop_line(debug_data_ref);
-
-                                        if line.is_some() {
-                                            println!("Stopped at {}", line.unwrap());
-                                        }
-                                    }
-                                    _ => (),
-                                }
-                            }
+                DebuggerCommand::RunBackground(args) => self.start_inferior(&args, false, true),
 
-                            Err(e) => eprintln!("{}", e),
-                        }
+                DebuggerCommand::Start(args) => self.start_inferior(&args, true, false),
+
+                DebuggerCommand::Cont => {
+                    if self.inferior.is_none() {
+                        output::error("No child process is running!");
+                        continue;
+                    }
+                    if self.recorder.is_some() {
+                        self.record_cont();
+                        continue;
+                    }
+                    match self.resume() {
+                        Ok(stat) => self.print_stop(&stat),
+                        Err(e) => output::error(&e.to_string()),
+                    }
+                }
+
+                DebuggerCommand::ContBackground => {
+                    if self.inferior.is_none() {
+                        output::error("No child process is running!");
+                    } else if self.recorder.is_some() {
+                        output::error("Cannot continue in the background while recording.");
                     } else {
-                        println!("Error starting subprocess");
+                        self.resume_background();
                     }
                 }
 
-                DebuggerCommand::Cont => {
+                DebuggerCommand::Interrupt => match &self.inferior {
+                    Some(inferior) if self.running => {
+                        if let Err(e) = inferior.interrupt() {
+                            output::error(&e.to_string());
+                        }
+                    }
+                    // already stopped
+                    Some(_) => (),
+                    None => output::error("The program is not being run."),
+                },
+
+                DebuggerCommand::Back(args) => {
                     if self.inferior.is_none() {
-                        eprintln!("No child process is running!");
+                        output::error("No child process is running!");
                         continue;
                     }
-                    let result = self
-                        .inferior
-                        .as_mut()
-                        .unwrap()
-                        .cont_exec(&self.breakpoint_set);
+                    self.backtrace(&args);
+                }
 
-                    match result {
-                        Ok(stat) => println!("Child {}", stat),
-                        Err(e) => eprintln!("{}", e),
+                DebuggerCommand::Break(arg) => self.break_at(&arg, false),
+
+                DebuggerCommand::TBreak(arg) => self.break_at(&arg, true),
+
+                DebuggerCommand::Step => self.step(false),
+
+                DebuggerCommand::Next => self.step(true),
+
+                DebuggerCommand::Stepi(count) => self.stepi(count, false),
+
+                DebuggerCommand::Nexti(count) => self.stepi(count, true),
+
+                DebuggerCommand::Finish => {
+                    if self.inferior.is_none() {
+                        output::error("No child process is running!");
+                        continue;
+                    }
+                    match self.finish() {
+                        Ok(Some((stat, value))) => {
+                            self.print_stop(&stat);
+                            if let Some(value) = value {
+                                output::print(&format!("Value returned is {}", value));
+                            }
+                        }
+                        Ok(None) => (),
+                        Err(e) => output::error(&e.to_string()),
                     }
                 }
 
-                DebuggerCommand::Back => {
+                DebuggerCommand::Return(value) => {
                     if self.inferior.is_none() {
-                        eprintln!("No child process is running!");
+                        output::error("The program is not being run.");
                         continue;
                     }
+                    if let Err(e) = self.return_now(value.trim()) {
+                        output::error(&e.to_string());
+                    }
+                }
 
-                    let ref mut debug_data_ref = self.debug_data;
+                DebuggerCommand::Jump(location) => {
+                    if self.inferior.is_none() {
+                        output::error("The program is not being run.");
+                        continue;
+                    }
+                    match self.jump(&location) {
+                        Ok(Some(stat)) => self.print_stop(&stat),
+                        Ok(None) => (),
+                        Err(e) => output::error(&e.to_string()),
+                    }
+                }
 
-                    let result = self
-                        .inferior
-                        .as_mut()
-                        .unwrap()
-                        .print_backtrace(debug_data_ref);
+                DebuggerCommand::Until(location) => {
+                    if self.inferior.is_none() {
+                        output::error("No child process is running!");
+                        continue;
+                    }
+                    let result = match location {
+                        Some(location) => self.advance(&location, true),
+                        None => self.until_next_line().map(Some),
+                    };
                     match result {
-                        Ok(()) => (),
-                        Err(e) => eprintln!("{}", e),
+                        Ok(Some(stat)) => self.print_stop(&stat),
+                        Ok(None) => (),
+                        Err(e) => output::error(&e.to_string()),
                     }
                 }
 
-                DebuggerCommand::Break(arg) => {
-                    let breakpoint: Option<usize>;
-                    if arg.starts_with("*") {
-                        if let Some(parsed_addr) = parse_address(&arg) {
-                            breakpoint = Some(parsed_addr);
-                        } else {
-                            breakpoint = None;
-                        }
-                    } else if let Some(line) = usize::from_str_radix(&arg, 10).ok() {
-                        if let Some(line_addr) = self.debug_data.get_addr_for_line(None, line) {
-                            breakpoint = Some(line_addr);
-                        } else {
-                            breakpoint = None;
+                DebuggerCommand::Advance(location) => {
+                    if self.inferior.is_none() {
+                        output::error("No child process is running!");
+                        continue;
+                    }
+                    let location = match location {
+                        Some(location) => location,
+                        None => {
+                            output::error("Argument required (a location).");
+                            continue;
                         }
-                    } else if let Some(func_addr) =
-                        self.debug_data.get_addr_for_function(None, &arg)
-                    {
-                        breakpoint = Some(func_addr);
-                    } else {
-                        breakpoint = None;
-                    }
-
-                    match breakpoint {
-                        Some(bp) => {
-                            if !self.breakpoints.contains(&bp) {
-                                if self.inferior.is_some() {
-                                    if let Some(orig_byte) =
-                                        self.inferior.as_mut().unwrap().write_byte(bp, 0xcc).ok()
-                                    {
-                                        self.breakpoints.push(bp);
-
-                                        let nbreakpoints = self.breakpoints.len() - 1;
-                                        println!("Set breakpoint {} at {:#x}", nbreakpoints, bp);
-
-                                        self.breakpoint_set.insert(
-                                            bp,
-                                            BreakPoint {
-                                                addr: bp,
-                                                orig_byte,
-                                            },
-                                        );
-                                    }
-                                } else {
-                                    self.breakpoints.push(bp);
-
-                                    let nbreakpoints = self.breakpoints.len() - 1;
-                                    println!("Set breakpoint {} at {:#x}", nbreakpoints, bp);
-                                }
+                    };
+                    match self.advance(&location, false) {
+                        Ok(Some(stat)) => self.print_stop(&stat),
+                        Ok(None) => (),
+                        Err(e) => output::error(&e.to_string()),
+                    }
+                }
+
+                DebuggerCommand::Commands(arg) => self.commands(arg.as_deref()),
+
+                DebuggerCommand::Printf(args) => self.printf(&args),
+
+                DebuggerCommand::Dprintf(args) => self.dprintf(&args),
+
+                DebuggerCommand::Info(args) => match args.first().map_or("", |arg| arg.as_str()) {
+                    "b" | "breakpoints" if output::is_json() => {
+                        let breakpoints = self
+                            .breakpoints
+                            .iter()
+                            .enumerate()
+                            .filter_map(|(index, bp)| {
+                                Some(Json::Object(vec![
+                                    ("id", index.into()),
+                                    (
+                                        "temporary",
+                                        self.temporary_breakpoints.contains(&index).into(),
+                                    ),
+                                    ("frame", self.frame_json((*bp)?)),
+                                ]))
+                            })
+                            .collect();
+                        output::record(
+                            "breakpoints",
+                            vec![("breakpoints", Json::Array(breakpoints))],
+                        );
+                    }
+                    "b" | "breakpoints" => {
+                        for (index, breakpoint) in self.breakpoints.iter().enumerate() {
+                            let breakpoint = match breakpoint {
+                                Some(breakpoint) => breakpoint,
+                                None => continue,
+                            };
+                            if self.temporary_breakpoints.contains(&index) {
+                                output::print(&format!(
+                                    "#{} = {:#x} (temporary)",
+                                    index, breakpoint
+                                ));
                             } else {
-                                println!("Breakpoint {:#x} exists", bp);
+                                output::print(&format!("#{} = {:#x}", index, breakpoint));
+                            }
+                            for command in
+                                self.breakpoint_commands.get(&index).into_iter().flatten()
+                            {
+                                output::print(&format!("        {}", command));
+                            }
+                        }
+                        for index in 0..self.catchpoints.len() {
+                            output::print(&format!(
+                                "Catchpoint {} ({})",
+                                index,
+                                self.describe_catchpoint(index)
+                            ));
+                        }
+                    }
+                    "record" => match &self.recorder {
+                        Some(recorder) => output::print(&format!(
+                            "Recording: {} of {} instructions logged",
+                            recorder.len(),
+                            recorder.limit()
+                        )),
+                        None => output::print("Not recording"),
+                    },
+                    "checkpoints" => {
+                        for checkpoint in &self.checkpoints {
+                            match &checkpoint.line {
+                                Some(line) => output::print(&format!(
+                                    "{} process {} at {}",
+                                    checkpoint.id,
+                                    checkpoint.inferior.pid(),
+                                    line
+                                )),
+                                None => output::print(&format!(
+                                    "{} process {}",
+                                    checkpoint.id,
+                                    checkpoint.inferior.pid()
+                                )),
                             }
                         }
+                    }
+                    "functions" => self.info_functions(&args[1..].join(" ")),
+                    "variables" => self.info_variables(&args[1..].join(" ")),
+                    "types" => self.info_types(&args[1..].join(" ")),
+                    "line" => self.info_line(&args[1..].join(" ")),
+                    "address" => self.info_address(&args[1..].join(" ")),
+                    "symbol" => self.info_symbol(&args[1..].join(" ")),
+                    "proc" => self.info_proc(args.get(1).map_or("", |arg| arg.as_str())),
+                    "files" | "target" => self.info_files(),
+                    "sections" => self.info_sections(&args[1..]),
+                    "auxv" => self.info_auxv(),
+                    "display" => self.info_display(),
+                    other => output::error(&format!("Undefined info command: \"{}\".", other)),
+                },
 
-                        None => println!("Function {} not defined!", arg),
+                DebuggerCommand::Ptype(expr) => self.ptype(expr.trim()),
+
+                DebuggerCommand::Checkpoint => {
+                    if self.inferior.is_none() {
+                        output::error("No child process is running!");
+                        continue;
+                    }
+                    let scratch_addr = self.disassemble.entry_point();
+                    let inferior = self.inferior.as_mut().unwrap();
+                    match inferior.checkpoint(scratch_addr) {
+                        Ok(snapshot) => {
+                            let line = inferior.get_stop_line(&mut self.debug_data);
+                            let id = self.next_checkpoint_id;
+                            self.next_checkpoint_id += 1;
+                            output::print(&format!(
+                                "Checkpoint {}: fork returned pid {}",
+                                id,
+                                snapshot.pid()
+                            ));
+                            self.checkpoints.push(Checkpoint {
+                                id,
+                                inferior: snapshot,
+                                line,
+                                breakpoint_set: self.breakpoint_set.clone(),
+                            });
+                        }
+                        Err(e) => output::error(&format!("Failed to create checkpoint: {}", e)),
                     }
                 }
 
-                DebuggerCommand::Info(arg) => match arg.as_str() {
-                    "b" | "breakpoints" => {
-                        for (index, breakpoint) in self.breakpoints.iter().enumerate() {
-                            println!("#{} = {:#x}", index, breakpoint);
+                DebuggerCommand::Restart(id) => self.restart(id),
+
+                DebuggerCommand::Record(arg) => match arg.as_deref() {
+                    None => {
+                        if self.inferior.is_none() {
+                            output::error("No child process is running!");
+                        } else if self.recorder.is_some() {
+                            output::error("Already recording");
+                        } else {
+                            self.recorder = Some(Recorder::new(record::DEFAULT_RECORD_LIMIT));
+                        }
+                    }
+                    Some("stop") => {
+                        if self.recorder.take().is_some() {
+                            output::print("Recording stopped, execution log discarded");
+                        } else {
+                            output::error("Not recording");
                         }
                     }
-                    _ => (),
+                    Some(other) => {
+                        output::error(&format!("Unknown record subcommand \"{}\"", other))
+                    }
                 },
 
+                DebuggerCommand::ReverseStepi(count) => {
+                    if self.recorder.is_none() {
+                        output::error("Not recording, use \"record\" first");
+                        continue;
+                    }
+                    for _ in 0..count {
+                        if !self.reverse_step() {
+                            break;
+                        }
+                    }
+                    self.print_reverse_stop();
+                }
+
+                DebuggerCommand::ReverseNext => {
+                    if self.recorder.is_none() {
+                        output::error("Not recording, use \"record\" first");
+                        continue;
+                    }
+                    if let Err(e) = self.reverse_next() {
+                        output::error(&e.to_string());
+                    }
+                    self.print_reverse_stop();
+                }
+
+                DebuggerCommand::ReverseCont => {
+                    if self.recorder.is_none() {
+                        output::error("Not recording, use \"record\" first");
+                        continue;
+                    }
+                    while self.reverse_step() {
+                        let rip = self.recorder_rip();
+                        if self.breakpoint_set.contains_key(&rip) {
+                            break;
+                        }
+                    }
+                    self.print_reverse_stop();
+                }
+
                 #[allow(unused_variables)]
                 DebuggerCommand::Disassemble() => {
                     // arg is where user want it to be disassembled
                     // not yet implmented
-                    self.disassemble.disassemble();
+                    self.disassemble.disassemble(self.debug_data.symbols());
+                }
+
+                DebuggerCommand::Call(expr) => self.call_function(&expr, false),
+
+                DebuggerCommand::Print(expr) => {
+                    if expr.contains('(') {
+                        self.call_function(&expr, true);
+                    } else {
+                        self.print_variable(expr.trim());
+                    }
                 }
 
+                DebuggerCommand::Display(format, expr) => self.display(format.as_deref(), &expr),
+
+                DebuggerCommand::Undisplay(args) => self.undisplay(&args),
+
+                DebuggerCommand::List(arg) => self.list(arg.as_deref()),
+
+                DebuggerCommand::Directory(dir) => match dir {
+                    Some(dir) => {
+                        self.sources.add_directory(&dir);
+                        output::print(&format!(
+                            "Source directories searched: {}",
+                            self.source_path()
+                        ));
+                    }
+                    None => output::print(&format!(
+                        "Source directories searched: {}",
+                        self.source_path()
+                    )),
+                },
+
+                DebuggerCommand::Set(args) => self.set(&args),
+
+                DebuggerCommand::Unset(args) => self.unset(&args),
+
+                DebuggerCommand::Tty(arg) => self.set_tty(arg.as_deref()),
+
+                DebuggerCommand::Tui(arg) => match arg.as_deref() {
+                    None | Some("enable") => {
+                        if output::is_json() {
+                            output::error("The TUI is not available with --interpreter=json");
+                            continue;
+                        }
+                        if self.tui.is_none() {
+                            self.tui = Tui::enable();
+                            if self.tui.is_none() {
+                                output::error(
+                                    "Standard output is not a terminal, staying in plain mode",
+                                );
+                            }
+                        }
+                        self.refresh_tui();
+                    }
+                    Some("disable") => {
+                        if let Some(tui) = self.tui.take() {
+                            tui.disable();
+                        }
+                    }
+                    Some(other) => output::error(&format!("Unknown tui subcommand \"{}\"", other)),
+                },
+
+                DebuggerCommand::Catch(args) => match args.first().map(|arg| arg.as_str()) {
+                    Some("syscall") => {
+                        let mut syscalls = Vec::new();
+                        for arg in &args[1..] {
+                            match syscall::parse_syscall(arg) {
+                                Some(nr) => syscalls.push(nr),
+                                None => {
+                                    output::error(&format!("Unknown syscall name '{}'", arg));
+                                    break;
+                                }
+                            }
+                        }
+                        if syscalls.len() == args.len() - 1 {
+                            self.catchpoints.push(syscalls);
+                            output::print(&format!(
+                                "Catchpoint {} ({})",
+                                self.catchpoints.len() - 1,
+                                self.describe_catchpoint(self.catchpoints.len() - 1)
+                            ));
+                        }
+                    }
+                    Some(other) => {
+                        output::error(&format!("Unknown catch subcommand \"{}\"", other))
+                    }
+                    None => output::error("Usage: catch syscall [name|number...]"),
+                },
+
                 DebuggerCommand::Quit => {
+                    if let Some(tui) = self.tui.take() {
+                        tui.disable();
+                    }
                     self.to_kill();
                     return;
                 }
@@ -233,54 +773,2797 @@ impl Debugger {
         }
     }
 
-    /// kill the current running inferior
-    fn to_kill(&mut self) {
-        if !self.inferior.is_none() {
-            self.breakpoints.clear();
-            let pid = self.inferior.as_mut().unwrap().pid();
-            self.inferior.as_mut().unwrap().kill_inferior();
-            println!("Killing running inferior (pid {})", pid);
+    /// run `expr` (a function call) inside the stopped inferior and print what it returned.
+    /// Functions returning void only print something when `print_void` is set.
+    fn call_function(&mut self, expr: &str, print_void: bool) {
+        if self.inferior.is_none() {
+            output::error("No child process is running!");
+            return;
         }
-    }
 
-    /// This function prompts the user to enter a command, and continues re-prompting until the user
-    /// enters a valid command. It uses DebuggerCommand::from_tokens to do the command parsing.
-    ///
-    /// You don't need to read, understand, or modify this function.
-    fn get_next_command(&mut self) -> DebuggerCommand {
-        loop {
-            // Print prompt and get next line of user input
-            match self.readline.readline("(deet) ") {
-                Err(ReadlineError::Interrupted) => {
-                    // User pressed ctrl+c. We're going to ignore it
-                    println!("Type \"quit\" to exit");
+        if self.recorder.is_some() {
+            return output::error(RECORDING_ERROR);
+        }
+
+        let (name, raw_args) = match parse_call_expr(expr) {
+            Some(call) => call,
+            None => {
+                output::error(&format!(
+                    "Expected a function call like func(arg, ...), got \"{}\"",
+                    expr
+                ));
+                return;
+            }
+        };
+        let func = match self.debug_data.get_function(name) {
+            Some(func) => func.clone(),
+            None => {
+                output::error(&format!("Function {} not defined!", name));
+                return;
+            }
+        };
+        if raw_args.len() > MAX_CALL_ARGS {
+            output::error(&format!(
+                "Calling functions with more than {} arguments is not supported",
+                MAX_CALL_ARGS
+            ));
+            return;
+        }
+        // bigger structs are returned in memory the caller provides, through a hidden argument
+        if let Some(ret_type) = &func.return_type {
+            let state = match self.inferior.as_ref().unwrap().frame_state() {
+                Ok(state) => state,
+                Err(e) => return output::error(&e.to_string()),
+            };
+            let resolved =
+                ValueFormatter::new(&self.debug_data, &state, &self.printers).resolve(ret_type);
+            if !resolved.is_float() && resolved.size > 16 {
+                output::error(&format!(
+                    "Calling functions returning {} is not supported",
+                    pretty::type_name(&self.debug_data, ret_type)
+                ));
+                return;
+            }
+        }
+        let mut args = Vec::new();
+        for raw_arg in &raw_args {
+            match parse_call_arg(raw_arg) {
+                Some(arg) => args.push(arg),
+                None => {
+                    output::error(&format!("Cannot evaluate argument \"{}\"", raw_arg));
+                    return;
                 }
-                Err(ReadlineError::Eof) => {
-                    // User pressed ctrl+d, which is the equivalent of "quit" for our purposes
-                    return DebuggerCommand::Quit;
+            }
+        }
+
+        let trap_addr = self.disassemble.entry_point();
+        let result = self
+            .inferior
+            .as_mut()
+            .unwrap()
+            .call_function(func.address, &args, trap_addr);
+
+        match result {
+            Ok(CallOutcome::Returned { regs, fpregs }) => match &func.return_type {
+                Some(ret_type) => {
+                    let inferior = self.inferior.as_ref().unwrap();
+                    let value = match inferior.frame_state() {
+                        Ok(mut state) => {
+                            state.regs = *regs;
+                            state.fpregs = *fpregs;
+                            self.return_value(ret_type, &state)
+                        }
+                        Err(e) => format!("<error: {}>", e),
+                    };
+                    output::print(&format!("{} = {}", expr, value))
                 }
-                Err(err) => {
-                    panic!("Unexpected I/O error: {:?}", err);
+                None if print_void => output::print(&format!("{} = void", expr)),
+                None => (),
+            },
+            Ok(CallOutcome::Interrupted(stat)) => {
+                output::print(&format!(
+                    "Child {} while in function {} called from deet",
+                    stat, name
+                ));
+                if let Status::Stopped(_, _) = stat {
+                    output::print("Registers were restored to their state before the call");
                 }
-                Ok(line) => {
-                    if line.trim().len() == 0 {
-                        continue;
-                    }
-                    self.readline.add_history_entry(line.as_str());
-                    if let Err(err) = self.readline.save_history(&self.history_path) {
-                        println!(
-                            "Warning: failed to save history file at {}: {}",
-                            self.history_path, err
-                        );
-                    }
-                    let tokens: Vec<&str> = line.split_whitespace().collect();
-                    if let Some(cmd) = DebuggerCommand::from_tokens(&tokens) {
-                        return cmd;
-                    } else {
-                        println!("Unrecognized command.");
+            }
+            Err(e) => output::error(&e.to_string()),
+        }
+    }
+
+    /// handle `backtrace [full] [N|-N]`: print the innermost (or with -N, outermost) N frames,
+    /// with the arguments of each call, and with `full` the local variables too
+    fn backtrace(&self, args: &[String]) {
+        let mut full = false;
+        let mut limit: Option<isize> = None;
+        for arg in args {
+            match arg.as_str() {
+                "full" | "-full" => full = true,
+                _ => match arg.parse::<isize>() {
+                    Ok(n) => limit = Some(n),
+                    Err(_) => {
+                        output::error(&format!("No symbol \"{}\" in current context.", arg));
+                        return;
                     }
-                }
+                },
+            }
+        }
+        let inferior = self.inferior.as_ref().unwrap();
+        let states = match inferior.stack_frames(&self.debug_data) {
+            Ok(states) => states,
+            Err(e) => return output::error(&e.to_string()),
+        };
+
+        // one line for each call, including the inlined ones
+        let mut frames = Vec::new();
+        for (index, state) in states.iter().enumerate() {
+            let pc = state.regs.rip as usize;
+            // a return address may already belong to the line after the call
+            let addr = if index == 0 { pc } else { pc - 1 };
+            let calls = self.debug_data.get_frames_at(addr).len().max(1);
+            let hidden = if index == 0 { self.inline_skip } else { 0 };
+            for level in hidden..calls {
+                frames.push((state, addr, level));
+            }
+        }
+        let shown = match limit {
+            Some(n) if n >= 0 => 0..(n as usize).min(frames.len()),
+            Some(n) => frames.len().saturating_sub(n.unsigned_abs())..frames.len(),
+            None => 0..frames.len(),
+        };
+        for number in shown.clone() {
+            let (state, addr, level) = frames[number];
+            self.print_frame(number, state, addr, level, full);
+        }
+        if shown.end < frames.len() {
+            output::print("(More stack frames follow...)");
+        }
+    }
+
+    /// print a line of a backtrace: the call `level` inlined calls out from the code at `addr`,
+    /// in the physical frame `state`
+    fn print_frame(
+        &self,
+        number: usize,
+        state: &dyn ProgramState,
+        addr: usize,
+        level: usize,
+        full: bool,
+    ) {
+        let (name, line) = match self.debug_data.get_frames_at(addr).into_iter().nth(level) {
+            Some(frame) => frame,
+            None => ("??".to_string(), None),
+        };
+        let func = self.debug_data.get_function_at(addr);
+        let calls = self.debug_data.get_inlined_calls_at(addr);
+        let variables: &[Variable] = if level < calls.len() {
+            &calls[calls.len() - 1 - level].variables
+        } else {
+            func.map(|func| func.variables.as_slice())
+                .unwrap_or_default()
+        };
+        let args: Vec<String> = variables
+            .iter()
+            .filter(|var| var.is_parameter)
+            .map(|var| format!("{}={}", var.name, self.format_variable(var, func, state)))
+            .collect();
+        match line {
+            Some(line) => output::print(&format!(
+                "#{:<2} {} ({}) at {}",
+                number,
+                name,
+                args.join(", "),
+                line
+            )),
+            None => output::print(&format!(
+                "#{:<2} {:#x} in {} ({})",
+                number,
+                state.pc(),
+                name,
+                args.join(", ")
+            )),
+        }
+        if full {
+            let mut locals = variables.iter().filter(|var| !var.is_parameter).peekable();
+            if locals.peek().is_none() {
+                output::print("No locals.");
             }
+            for var in locals {
+                output::print(&format!(
+                    "        {} = {}",
+                    var.name,
+                    self.format_variable(var, func, state)
+                ));
+            }
+        }
+    }
+
+    /// format the value of a variable of the frame `state`, or why it has none
+    fn format_variable(
+        &self,
+        var: &Variable,
+        func: Option<&Function>,
+        state: &dyn ProgramState,
+    ) -> String {
+        let formatter = ValueFormatter::new(&self.debug_data, state, &self.printers).brief();
+        let value = self
+            .debug_data
+            .read_variable(var, func, state)
+            .and_then(|bytes| formatter.format(&Value::new(&var.entity_type, bytes)));
+        match value {
+            Ok(value) => value,
+            Err(DwarfEvalError::OptimizedOut) => DwarfEvalError::OptimizedOut.to_string(),
+            Err(e) => format!("<error: {}>", e),
         }
     }
+
+    /// print the value of a variable or register (`$rax`) in the current frame
+    fn print_variable(&self, name: &str) {
+        let inferior = match &self.inferior {
+            Some(inferior) => inferior,
+            None => {
+                output::error("No child process is running!");
+                return;
+            }
+        };
+        let state = match inferior.frame_state() {
+            Ok(state) => state,
+            Err(e) => {
+                output::error(&e.to_string());
+                return;
+            }
+        };
+        match self.expression_value(&state, name) {
+            Ok(value) => output::print(&format!("{} = {}", name, value)),
+            Err(e) => output::error(&e),
+        }
+    }
+
+    /// the value of a variable or register (`$rax`) in the frame of `state`, ready to print
+    fn expression_value(&self, state: &FrameState, name: &str) -> Result<String, String> {
+        if name.starts_with('$') {
+            return register_value(&state.regs, name)
+                .map(|value| format!("{:#x}", value))
+                .ok_or_else(|| format!("Unknown register \"{}\"", name));
+        }
+        let (var, func) = self
+            .debug_data
+            .get_variable(state.regs.rip as usize, name)
+            .ok_or_else(|| format!("No symbol \"{}\" in current context.", name))?;
+        match self.debug_data.read_variable(var, func, state) {
+            Ok(bytes) => {
+                let formatter = ValueFormatter::new(&self.debug_data, state, &self.printers);
+                formatter
+                    .format(&Value::new(&var.entity_type, bytes))
+                    .map_err(|e| e.to_string())
+            }
+            Err(DwarfEvalError::OptimizedOut) => Ok(DwarfEvalError::OptimizedOut.to_string()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// the address a variable or register holds, for `display/i`
+    fn expression_address(&self, state: &FrameState, name: &str) -> Result<usize, String> {
+        if name.starts_with('$') {
+            return register_value(&state.regs, name)
+                .map(|value| value as usize)
+                .ok_or_else(|| format!("Unknown register \"{}\"", name));
+        }
+        let (var, func) = self
+            .debug_data
+            .get_variable(state.regs.rip as usize, name)
+            .ok_or_else(|| format!("No symbol \"{}\" in current context.", name))?;
+        let bytes = self
+            .debug_data
+            .read_variable(var, func, state)
+            .map_err(|e| e.to_string())?;
+        Ok(Value::new(&var.entity_type, bytes).as_u64() as usize)
+    }
+
+    /// handle `display[/Ni] [expr]`: print `expr` now and whenever the inferior stops, or
+    /// disassemble N instructions at the address it gives with `/i`. Without `expr`, prints
+    /// all of them again.
+    fn display(&mut self, format: Option<&str>, expr: &str) {
+        if expr.is_empty() {
+            self.do_displays();
+            return;
+        }
+        let instructions = match format.map(|format| format.strip_suffix('i')) {
+            None => None,
+            Some(Some("")) => Some(1),
+            Some(Some(count)) => match count.parse() {
+                Ok(count) => Some(count),
+                Err(_) => {
+                    output::error(&format!("Invalid number \"{}\".", count));
+                    return;
+                }
+            },
+            Some(None) => {
+                output::error(&format!(
+                    "Unsupported display format \"/{}\"; only /i is supported.",
+                    format.unwrap()
+                ));
+                return;
+            }
+        };
+        self.displays.push(Display {
+            number: self.next_display,
+            expr: expr.to_string(),
+            instructions,
+        });
+        self.next_display += 1;
+        if let Some(state) = self
+            .inferior
+            .as_ref()
+            .and_then(|inferior| inferior.frame_state().ok())
+        {
+            self.show_display(self.displays.last().unwrap(), &state);
+        }
+    }
+
+    /// print the `display` expressions in the frame the inferior is stopped in
+    fn do_displays(&self) {
+        // nothing to show once the inferior has exited
+        let state = match self
+            .inferior
+            .as_ref()
+            .and_then(|inferior| inferior.frame_state().ok())
+        {
+            Some(state) => state,
+            None => return,
+        };
+        for display in &self.displays {
+            self.show_display(display, &state);
+        }
+    }
+
+    fn show_display(&self, display: &Display, state: &FrameState) {
+        // variables are only shown where they are in scope
+        if !display.expr.starts_with('$')
+            && self
+                .debug_data
+                .get_variable(state.regs.rip as usize, &display.expr)
+                .is_none()
+        {
+            return;
+        }
+        let count = match display.instructions {
+            Some(count) => count,
+            None => {
+                let value = self
+                    .expression_value(state, &display.expr)
+                    .unwrap_or_else(|e| format!("<error: {}>", e));
+                output::print(&format!("{}: {} = {}", display.number, display.expr, value));
+                return;
+            }
+        };
+        let format = match count {
+            1 => "i".to_string(),
+            count => format!("{}i", count),
+        };
+        output::print(&format!(
+            "{}: x/{} {}",
+            display.number, format, display.expr
+        ));
+        let addr = match self.expression_address(state, &display.expr) {
+            Ok(addr) => addr,
+            Err(e) => return output::error(&e),
+        };
+        let instructions = self.disassemble.instructions_at(addr, count);
+        if instructions.is_empty() {
+            output::error(&format!("Cannot access memory at address {:#x}", addr));
+        }
+        for (addr, text) in instructions {
+            let marker = if addr == state.regs.rip as usize {
+                "=>"
+            } else {
+                "  "
+            };
+            output::print(&format!(
+                "{} {:#x}{}:\t{}",
+                marker,
+                addr,
+                self.symbolic(addr),
+                text
+            ));
+        }
+    }
+
+    /// handle `info display`
+    fn info_display(&self) {
+        if self.displays.is_empty() {
+            output::print("There are no auto-display expressions now.");
+            return;
+        }
+        output::print("Auto-display expressions now in effect:");
+        output::print("Num Enb Expression");
+        for display in &self.displays {
+            let format = match display.instructions {
+                Some(1) => "/i ".to_string(),
+                Some(count) => format!("/{}i ", count),
+                None => String::new(),
+            };
+            output::print(&format!(
+                "{}:   y  {}{}",
+                display.number, format, display.expr
+            ));
+        }
+    }
+
+    /// handle `undisplay [N...]`: stop displaying the given expressions, or all of them
+    fn undisplay(&mut self, args: &[String]) {
+        if args.is_empty() {
+            self.displays.clear();
+            return;
+        }
+        for arg in args {
+            match arg.parse::<usize>() {
+                Ok(number) if self.displays.iter().any(|display| display.number == number) => {
+                    self.displays.retain(|display| display.number != number)
+                }
+                Ok(number) => output::error(&format!("No display number {}.", number)),
+                Err(_) => output::error(&format!(
+                    "Arguments must be display numbers, not \"{}\".",
+                    arg
+                )),
+            }
+        }
+    }
+
+    /// handle `ptype <expr|type>`: describe the type of a variable, or a type given by name
+    fn ptype(&self, expr: &str) {
+        let ty = match self.debug_data.get_variable(self.current_pc(), expr) {
+            Some((var, _)) => Some(&var.entity_type),
+            None => {
+                let name = ["struct ", "class ", "union ", "enum "]
+                    .iter()
+                    .find_map(|keyword| expr.strip_prefix(keyword))
+                    .unwrap_or(expr);
+                self.debug_data.get_type_by_name(name.trim())
+            }
+        };
+        match ty {
+            Some(ty) => output::print(&pretty::describe_type(&self.debug_data, ty)),
+            None => output::error(&format!("No symbol \"{}\" in current context.", expr)),
+        }
+    }
+
+    /// compile the regular expression of `info functions`, `info variables` or `info types`,
+    /// and print the heading of the list
+    fn info_regex(&self, what: &str, pattern: &str) -> Option<Regex> {
+        match Regex::new(pattern) {
+            Ok(regex) => {
+                if pattern.is_empty() {
+                    output::print(&format!("All defined {}:", what));
+                } else {
+                    output::print(&format!(
+                        "All {} matching regular expression \"{}\":",
+                        what, pattern
+                    ));
+                }
+                Some(regex)
+            }
+            Err(e) => {
+                output::error(&format!("Invalid regexp: {}", e));
+                None
+            }
+        }
+    }
+
+    /// print `entries` (name, line number, declaration) under the name of the file they are
+    /// from, sorted by name
+    fn print_file_entries(&self, file: &dwarf_data::File, mut entries: Vec<(&str, usize, String)>) {
+        if entries.is_empty() {
+            return;
+        }
+        entries.sort();
+        entries.dedup();
+        output::print("");
+        output::print(&format!(
+            "File {}:",
+            Path::new(&file.directory).join(&file.name).display()
+        ));
+        for (_, line, declaration) in entries {
+            output::print(&format!("{}:\t{};", line, declaration));
+        }
+    }
+
+    /// handle `info functions [regex]`: the functions with debug info, by file, then the ELF
+    /// symbols of code without any
+    fn info_functions(&self, pattern: &str) {
+        let regex = match self.info_regex("functions", pattern) {
+            Some(regex) => regex,
+            None => return,
+        };
+        for file in self.debug_data.files() {
+            let entries = file
+                .functions
+                .iter()
+                // declarations of functions from other files come without code
+                .filter(|func| func.address != 0 && regex.is_match(&func.name))
+                .map(|func| {
+                    (
+                        func.name.as_str(),
+                        func.line_number,
+                        self.signature(func, file),
+                    )
+                })
+                .collect();
+            self.print_file_entries(file, entries);
+        }
+
+        let with_debug_info: HashSet<usize> = self
+            .debug_data
+            .files()
+            .iter()
+            .flat_map(|file| file.functions.iter().map(|func| func.address))
+            .collect();
+        let symbols: Vec<_> = self
+            .debug_data
+            .symbols()
+            .iter()
+            .filter(|symbol| {
+                !with_debug_info.contains(&symbol.address) && regex.is_match(&symbol.name)
+            })
+            .collect();
+        if !symbols.is_empty() {
+            output::print("");
+            output::print("Non-debugging symbols:");
+            for symbol in symbols {
+                output::print(&format!("{:#018x}  {}", symbol.address, symbol.name));
+            }
+        }
+    }
+
+    /// declare `func` the way its language would: `int main(int, char **)`, or
+    /// `fn add(i32, i32) -> i32` in Rust
+    fn signature(&self, func: &Function, file: &dwarf_data::File) -> String {
+        let params: Vec<String> = func
+            .variables
+            .iter()
+            .filter(|var| var.is_parameter)
+            .map(|var| pretty::type_name(&self.debug_data, &var.entity_type))
+            .collect();
+        let mut params = params.join(", ");
+        let return_type = func
+            .return_type
+            .as_ref()
+            .map(|ty| pretty::type_name(&self.debug_data, ty));
+        if file.language == Some(gimli::DW_LANG_Rust) {
+            match return_type {
+                Some(return_type) => format!("fn {}({}) -> {}", func.name, params, return_type),
+                None => format!("fn {}({})", func.name, params),
+            }
+        } else {
+            if params.is_empty() {
+                params = "void".to_string();
+            }
+            let return_type = return_type.unwrap_or_else(|| "void".to_string());
+            let separator = if return_type.ends_with('*') { "" } else { " " };
+            format!("{}{}{}({})", return_type, separator, func.name, params)
+        }
+    }
+
+    /// handle `info variables [regex]`: the global variables, by file
+    fn info_variables(&self, pattern: &str) {
+        let regex = match self.info_regex("variables", pattern) {
+            Some(regex) => regex,
+            None => return,
+        };
+        for file in self.debug_data.files() {
+            let entries = file
+                .global_variables
+                .iter()
+                .filter(|var| regex.is_match(&var.name))
+                .map(|var| {
+                    (
+                        var.name.as_str(),
+                        var.line_number,
+                        pretty::declaration(&self.debug_data, &var.entity_type, &var.name),
+                    )
+                })
+                .collect();
+            self.print_file_entries(file, entries);
+        }
+    }
+
+    /// handle `info types [regex]`: the named structures, enums, typedefs and base types
+    fn info_types(&self, pattern: &str) {
+        let regex = match self.info_regex("types", pattern) {
+            Some(regex) => regex,
+            None => return,
+        };
+        let mut types: Vec<String> = self
+            .debug_data
+            .types()
+            .filter(|ty| !ty.name.is_empty() && regex.is_match(&ty.name))
+            .filter_map(|ty| match &ty.kind {
+                TypeKind::Struct(struct_type) => {
+                    Some(format!("{} {};", struct_type.keyword, ty.name))
+                }
+                TypeKind::Enum(_) => Some(format!("enum {};", ty.name)),
+                // qualified types are named after what they qualify, typedefs are not
+                TypeKind::Alias(Some(target)) if !ty.is_qualified() => {
+                    let target = self.debug_data.get_type(*target)?;
+                    Some(format!(
+                        "typedef {} {};",
+                        pretty::type_name(&self.debug_data, target),
+                        ty.name
+                    ))
+                }
+                TypeKind::Base => Some(ty.name.clone()),
+                _ => None,
+            })
+            .collect();
+        types.sort();
+        types.dedup();
+        if !types.is_empty() {
+            output::print("");
+        }
+        for ty in types {
+            output::print(&format!("\t{}", ty));
+        }
+    }
+
+    /// handle `info line [location]`: where the code of a line starts and ends
+    fn info_line(&self, location: &str) {
+        let found = if location.is_empty() {
+            self.current_line().map(|line| (line.file, line.number))
+        } else {
+            self.list_location(location)
+        };
+        let (file, number) = match found {
+            Some(found) => found,
+            None if location.is_empty() => {
+                return output::error("No line number information available.")
+            }
+            None => return output::error(&format!("Function \"{}\" not defined.", location)),
+        };
+        match self.debug_data.get_line_range(&file, number) {
+            Some((start, end)) if end > start => output::print(&format!(
+                "Line {} of \"{}\" starts at address {:#x}{} and ends at {:#x}{}.",
+                number,
+                file,
+                start,
+                self.symbolic(start),
+                end,
+                self.symbolic(end)
+            )),
+            Some((start, _)) => output::print(&format!(
+                "Line {} of \"{}\" is at address {:#x}{} but contains no code.",
+                number,
+                file,
+                start,
+                self.symbolic(start)
+            )),
+            None => output::print(&format!(
+                "Line {} of \"{}\" contains no code.",
+                number, file
+            )),
+        }
+    }
+
+    /// ` <func+0x1a>` after an address in code, or nothing
+    fn symbolic(&self, addr: usize) -> String {
+        match self.debug_data.symbols().describe(addr) {
+            Some(name) => format!(" <{}>", name),
+            None => String::new(),
+        }
+    }
+
+    /// handle `info address <symbol>`: where a variable is stored, or where a function is
+    fn info_address(&self, name: &str) {
+        if let Some((var, _)) = self.debug_data.get_variable(self.current_pc(), name) {
+            let description = match (&var.location, var.location.static_address()) {
+                (_, Some(addr)) => format!("static storage at address {:#x}", addr),
+                (Location::Value(_), _) => "constant".to_string(),
+                (Location::OptimizedOut, _) => "optimized out".to_string(),
+                (location, _) => format!("a variable with DWARF location {}", location),
+            };
+            output::print(&format!("Symbol \"{}\" is {}.", name, description));
+        } else if let Some(func) = self.debug_data.get_function(name) {
+            output::print(&format!(
+                "Symbol \"{}\" is a function at address {:#x}.",
+                func.name, func.address
+            ));
+        } else if let Some(addr) = self.debug_data.get_addr_for_function(None, name) {
+            output::print(&format!(
+                "Symbol \"{}\" is at {:#x} in a file compiled without debugging.",
+                name, addr
+            ));
+        } else {
+            output::error(&format!("No symbol \"{}\" in current context.", name));
+        }
+    }
+
+    /// handle `info symbol <addr>`: which function an address is in
+    fn info_symbol(&self, arg: &str) {
+        let addr = match arg.strip_prefix("0x") {
+            Some(hex) => usize::from_str_radix(hex, 16).ok(),
+            None => arg
+                .parse()
+                .ok()
+                .or_else(|| self.debug_data.get_addr_for_function(None, arg)),
+        };
+        let addr = match addr {
+            Some(addr) => addr,
+            None => return output::error(&format!("No symbol \"{}\" in current context.", arg)),
+        };
+        match self.debug_data.symbols().symbol_at(addr) {
+            Some((symbol, 0)) => {
+                output::print(&format!("{} in section {}", symbol.name, symbol.section))
+            }
+            Some((symbol, offset)) => output::print(&format!(
+                "{} + {} in section {}",
+                symbol.name, offset, symbol.section
+            )),
+            None => output::print(&format!("No symbol matches {}.", arg)),
+        }
+    }
+
+    /// handle `info proc [mappings]`: the process's command line and directories, or its
+    /// memory map
+    fn info_proc(&self, what: &str) {
+        let pid = match &self.inferior {
+            Some(inferior) => inferior.pid(),
+            None => return output::error("No child process is running!"),
+        };
+        output::print(&format!("process {}", pid));
+        match what {
+            "" => {
+                let cmdline = fs::read(format!("/proc/{}/cmdline", pid)).unwrap_or_default();
+                let args: Vec<_> = cmdline
+                    .split(|byte| *byte == 0)
+                    .filter(|arg| !arg.is_empty())
+                    .map(String::from_utf8_lossy)
+                    .collect();
+                output::print(&format!("cmdline = '{}'", args.join(" ")));
+                for link in &["cwd", "exe"] {
+                    if let Ok(path) = fs::read_link(format!("/proc/{}/{}", pid, link)) {
+                        output::print(&format!("{} = '{}'", link, path.display()));
+                    }
+                }
+            }
+            "mappings" => {
+                let mappings = match procfs::mappings(pid) {
+                    Ok(mappings) => mappings,
+                    Err(e) => return output::error(&format!("Unable to read mappings: {}", e)),
+                };
+                output::print("Mapped address spaces:\n");
+                output::print(&format!(
+                    "{:>20} {:>18} {:>10} {:>10}  {:<5}  {}",
+                    "Start Addr", "End Addr", "Size", "Offset", "Perms", "objfile"
+                ));
+                for mapping in mappings {
+                    let line = format!(
+                        "{:>#20x} {:>#18x} {:>#10x} {:>#10x}  {:<5}  {}",
+                        mapping.start,
+                        mapping.end,
+                        mapping.end - mapping.start,
+                        mapping.offset,
+                        mapping.perms,
+                        mapping.path
+                    );
+                    // anonymous mappings have no objfile
+                    output::print(line.trim_end());
+                }
+            }
+            other => output::error(&format!("Undefined info proc command: \"{}\".", other)),
+        }
+    }
+
+    /// handle `info files`: the program being debugged and where its sections are
+    fn info_files(&self) {
+        output::print(&format!("Symbols from \"{}\".", self.target));
+        if let Some(inferior) = &self.inferior {
+            output::print("Native process:");
+            output::print(&format!(
+                "\tUsing the running image of child process {}.",
+                inferior.pid()
+            ));
+        }
+        output::print("Local exec file:");
+        output::print(&format!(
+            "\t`{}', file type {}.",
+            self.target,
+            self.disassemble.file_format()
+        ));
+        output::print(&format!(
+            "\tEntry point: {:#x}",
+            self.disassemble.entry_point()
+        ));
+        for section in self.disassemble.sections() {
+            if section.flags.contains(&"ALLOC") {
+                output::print(&format!(
+                    "\t{:#018x} - {:#018x} is {}",
+                    section.address,
+                    section.address + section.size,
+                    section.name
+                ));
+            }
+        }
+    }
+
+    /// handle `info sections [names...]`: every section of the program with its flags, or just
+    /// the ones named
+    fn info_sections(&self, names: &[String]) {
+        output::print(&format!(
+            "Exec file: `{}', file type {}.",
+            self.target,
+            self.disassemble.file_format()
+        ));
+        for (index, section) in self.disassemble.sections().iter().enumerate() {
+            if !names.is_empty() && !names.contains(&section.name) {
+                continue;
+            }
+            output::print(&format!(
+                " [{}]      {:#018x}->{:#018x} at {:#010x}: {} {}",
+                index,
+                section.address,
+                section.address + section.size,
+                section.file_offset.unwrap_or(0),
+                section.name,
+                section.flags.join(" ")
+            ));
+        }
+    }
+
+    /// handle `info auxv`: the auxiliary vector the kernel passed the inferior
+    fn info_auxv(&self) {
+        let inferior = match &self.inferior {
+            Some(inferior) => inferior,
+            None => return output::error("The program has no auxiliary information now."),
+        };
+        let entries = match procfs::auxv(inferior.pid()) {
+            Ok(entries) => entries,
+            Err(e) => return output::error(&format!("Unable to read auxv: {}", e)),
+        };
+        let state = match inferior.frame_state() {
+            Ok(state) => state,
+            Err(e) => return output::error(&e.to_string()),
+        };
+        let formatter = ValueFormatter::new(&self.debug_data, &state, &self.printers);
+        for (kind, value) in entries {
+            let (name, description, format) = procfs::auxv_type(kind);
+            let value = match format {
+                AuxvFormat::Hex => format!("{:#x}", value),
+                AuxvFormat::Decimal => value.to_string(),
+                AuxvFormat::Str => match formatter.read_c_string(value) {
+                    Ok(string) => format!("{:#x} \"{}\"", value, string),
+                    Err(_) => format!("{:#x}", value),
+                },
+            };
+            output::print(&format!(
+                "{:<4} {:<20} {:<30} {}",
+                kind, name, description, value
+            ));
+        }
+    }
+
+    /// where the inferior is stopped, or 0 (which is in no function, leaving only globals in
+    /// scope) if it isn't running
+    fn current_pc(&self) -> usize {
+        self.inferior
+            .as_ref()
+            .and_then(|inferior| getregs(inferior.pid()).ok())
+            .map_or(0, |regs| regs.rip as usize)
+    }
+
+    /// print how the inferior stopped, and where
+    fn print_stop(&mut self, stat: &Status) {
+        // a following `list` shows the code around the new location
+        self.last_listed = None;
+        let breakpoint = match stat {
+            Status::Stopped(signal::SIGTRAP, rip) => {
+                self.breakpoints.iter().position(|bp| *bp == Some(*rip))
+            }
+            _ => None,
+        };
+        let temporary = breakpoint.filter(|index| self.temporary_breakpoints.contains(index));
+        // a new stop cancels what is left of the previous command list, as after `continue`
+        self.pending_commands = breakpoint
+            .and_then(|index| self.breakpoint_commands.get(&index))
+            .cloned()
+            .unwrap_or_default()
+            .into();
+        if self
+            .pending_commands
+            .front()
+            .map(|command| command.as_str())
+            == Some("silent")
+        {
+            self.pending_commands.pop_front();
+            if let Some(index) = temporary {
+                self.delete_breakpoint(index);
+            }
+            self.refresh_tui();
+            return;
+        }
+        if output::is_json() {
+            self.stop_record(stat);
+            if let Some(index) = temporary {
+                self.delete_breakpoint(index);
+            }
+            self.do_displays();
+            return;
+        }
+        if let Status::Syscall(stop, _rip) = stat {
+            self.print_syscall_stop(*stop);
+        } else if let (Some(index), Status::Stopped(_, rip)) = (temporary, stat) {
+            output::print(&format!(
+                "Temporary breakpoint {}, {} ()",
+                index,
+                self.debug_data
+                    .get_function_from_addr(*rip)
+                    .unwrap_or_else(|| "??".to_string())
+            ));
+            self.delete_breakpoint(index);
+        } else {
+            output::print(&format!("Child {}", stat));
+        }
+        if let Status::Stopped(_, rip) | Status::Syscall(_, rip) = stat {
+            // with inlined calls not entered yet, the line is that of the first call
+            let line = self
+                .debug_data
+                .get_frames_at(*rip)
+                .into_iter()
+                .nth(self.inline_skip)
+                .and_then(|(_, line)| line);
+            if let Some(line) = line {
+                output::print(&format!("Stopped at {}", line));
+                self.print_stop_line(&line);
+            } else if let Some(name) = self.debug_data.symbols().describe(*rip) {
+                output::print(&format!("Stopped at {:#x} in {} ()", rip, name));
+            }
+        }
+        self.do_displays();
+        self.refresh_tui();
+    }
+
+    /// emit a JSON record describing how the inferior stopped
+    fn stop_record(&self, stat: &Status) {
+        match stat {
+            Status::Stopped(sig, rip) => {
+                let breakpoint = self.breakpoints.iter().position(|bp| *bp == Some(*rip));
+                let reason = if breakpoint.is_some() {
+                    "breakpoint"
+                } else {
+                    "signal"
+                };
+                output::record(
+                    "stopped",
+                    vec![
+                        ("reason", reason.into()),
+                        ("signal", sig.as_str().into()),
+                        ("breakpoint", breakpoint.into()),
+                        ("frame", self.frame_json(*rip)),
+                    ],
+                );
+            }
+            Status::Exited(code) => output::record("exited", vec![("code", (*code).into())]),
+            Status::Signaled(sig) => {
+                output::record("signaled", vec![("signal", sig.as_str().into())])
+            }
+            Status::Syscall(stop, rip) => {
+                let inferior = self.inferior.as_ref().unwrap();
+                let regs = match getregs(inferior.pid()) {
+                    Ok(regs) => regs,
+                    Err(e) => return output::error(&e.to_string()),
+                };
+                let syscall = Syscall::from_regs(&regs);
+                let (reason, ret) = match stop {
+                    SyscallStop::Entry => ("syscall-entry", None),
+                    SyscallStop::Exit => ("syscall-return", Some(regs.rax as i64)),
+                };
+                output::record(
+                    "stopped",
+                    vec![
+                        ("reason", reason.into()),
+                        ("syscall", syscall.name().into()),
+                        ("call", syscall.format_call(inferior, ret).into()),
+                        ("return", ret.map(|ret| syscall.format_return(ret)).into()),
+                        ("frame", self.frame_json(*rip)),
+                    ],
+                );
+            }
+        }
+    }
+
+    /// describe which syscalls a catchpoint stops on
+    fn describe_catchpoint(&self, index: usize) -> String {
+        let syscalls = &self.catchpoints[index];
+        if syscalls.is_empty() {
+            return "any syscall".to_string();
+        }
+        let names: Vec<String> = syscalls
+            .iter()
+            .map(|nr| match syscall::syscall_name(*nr) {
+                Some(name) => format!("'{}' [{}]", name, nr),
+                None => format!("{}", nr),
+            })
+            .collect();
+        format!("syscall {}", names.join(" "))
+    }
+
+    /// print the syscall the inferior stopped in, with its decoded arguments
+    fn print_syscall_stop(&self, stop: SyscallStop) {
+        let inferior = self.inferior.as_ref().unwrap();
+        let regs = match getregs(inferior.pid()) {
+            Ok(regs) => regs,
+            Err(e) => return output::error(&e.to_string()),
+        };
+        let syscall = Syscall::from_regs(&regs);
+        let index = self
+            .catchpoints
+            .iter()
+            .position(|syscalls| syscalls.is_empty() || syscalls.contains(&syscall.nr))
+            .unwrap_or(0);
+        match stop {
+            SyscallStop::Entry => output::print(&format!(
+                "Catchpoint {} (call to syscall {}), {}",
+                index,
+                syscall.name(),
+                syscall.format_call(inferior, None)
+            )),
+            SyscallStop::Exit => {
+                let ret = regs.rax as i64;
+                output::print(&format!(
+                    "Catchpoint {} (returned from syscall {}), {} = {}",
+                    index,
+                    syscall.name(),
+                    syscall.format_call(inferior, Some(ret)),
+                    syscall.format_return(ret)
+                ))
+            }
+        }
+    }
+
+    /// handle `break` and `tbreak`
+    fn break_at(&mut self, location: &str, temporary: bool) {
+        let breakpoints = self.resolve_location(location);
+        if breakpoints.is_empty() {
+            output::print(&format!("Function {} not defined!", location));
+        }
+        for bp in breakpoints {
+            self.set_breakpoint(bp, temporary);
+        }
+    }
+
+    /// find the addresses a location (`*addr`, a line number or a function) stands for. An
+    /// inlined function has one at each place it was inlined at.
+    fn resolve_location(&self, location: &str) -> Vec<usize> {
+        if location.starts_with('*') {
+            parse_address(location).into_iter().collect()
+        } else if let Ok(line) = location.parse::<usize>() {
+            self.debug_data
+                .get_addr_for_line(None, line)
+                .into_iter()
+                .collect()
+        } else {
+            self.debug_data.get_addrs_for_function(location)
+        }
+    }
+
+    /// add a breakpoint at `bp`, inserting it right away if the inferior is running
+    /// Returns the number of the new breakpoint.
+    fn set_breakpoint(&mut self, bp: usize, temporary: bool) -> Option<usize> {
+        if self.breakpoints.contains(&Some(bp)) {
+            output::print(&format!("Breakpoint {:#x} exists", bp));
+            return None;
+        }
+        if let Some(inferior) = self.inferior.as_mut() {
+            let orig_byte = match inferior.write_byte(bp, 0xcc) {
+                Ok(orig_byte) => orig_byte,
+                Err(_) => return None,
+            };
+            self.breakpoint_set.insert(
+                bp,
+                BreakPoint {
+                    addr: bp,
+                    orig_byte,
+                },
+            );
+        }
+        self.breakpoints.push(Some(bp));
+        if temporary {
+            self.temporary_breakpoints
+                .insert(self.breakpoints.len() - 1);
+            output::print(&format!(
+                "Temporary breakpoint {} at {:#x}",
+                self.breakpoints.len() - 1,
+                bp
+            ));
+        } else {
+            output::print(&format!(
+                "Set breakpoint {} at {:#x}",
+                self.breakpoints.len() - 1,
+                bp
+            ));
+        }
+        Some(self.breakpoints.len() - 1)
+    }
+
+    /// handle `commands [N]`: read the commands to run when breakpoint `N` (by default the last
+    /// one set) is hit, up to a line saying `end`
+    fn commands(&mut self, arg: Option<&str>) {
+        let index = match arg {
+            Some(arg) => match arg.parse::<usize>() {
+                Ok(index) => index,
+                Err(_) => {
+                    output::error(&format!("Bad breakpoint number '{}'", arg));
+                    return;
+                }
+            },
+            None => match self.breakpoints.len().checked_sub(1) {
+                Some(index) => index,
+                None => {
+                    output::error("No breakpoints specified.");
+                    return;
+                }
+            },
+        };
+        if self.breakpoints.get(index).copied().flatten().is_none() {
+            output::error(&format!("No breakpoint number {}.", index));
+            return;
+        }
+        if !output::is_json() {
+            output::print(&format!(
+                "Type commands for breakpoint {}, one per line.\nEnd with a line saying just \"end\".",
+                index
+            ));
+        }
+        let mut commands = Vec::new();
+        while let Some(line) = self.read_line(">") {
+            let line = line.trim();
+            if line == "end" {
+                break;
+            }
+            if !line.is_empty() {
+                commands.push(line.to_string());
+            }
+        }
+        if commands.is_empty() {
+            self.breakpoint_commands.remove(&index);
+        } else {
+            self.breakpoint_commands.insert(index, commands);
+        }
+    }
+
+    /// handle `dprintf location,"format",args...`: a breakpoint that prints and carries on
+    fn dprintf(&mut self, args: &str) {
+        let (location, format) = match args.split_once(',') {
+            Some((location, format)) => (location.trim(), format.trim()),
+            None => {
+                output::error("Format string required");
+                return;
+            }
+        };
+        if let Err(e) = printf::parse(format) {
+            output::error(&e);
+            return;
+        }
+        let addrs = self.resolve_location(location);
+        if addrs.is_empty() {
+            output::print(&format!("Function {} not defined!", location));
+        }
+        for addr in addrs {
+            if let Some(index) = self.set_breakpoint(addr, false) {
+                self.breakpoint_commands.insert(
+                    index,
+                    vec![
+                        "silent".to_string(),
+                        format!("printf {}", format),
+                        "continue".to_string(),
+                    ],
+                );
+            }
+        }
+    }
+
+    /// handle `printf "format", args...`
+    fn printf(&self, args: &str) {
+        let (pieces, exprs) = match printf::parse(args) {
+            Ok(parsed) => parsed,
+            Err(e) => return output::error(&e),
+        };
+        let mut text = String::new();
+        let mut exprs = exprs.iter();
+        for piece in pieces {
+            match piece {
+                printf::Piece::Text(s) => text.push_str(&s),
+                printf::Piece::Conversion(spec) => {
+                    let expr = exprs.next().unwrap();
+                    match self.printf_arg(expr, &spec) {
+                        Ok(arg) => text.push_str(&spec.format(arg)),
+                        Err(e) => return output::error(&e),
+                    }
+                }
+            }
+        }
+        output::write(&text);
+    }
+
+    /// read the value of `expr` (a variable or register) as the argument `spec` asks for
+    fn printf_arg(&self, expr: &str, spec: &printf::Spec) -> Result<Arg, String> {
+        let inferior = match &self.inferior {
+            Some(inferior) => inferior,
+            None => return Err("No child process is running!".to_string()),
+        };
+        let state = inferior.frame_state().map_err(|e| e.to_string())?;
+        if expr.starts_with('$') {
+            let value = register_value(&state.regs, expr)
+                .ok_or_else(|| format!("Unknown register \"{}\"", expr))?;
+            return Ok(if spec.is_signed() {
+                Arg::Signed(value as i64)
+            } else if spec.is_float() {
+                Arg::Float(f64::from_bits(value))
+            } else {
+                Arg::Unsigned(value)
+            });
+        }
+        let (var, func) = self
+            .debug_data
+            .get_variable(state.regs.rip as usize, expr)
+            .ok_or_else(|| format!("No symbol \"{}\" in current context.", expr))?;
+        let bytes = self
+            .debug_data
+            .read_variable(var, func, &state)
+            .map_err(|e| e.to_string())?;
+        let formatter = ValueFormatter::new(&self.debug_data, &state, &self.printers);
+        let value = Value::new(&var.entity_type, bytes);
+        let ty = formatter.resolve(value.ty);
+        let raw = value.as_u64();
+        if spec.is_string() {
+            // C strings are read through their pointer; anything else is shown like `print` does
+            return match ty.kind {
+                TypeKind::Pointer(_) if !ty.is_rust() => formatter
+                    .read_c_string(raw)
+                    .map(Arg::Str)
+                    .map_err(|e| e.to_string()),
+                _ => formatter
+                    .format(&value)
+                    .map(Arg::Str)
+                    .map_err(|e| e.to_string()),
+            };
+        }
+        if spec.is_float() {
+            return Ok(match (ty.is_float(), ty.size) {
+                (true, 4) => Arg::Float(f32::from_bits(raw as u32) as f64),
+                (true, _) => Arg::Float(f64::from_bits(raw)),
+                (false, _) => Arg::Float(raw as i64 as f64),
+            });
+        }
+        if spec.is_signed() {
+            let bits = ty.size.min(8) * 8;
+            return Ok(Arg::Signed(if bits == 0 || bits == 64 {
+                raw as i64
+            } else {
+                // sign extend from the type's width
+                ((raw << (64 - bits)) as i64) >> (64 - bits)
+            }));
+        }
+        Ok(Arg::Unsigned(raw))
+    }
+
+    /// remove breakpoint number `index`, restoring the code it replaced
+    fn delete_breakpoint(&mut self, index: usize) {
+        let bp = match self.breakpoints.get_mut(index).and_then(|bp| bp.take()) {
+            Some(bp) => bp,
+            None => return,
+        };
+        self.temporary_breakpoints.remove(&index);
+        self.breakpoint_commands.remove(&index);
+        if let Some(breakpoint) = self.breakpoint_set.remove(&bp) {
+            if let Some(inferior) = self.inferior.as_mut() {
+                inferior.write_byte(bp, breakpoint.orig_byte).ok();
+            }
+        }
+    }
+
+    /// handle `run`, `run&` and `start`: start the program from the beginning, stopping at the
+    /// start of main for `start`, and leaving it running in the background for `run&`
+    fn start_inferior(&mut self, args: &[String], stop_at_main: bool, background: bool) {
+        let (args, launch) = match self.launch(args) {
+            Ok(launch) => launch,
+            Err(e) => return output::error(&e),
+        };
+        self.to_kill();
+        if stop_at_main {
+            let main = self.debug_data.get_addrs_for_function("main");
+            if main.is_empty() {
+                output::error("No symbol \"main\" in current context.");
+                return;
+            }
+            for bp in main {
+                // stop after the prologue, where the arguments can be printed
+                let bp = match self.debug_data.get_function_at(bp) {
+                    Some(func) if func.address == bp => self.debug_data.get_body_addr(func),
+                    _ => bp,
+                };
+                self.set_breakpoint(bp, true);
+            }
+        }
+        let breakpoints: Vec<usize> = self.breakpoints.iter().flatten().copied().collect();
+        if let Some(inferior) = Inferior::new(
+            &self.target,
+            &args,
+            launch,
+            &breakpoints,
+            &mut self.breakpoint_set,
+        ) {
+            // Create the inferior
+            self.inferior = Some(inferior);
+            if background {
+                self.resume_background();
+                return;
+            }
+            match self.resume() {
+                Ok(stat) => self.print_stop(&stat),
+                Err(e) => output::error(&e.to_string()),
+            }
+        } else {
+            output::print("Error starting subprocess");
+        }
+    }
+
+    /// how to start the inferior: with the environment, working directory and terminal set for
+    /// it, and the redirections of its standard streams among the `run` arguments (`< in`,
+    /// `> out`, `>> out`, `2> err`, `2>>err` and `2>&1`). Returns the rest of the arguments.
+    fn launch(&self, args: &[String]) -> Result<(Vec<String>, Launch), String> {
+        let mut launch = Launch {
+            env: Some(self.environment.clone()),
+            cwd: self.cwd.clone(),
+            ..Launch::default()
+        };
+        if let Some(tty) = &self.tty {
+            let open = || {
+                OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .open(tty)
+                    .map_err(|e| format!("{}: {}", tty, e))
+            };
+            launch.stdin = Some(open()?);
+            launch.stdout = Some(open()?);
+            launch.stderr = Some(open()?);
+            launch.new_session = true;
+        }
+        let mut remaining = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg == "2>&1" {
+                let stdout = match &launch.stdout {
+                    Some(stdout) => stdout.try_clone(),
+                    None => io::stdout().as_fd().try_clone_to_owned().map(File::from),
+                };
+                launch.stderr = Some(stdout.map_err(|e| e.to_string())?);
+                continue;
+            }
+            let (op, path) = match ["2>>", "2>", ">>", ">", "<"]
+                .iter()
+                .find(|op| arg.starts_with(**op))
+            {
+                Some(op) => (*op, &arg[op.len()..]),
+                None => {
+                    remaining.push(arg.clone());
+                    continue;
+                }
+            };
+            // the file name may be a separate argument, as in `> out.txt`
+            let path = match path {
+                "" => args
+                    .next()
+                    .ok_or_else(|| format!("Missing file name after `{}'", op))?,
+                path => path,
+            };
+            // like a shell started in the inferior's working directory would
+            let full_path = match &self.cwd {
+                Some(cwd) => Path::new(cwd).join(path),
+                None => Path::new(path).to_path_buf(),
+            };
+            let file = match op {
+                "<" => File::open(&full_path),
+                ">" | "2>" => File::create(&full_path),
+                _ => OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(&full_path),
+            }
+            .map_err(|e| format!("{}: {}", path, e))?;
+            match op {
+                "<" => launch.stdin = Some(file),
+                ">" | ">>" => launch.stdout = Some(file),
+                _ => launch.stderr = Some(file),
+            }
+        }
+        Ok((remaining, launch))
+    }
+
+    /// handle `step` and `next`
+    fn step(&mut self, over: bool) {
+        if self.inferior.is_none() {
+            output::error("No child process is running!");
+            return;
+        }
+        match self.step_line(over) {
+            Ok(stat) => self.print_stop(&stat),
+            Err(e) => output::error(&e.to_string()),
+        }
+    }
+
+    /// handle `stepi` and `nexti`: execute `count` instructions, treating a call as a single
+    /// instruction when `over` is set
+    fn stepi(&mut self, count: usize, over: bool) {
+        if self.inferior.is_none() {
+            output::error("No child process is running!");
+            return;
+        }
+        let start_rip = self.current_pc();
+        match self.step_instructions(count, over) {
+            // only a plain step ending somewhere without a breakpoint gets the short report
+            Ok(Status::Stopped(signal::SIGTRAP, rip))
+                if !self.breakpoint_set.contains_key(&rip) && !output::is_json() =>
+            {
+                self.print_instruction_stop(start_rip, rip)
+            }
+            Ok(stat) => self.print_stop(&stat),
+            Err(e) => output::error(&e.to_string()),
+        }
+    }
+
+    /// execute up to `count` instructions, stopping early at a breakpoint or for any other
+    /// reason the inferior stops. With `over` set, a call runs until it returns.
+    fn step_instructions(&mut self, count: usize, over: bool) -> Result<Status, nix::Error> {
+        self.inline_skip = 0;
+        let pid = self.inferior.as_ref().unwrap().pid();
+        let mut status = Status::Stopped(signal::SIGTRAP, getregs(pid)?.rip as usize);
+        for _ in 0..count {
+            let regs = getregs(pid)?;
+            let insns = self.disassemble.instructions_at(regs.rip as usize, 2);
+            status = match insns.as_slice() {
+                [(_, text), (next_addr, _)] if over && text.starts_with("call") => {
+                    self.run_until(*next_addr, regs.rsp as usize)?
+                }
+                _ => {
+                    let inferior = self.inferior.as_mut().unwrap();
+                    inferior.step_instruction(&self.breakpoint_set)?
+                }
+            };
+            match status {
+                Status::Stopped(signal::SIGTRAP, rip)
+                    if !self.breakpoint_set.contains_key(&rip) => {}
+                _ => break,
+            }
+        }
+        Ok(status)
+    }
+
+    /// report where `stepi` or `nexti` left off: the source line if it is not the one the step
+    /// started from, then the instruction about to be executed
+    fn print_instruction_stop(&mut self, start_rip: usize, rip: usize) {
+        self.last_listed = None;
+        let same_line = self.line_number(start_rip) == self.line_number(rip)
+            && self
+                .debug_data
+                .get_function_at(start_rip)
+                .map(|func| func.address)
+                == self
+                    .debug_data
+                    .get_function_at(rip)
+                    .map(|func| func.address);
+        match self.debug_data.get_line_from_addr(rip) {
+            Some(line) if !same_line => {
+                output::print(&format!("Stopped at {}", line));
+                self.print_stop_line(&line);
+            }
+            Some(_) => (),
+            None => output::print(&format!("Stopped at {:#x}{}", rip, self.symbolic(rip))),
+        }
+        if let Some((addr, text)) = self.disassemble.instructions_at(rip, 1).pop() {
+            output::print(&format!("=> {:#x}{}:\t{}", addr, self.symbolic(addr), text));
+        }
+        self.do_displays();
+        self.refresh_tui();
+    }
+
+    /// run until the start of another source line. Calls made on the way are entered unless
+    /// `over` is set, or they have no line information; inlined calls count as calls too.
+    fn step_line(&mut self, over: bool) -> Result<Status, nix::Error> {
+        let pid = self.inferior.as_ref().unwrap().pid();
+        let start = getregs(pid)?;
+        let start_line = self.line_number(start.rip as usize);
+        let start_depth = self
+            .debug_data
+            .get_inlined_calls_at(start.rip as usize)
+            .len()
+            .saturating_sub(self.inline_skip);
+        if !over && self.inline_skip > 0 {
+            // the next inlined call starts right here, so just enter it
+            self.inline_skip -= 1;
+            return Ok(Status::Stopped(signal::SIGTRAP, start.rip as usize));
+        }
+        self.inline_skip = 0;
+
+        loop {
+            let regs = getregs(pid)?;
+            let insns = self.disassemble.instructions_at(regs.rip as usize, 2);
+            let (is_call, is_ret, next_addr) = match insns.as_slice() {
+                [(_, text), (next_addr, _)] => (
+                    text.starts_with("call"),
+                    text.starts_with("ret"),
+                    *next_addr,
+                ),
+                _ => (false, false, 0),
+            };
+
+            let status = if is_call && over {
+                self.run_until(next_addr, regs.rsp as usize)?
+            } else {
+                let inferior = self.inferior.as_mut().unwrap();
+                inferior.step_instruction(&self.breakpoint_set)?
+            };
+            let rip = match status {
+                Status::Stopped(signal::SIGTRAP, rip) => rip,
+                _ => return Ok(status),
+            };
+            if self.breakpoint_set.contains_key(&rip) {
+                return Ok(status);
+            }
+
+            if is_call && !over {
+                match self.debug_data.get_function_at(rip) {
+                    // stop once the function has set up its frame
+                    Some(func) if func.address == rip => {
+                        let body = self.debug_data.get_body_addr(func);
+                        return self.run_until(body, 0);
+                    }
+                    // no line information to step through
+                    _ => match self.run_until(next_addr, regs.rsp as usize)? {
+                        Status::Stopped(signal::SIGTRAP, rip) if rip == next_addr => (),
+                        status => return Ok(status),
+                    },
+                }
+            }
+            let rip = getregs(pid)?.rip as usize;
+
+            if is_ret && getregs(pid)?.rsp > start.rsp {
+                // returned from the function; a caller without line information (such as the
+                // C runtime calling main) just runs on
+                if self.line_number(rip).is_none() {
+                    return self.resume();
+                }
+                return Ok(Status::Stopped(signal::SIGTRAP, rip));
+            }
+
+            let depth = self.debug_data.get_inlined_calls_at(rip).len();
+            if depth > start_depth {
+                if over {
+                    continue;
+                }
+                // entered one or more inlined calls; stop in the outermost of them
+                self.inline_skip = depth - start_depth - 1;
+                return Ok(Status::Stopped(signal::SIGTRAP, rip));
+            }
+            if depth < start_depth {
+                // left an inlined call, in the middle of the line that calls it
+                return Ok(Status::Stopped(signal::SIGTRAP, rip));
+            }
+            let line = self.line_number(rip);
+            if line.is_some() && line != start_line && self.debug_data.is_line_start(rip) {
+                return Ok(Status::Stopped(signal::SIGTRAP, rip));
+            }
+        }
+    }
+
+    /// run until the current function (or inlined call) returns. Returns how the inferior
+    /// stopped, along with the value the function returned if it did so.
+    fn finish(&mut self) -> Result<Option<(Status, Option<String>)>, nix::Error> {
+        let inferior = self.inferior.as_ref().unwrap();
+        let regs = getregs(inferior.pid())?;
+        let pc = regs.rip as usize;
+        let calls = self.debug_data.get_inlined_calls_at(pc);
+        let depth = calls.len().saturating_sub(self.inline_skip);
+        let frames = self.debug_data.get_frames_at(pc);
+        let (name, line) = match frames.get(self.inline_skip) {
+            Some(frame) => frame,
+            None => {
+                output::error("Cannot find bounds of current function");
+                return Ok(None);
+            }
+        };
+        if depth == 0 && dwarf_data::is_main(name) {
+            output::error("\"finish\" not meaningful in the outermost frame.");
+            return Ok(None);
+        }
+        match line {
+            Some(line) => output::print(&format!("Run till exit from {} ({})", name, line)),
+            None => output::print(&format!("Run till exit from {}", name)),
+        }
+        self.inline_skip = 0;
+
+        if depth > 0 {
+            // inlined code has no return address to run to, so step until it is left
+            let ranges = calls[depth - 1].ranges.clone();
+            loop {
+                let regs = getregs(self.inferior.as_ref().unwrap().pid())?;
+                let insns = self.disassemble.instructions_at(regs.rip as usize, 2);
+                let status = match insns.as_slice() {
+                    [(_, text), (next_addr, _)] if text.starts_with("call") => {
+                        self.run_until(*next_addr, regs.rsp as usize)?
+                    }
+                    _ => {
+                        let inferior = self.inferior.as_mut().unwrap();
+                        inferior.step_instruction(&self.breakpoint_set)?
+                    }
+                };
+                let rip = match status {
+                    Status::Stopped(signal::SIGTRAP, rip) => rip,
+                    _ => return Ok(Some((status, None))),
+                };
+                let inside = ranges
+                    .iter()
+                    .any(|(begin, end)| *begin <= rip && rip < *end);
+                if !inside || self.breakpoint_set.contains_key(&rip) {
+                    return Ok(Some((status, None)));
+                }
+            }
+        }
+
+        let return_type = self
+            .debug_data
+            .get_function_at(pc)
+            .and_then(|func| func.return_type.clone());
+        let (ret, cfa) = match self.return_address()? {
+            Some(found) => found,
+            None => {
+                output::error("Cannot find bounds of current function");
+                return Ok(None);
+            }
+        };
+        let status = self.run_until(ret, cfa)?;
+        // a breakpoint on the way stops it before the function has returned
+        let value = match (&status, return_type) {
+            (Status::Stopped(signal::SIGTRAP, rip), Some(ty)) if *rip == ret => {
+                let inferior = self.inferior.as_ref().unwrap();
+                Some(match inferior.frame_state() {
+                    Ok(state) => self.return_value(&ty, &state),
+                    Err(e) => format!("<error: {}>", e),
+                })
+            }
+            _ => None,
+        };
+        Ok(Some((status, value)))
+    }
+
+    /// the value a function returning `ty` has just returned with the registers in `state`,
+    /// read from where the SysV x86-64 ABI puts it: xmm0 for floating point, rax and rdx for
+    /// anything else of up to 16 bytes, and the memory rax points to for bigger structs. Small
+    /// structs with floating-point members, which are partly returned in xmm registers, are not
+    /// told apart.
+    fn return_value(&self, ty: &Type, state: &FrameState) -> String {
+        let inferior = self.inferior.as_ref().unwrap();
+        let formatter = ValueFormatter::new(&self.debug_data, state, &self.printers);
+        let resolved = formatter.resolve(ty);
+        let bytes: Vec<u8> = if resolved.is_float() {
+            state.fpregs.xmm_space[..4]
+                .iter()
+                .flat_map(|word| word.to_le_bytes())
+                .take(resolved.size)
+                .collect()
+        } else if resolved.size <= 16 {
+            [state.regs.rax, state.regs.rdx]
+                .iter()
+                .flat_map(|reg| reg.to_le_bytes())
+                .take(resolved.size)
+                .collect()
+        } else {
+            match inferior.read_memory(state.regs.rax as usize, resolved.size) {
+                Ok(bytes) => bytes,
+                Err(e) => return format!("<error: {}>", e),
+            }
+        };
+        formatter
+            .format(&Value::new(ty, bytes))
+            .unwrap_or_else(|e| format!("<error: {}>", e))
+    }
+
+    /// handle `return [value]`: pop the current frame without running the rest of the
+    /// function, making it return `value` if there is one
+    fn return_now(&mut self, value: &str) -> Result<(), nix::Error> {
+        let inferior = self.inferior.as_ref().unwrap();
+        let state = inferior.frame_state()?;
+        let pc = state.regs.rip as usize;
+        if self.debug_data.get_inlined_calls_at(pc).len() > self.inline_skip {
+            output::error("Can not force return from an inlined function.");
+            return Ok(());
+        }
+        let caller = match state.caller(&self.debug_data) {
+            Some(caller) => caller,
+            None => {
+                output::error("Can not force return from the outermost frame.");
+                return Ok(());
+            }
+        };
+        // the caller's registers, except for those holding the return value
+        let mut regs = caller.regs;
+        let mut fpregs = state.fpregs;
+        if !value.is_empty() {
+            let return_type = self
+                .debug_data
+                .get_function_at(pc)
+                .and_then(|func| func.return_type.as_ref());
+            match return_type {
+                Some(ty) if ty.is_float() => {
+                    let number = match value.parse::<f64>() {
+                        Ok(number) => number,
+                        Err(_) => {
+                            output::error(&format!("Invalid number \"{}\".", value));
+                            return Ok(());
+                        }
+                    };
+                    let bits = if ty.size == 4 {
+                        (number as f32).to_bits() as u64
+                    } else {
+                        number.to_bits()
+                    };
+                    fpregs.xmm_space[0] = bits as u32;
+                    fpregs.xmm_space[1] = (bits >> 32) as u32;
+                }
+                _ => match parse_call_arg(value) {
+                    Some(CallArg::Int(number)) => regs.rax = number,
+                    _ => {
+                        output::error(&format!("Cannot evaluate return value \"{}\"", value));
+                        return Ok(());
+                    }
+                },
+            }
+        }
+        setregs(inferior.pid(), regs)?;
+        setfpregs(inferior.pid(), fpregs)?;
+        self.inline_skip = 0;
+        self.last_listed = None;
+
+        let state = inferior.frame_state()?;
+        let pc = state.regs.rip as usize;
+        // the return address may already belong to the line after the call
+        self.print_frame(0, &state, pc - 1, 0, false);
+        if let Some(line) = self.debug_data.get_line_from_addr(pc - 1) {
+            self.print_stop_line(&line);
+        }
+        self.do_displays();
+        self.refresh_tui();
+        Ok(())
+    }
+
+    /// handle `jump <location>`: carry on running from somewhere else in the current function
+    fn jump(&mut self, location: &str) -> Result<Option<Status>, nix::Error> {
+        let addr = match self.resolve_location(location).first() {
+            Some(addr) => *addr,
+            None => {
+                output::error(&format!("No line {} in the current file.", location));
+                return Ok(None);
+            }
+        };
+        let pid = self.inferior.as_ref().unwrap().pid();
+        let mut regs = getregs(pid)?;
+        let current = self.debug_data.get_function_at(regs.rip as usize);
+        let target = self.debug_data.get_function_at(addr);
+        // the frame would not fit the code of another function
+        if let Some(current) = current {
+            if target.is_none_or(|target| target.address != current.address) {
+                output::error(&format!("{} is not in `{}'.", location, current.name));
+                return Ok(None);
+            }
+        }
+        regs.rip = addr as u64;
+        setregs(pid, regs)?;
+        output::print(&format!("Continuing at {:#x}.", addr));
+        self.resume().map(Some)
+    }
+
+    /// the address the current function returns to, and its canonical frame address (the stack
+    /// pointer before the call)
+    fn return_address(&self) -> Result<Option<(usize, usize)>, nix::Error> {
+        let inferior = self.inferior.as_ref().unwrap();
+        let cfa = match self.debug_data.get_cfa(&inferior.frame_state()?) {
+            Some(cfa) => cfa as usize,
+            None => return Ok(None),
+        };
+        let ret = inferior.read_memory(cfa - 8, 8)?;
+        let ret = u64::from_le_bytes(ret.as_slice().try_into().unwrap()) as usize;
+        Ok(Some((ret, cfa)))
+    }
+
+    /// handle `until` without an argument: like `next`, but keep going when jumping back to an
+    /// earlier line, so that a loop runs to its end
+    fn until_next_line(&mut self) -> Result<Status, nix::Error> {
+        let pid = self.inferior.as_ref().unwrap().pid();
+        let start = getregs(pid)?;
+        let start_line = self.line_number(start.rip as usize);
+        let start_func = self
+            .debug_data
+            .get_function_at(start.rip as usize)
+            .map(|func| func.address);
+        loop {
+            let status = self.step_line(true)?;
+            let rip = match status {
+                Status::Stopped(signal::SIGTRAP, rip) => rip,
+                _ => return Ok(status),
+            };
+            let returned = getregs(pid)?.rsp > start.rsp
+                || self
+                    .debug_data
+                    .get_function_at(rip)
+                    .map(|func| func.address)
+                    != start_func;
+            if returned
+                || self.breakpoint_set.contains_key(&rip)
+                || self.line_number(rip) > start_line
+            {
+                return Ok(status);
+            }
+        }
+    }
+
+    /// handle `advance` and `until` with a location: run until the location is reached, or the
+    /// current function returns. `until` only stops at the location in the current frame or one
+    /// of its callers, not in deeper recursive calls.
+    fn advance(&mut self, location: &str, in_frame: bool) -> Result<Option<Status>, nix::Error> {
+        let addrs = self.resolve_location(location);
+        if addrs.is_empty() {
+            output::error(&format!("Function {} not defined!", location));
+            return Ok(None);
+        }
+        let min_sp = if in_frame {
+            getregs(self.inferior.as_ref().unwrap().pid())?.rsp as usize
+        } else {
+            0
+        };
+        let mut stops: Vec<(usize, usize)> = addrs.into_iter().map(|addr| (addr, min_sp)).collect();
+        if let Some((ret, cfa)) = self.return_address()? {
+            stops.push((ret, cfa));
+        }
+        self.inline_skip = 0;
+        self.run_until_any(&stops).map(Some)
+    }
+
+    /// let the inferior run until it gets to `addr` with a stack pointer of at least `min_sp`
+    /// (so that recursive calls don't count), or stops for any other reason
+    fn run_until(&mut self, addr: usize, min_sp: usize) -> Result<Status, nix::Error> {
+        self.run_until_any(&[(addr, min_sp)])
+    }
+
+    /// like `run_until`, with several places to stop at, each with its own minimum stack pointer
+    fn run_until_any(&mut self, stops: &[(usize, usize)]) -> Result<Status, nix::Error> {
+        let inferior = self.inferior.as_mut().unwrap();
+        let mut breakpoint_set = self.breakpoint_set.clone();
+        let mut temporary = Vec::new();
+        for &(addr, _) in stops {
+            if let Entry::Vacant(entry) = breakpoint_set.entry(addr) {
+                let orig_byte = inferior.write_byte(addr, 0xcc)?;
+                entry.insert(BreakPoint { addr, orig_byte });
+                temporary.push(addr);
+            }
+        }
+        let status = loop {
+            let status = inferior.cont_exec(&breakpoint_set)?;
+            if let Status::Stopped(signal::SIGTRAP, rip) = status {
+                let sp = getregs(inferior.pid())?.rsp as usize;
+                let too_deep = stops
+                    .iter()
+                    .any(|&(addr, min_sp)| rip == addr && sp < min_sp);
+                if too_deep && !self.breakpoint_set.contains_key(&rip) {
+                    continue;
+                }
+            }
+            break status;
+        };
+        // an exited program has no code left to restore
+        if let Status::Stopped(_, _) | Status::Syscall(_, _) = status {
+            for addr in temporary {
+                inferior.write_byte(addr, breakpoint_set[&addr].orig_byte)?;
+            }
+        }
+        Ok(status)
+    }
+
+    /// let the inferior run until it stops. With catchpoints set, this traces system calls and
+    /// keeps going past those no catchpoint is interested in.
+    fn resume(&mut self) -> Result<Status, nix::Error> {
+        self.inline_skip = 0;
+        let inferior = self.inferior.as_mut().unwrap();
+        if self.catchpoints.is_empty() {
+            return inferior.cont_exec(&self.breakpoint_set);
+        }
+        loop {
+            let status = inferior.syscall_exec(&self.breakpoint_set, None)?;
+            if let Status::Syscall(_, _) = status {
+                if !caught(&self.catchpoints, getregs(inferior.pid())?.orig_rax) {
+                    continue;
+                }
+            }
+            return Ok(status);
+        }
+    }
+
+    /// handle `continue&`: let the inferior run while more commands are read. Its stopping is
+    /// reported by `poll_background`.
+    fn resume_background(&mut self) {
+        self.inline_skip = 0;
+        let inferior = self.inferior.as_mut().unwrap();
+        match inferior.cont_background(&self.breakpoint_set, !self.catchpoints.is_empty()) {
+            Ok(Some(stat)) => self.print_stop(&stat),
+            Ok(None) => {
+                self.running = true;
+                if output::is_json() {
+                    output::record("running", Vec::new());
+                }
+            }
+            Err(e) => output::error(&e.to_string()),
+        }
+    }
+
+    /// report the inferior running in the background stopping, if it has. With the prompt
+    /// showing (`at_prompt`), the report starts on a line of its own and the prompt is shown
+    /// again after it.
+    fn poll_background(&mut self, at_prompt: bool) {
+        if !self.running {
+            return;
+        }
+        let inferior = self.inferior.as_mut().unwrap();
+        let mut status = inferior.poll(&self.breakpoint_set);
+        // pass over system calls no catchpoint is interested in, as `resume` does
+        while let Ok(Some(Status::Syscall(_, _))) = status {
+            match getregs(inferior.pid()) {
+                Ok(regs) if !caught(&self.catchpoints, regs.orig_rax) => {
+                    status = inferior.cont_background(&self.breakpoint_set, true);
+                }
+                _ => break,
+            }
+        }
+        let status = match status {
+            Ok(Some(status)) => status,
+            Ok(None) => return,
+            Err(e) => {
+                self.running = false;
+                return output::error(&e.to_string());
+            }
+        };
+        self.running = false;
+        // a prompt is only shown on a terminal
+        let interactive = at_prompt && !output::is_json() && isatty(0).unwrap_or(false);
+        if interactive {
+            output::print("");
+        }
+        self.print_stop(&status);
+        if interactive {
+            output::write("(deet) ");
+        }
+    }
+
+    /// wait for `read` to read a line of input on another thread, checking on the inferior
+    /// running in the background meanwhile so that it stopping is reported right away
+    fn read_while_running<T: Send + 'static>(
+        &mut self,
+        read: impl FnOnce() -> T + Send + 'static,
+    ) -> T {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || sender.send(read()));
+        loop {
+            match receiver.recv_timeout(POLL_INTERVAL) {
+                Ok(input) => return input,
+                Err(RecvTimeoutError::Timeout) => self.poll_background(true),
+                Err(RecvTimeoutError::Disconnected) => panic!("Input thread exited"),
+            }
+        }
+    }
+
+    /// describe the code location `addr` as a JSON object
+    fn frame_json(&self, addr: usize) -> Json {
+        let line = self.debug_data.get_line_from_addr(addr);
+        Json::Object(vec![
+            ("addr", format!("{:#x}", addr).into()),
+            (
+                "function",
+                self.debug_data.get_function_from_addr(addr).into(),
+            ),
+            ("file", line.as_ref().map(|line| line.file.clone()).into()),
+            ("line", line.as_ref().map(|line| line.number).into()),
+        ])
+    }
+
+    /// handle `set <option> <args...>`
+    fn set(&mut self, args: &[String]) {
+        match args.first().map(|arg| arg.as_str()) {
+            Some("substitute-path") => {
+                if args.len() == 1 {
+                    for (from, to) in self.sources.substitutions() {
+                        output::print(&format!("`{}' -> `{}'", from, to));
+                    }
+                } else if args.len() == 3 {
+                    self.sources.add_substitution(&args[1], &args[2]);
+                } else {
+                    output::error("Usage: set substitute-path <from> <to>");
+                }
+            }
+            Some("debug-file-directory") => {
+                if args.len() == 1 {
+                    output::print(&format!(
+                        "The directory where separate debug symbols are searched for is \"{}\".",
+                        self.debug_file_directories.join(":")
+                    ));
+                    return;
+                }
+                self.debug_file_directories = args[1..]
+                    .join(" ")
+                    .split(':')
+                    .filter(|dir| !dir.is_empty())
+                    .map(|dir| dir.to_string())
+                    .collect();
+                // the separate debug file may be found now, or be a different one
+                if let Some(debug_data) =
+                    load_debug_data(&self.target, &self.debug_file_directories)
+                {
+                    self.debug_data = debug_data;
+                }
+            }
+            Some("environment") => {
+                let assignment = args[1..].join(" ");
+                if assignment.is_empty() {
+                    for (name, value) in &self.environment {
+                        output::print(&format!("{}={}", name, value));
+                    }
+                    return;
+                }
+                // either `VAR=value` or `VAR value`
+                let (name, value) = match assignment.split_once('=') {
+                    Some((name, value)) => (name.trim(), value.trim()),
+                    None => assignment
+                        .split_once(' ')
+                        .map_or((assignment.as_str(), ""), |(name, value)| {
+                            (name, value.trim())
+                        }),
+                };
+                if value.is_empty() {
+                    output::print(&format!(
+                        "Setting environment variable \"{}\" to null value.",
+                        name
+                    ));
+                }
+                match self.environment.iter_mut().find(|(var, _)| var == name) {
+                    Some(var) => var.1 = value.to_string(),
+                    None => self.environment.push((name.to_string(), value.to_string())),
+                }
+            }
+            Some("cwd") => {
+                if args.len() == 1 {
+                    match &self.cwd {
+                        Some(cwd) => output::print(&format!(
+                            "Current working directory that will be used when starting the \
+                             inferior is \"{}\".",
+                            cwd
+                        )),
+                        None => output::print(
+                            "You have not set the inferior's current working directory.\n\
+                             The inferior will inherit deet's cwd.",
+                        ),
+                    }
+                    return;
+                }
+                let dir = args[1..].join(" ");
+                self.cwd = Some(match dir.strip_prefix('~') {
+                    Some(rest) => format!("{}{}", std::env::var("HOME").unwrap_or_default(), rest),
+                    None => dir,
+                });
+            }
+            Some("inferior-tty") => {
+                let tty = args[1..].join(" ");
+                self.set_tty(Some(tty.as_str()).filter(|tty| !tty.is_empty()));
+            }
+            Some(option) => output::error(&format!("Unknown option \"{}\"", option)),
+            None => output::error("Usage: set <option> <value>"),
+        }
+    }
+
+    /// handle `unset environment [VAR]`: remove a variable from the inferior's environment,
+    /// or all of them
+    fn unset(&mut self, args: &[String]) {
+        match args.first().map(|arg| arg.as_str()) {
+            Some("environment") => match args.get(1) {
+                Some(name) => self.environment.retain(|(var, _)| var != name),
+                None => self.environment.clear(),
+            },
+            Some(option) => output::error(&format!("Unknown option \"{}\"", option)),
+            None => output::error("Usage: unset environment [VAR]"),
+        }
+    }
+
+    /// handle `tty [terminal]`: show or set the terminal the inferior is started on, which
+    /// keeps it from competing with deet's prompt for input
+    fn set_tty(&mut self, tty: Option<&str>) {
+        match tty {
+            Some(tty) => self.tty = Some(tty.to_string()),
+            None => output::print(&format!(
+                "Terminal for future runs of program being debugged is \"{}\".",
+                self.tty.as_deref().unwrap_or("")
+            )),
+        }
+    }
+
+    fn source_path(&self) -> String {
+        let mut dirs: Vec<String> = self
+            .sources
+            .directories()
+            .iter()
+            .map(|dir| dir.display().to_string())
+            .collect();
+        dirs.push("$cdir".to_string());
+        dirs.join(":")
+    }
+
+    /// line the inferior is stopped at, if there is a live inferior
+    fn current_line(&self) -> Option<Line> {
+        let regs = getregs(self.inferior.as_ref()?.pid()).ok()?;
+        self.debug_data.get_line_from_addr(regs.rip as usize)
+    }
+
+    /// resolve a `list` argument (`N`, `file.c:N`, `func` or `*addr`) to a file and line
+    fn list_location(&self, arg: &str) -> Option<(String, usize)> {
+        if arg.starts_with('*') {
+            let line = self.debug_data.get_line_from_addr(parse_address(arg)?)?;
+            return Some((line.file, line.number));
+        }
+        if let Some((file, number)) = arg.rsplit_once(':') {
+            let number = number.parse::<usize>().ok()?;
+            // go through the line table so that the file name matches the one in stop locations
+            let file = self
+                .debug_data
+                .get_addr_for_line(Some(file), number)
+                .and_then(|addr| self.debug_data.get_line_from_addr(addr))
+                .map(|line| line.file)
+                .unwrap_or_else(|| file.to_string());
+            return Some((file, number));
+        }
+        if let Ok(number) = arg.parse::<usize>() {
+            let file = match &self.last_listed {
+                Some((file, _)) => file.clone(),
+                None => self.default_list_location()?.0,
+            };
+            return Some((file, number));
+        }
+        let addr = self.debug_data.get_addr_for_function(None, arg)?;
+        let line = self.debug_data.get_line_from_addr(addr)?;
+        Some((line.file, line.number))
+    }
+
+    /// where `list` starts without an argument: the stop location, or else main
+    fn default_list_location(&self) -> Option<(String, usize)> {
+        let line = match self.current_line() {
+            Some(line) => line,
+            None => {
+                let addr = self.debug_data.get_addr_for_function(None, "main")?;
+                self.debug_data.get_line_from_addr(addr)?
+            }
+        };
+        Some((line.file, line.number))
+    }
+
+    fn list(&mut self, arg: Option<&str>) {
+        let (file, first) = match arg {
+            Some(arg) => match self.list_location(arg) {
+                Some((file, number)) => (file, number.saturating_sub(LIST_SIZE / 2).max(1)),
+                None => {
+                    output::error(&format!("No line or function matches \"{}\"", arg));
+                    return;
+                }
+            },
+            None => match self.last_listed.take() {
+                Some((file, last)) => (file, last + 1),
+                None => match self.default_list_location() {
+                    Some((file, number)) => (file, number.saturating_sub(LIST_SIZE / 2).max(1)),
+                    None => {
+                        output::error("No source location to list");
+                        return;
+                    }
+                },
+            },
+        };
+
+        let current = self
+            .current_line()
+            .filter(|line| line.file == file)
+            .map(|line| line.number);
+        let last = first + LIST_SIZE - 1;
+        if let Some(printed) = self.print_source(&file, first, last, current) {
+            self.last_listed = Some((file, printed));
+        } else {
+            // keep the position, so that running off the end doesn't restart the listing
+            self.last_listed = Some((file, first - 1));
+        }
+    }
+
+    /// print lines `first..=last` of `file`, marking the `current` line and lines with
+    /// breakpoints. Returns the number of the last line printed.
+    fn print_source(
+        &mut self,
+        file: &str,
+        first: usize,
+        last: usize,
+        current: Option<usize>,
+    ) -> Option<usize> {
+        let lines = match self.source_lines(file, first, last, current) {
+            Ok(lines) => lines,
+            Err(e) => {
+                output::error(&e.to_string());
+                return None;
+            }
+        };
+        for (_number, text) in &lines {
+            output::print(text);
+        }
+        lines.last().map(|(number, _text)| *number)
+    }
+
+    /// print the line the inferior stopped at. A missing source file is only reported the first
+    /// time, rather than at every stop.
+    fn print_stop_line(&mut self, line: &Line) {
+        if !self.sources.is_missing(&line.file) {
+            self.print_source(&line.file, line.number, line.number, Some(line.number));
+        }
+    }
+
+    /// format lines `first..=last` of `file` (or as many of them as exist) for display, marking
+    /// the `current` line and lines with breakpoints
+    fn source_lines(
+        &mut self,
+        file: &str,
+        first: usize,
+        last: usize,
+        current: Option<usize>,
+    ) -> Result<Vec<(usize, String)>, String> {
+        let breakpoint_lines: Vec<usize> = self
+            .breakpoints
+            .iter()
+            .flatten()
+            .filter_map(|bp| self.debug_data.get_line_from_addr(*bp))
+            .filter(|line| line.file == file)
+            .map(|line| line.number)
+            .collect();
+        let comp_dir = self.debug_data.get_comp_dir(file);
+
+        let lines = match self.sources.lines(file, comp_dir) {
+            Some(lines) => lines,
+            None => return Err(format!("Cannot find source file {}", file)),
+        };
+        if first > lines.len() {
+            return Err(format!(
+                "Line number {} out of range; {} has {} lines.",
+                first,
+                file,
+                lines.len()
+            ));
+        }
+
+        let last = last.min(lines.len());
+        Ok((first..=last)
+            .map(|number| {
+                let text = format!(
+                    "{}{}{:>5}  {}",
+                    if breakpoint_lines.contains(&number) {
+                        "b"
+                    } else {
+                        " "
+                    },
+                    if current == Some(number) { ">" } else { " " },
+                    number,
+                    lines[number - 1]
+                );
+                (number, text)
+            })
+            .collect())
+    }
+
+    /// redraw the TUI windows for the inferior's current state, if the TUI is on
+    fn refresh_tui(&mut self) {
+        let (source_rows, lower_rows) = match &self.tui {
+            Some(tui) => (
+                tui.source_rows().saturating_sub(1),
+                tui.lower_rows().saturating_sub(1),
+            ),
+            None => return,
+        };
+
+        let mut source = Vec::new();
+        let location = match self.current_line() {
+            Some(line) => Some((line.file, line.number, Some(line.number))),
+            None => self
+                .default_list_location()
+                .map(|(file, number)| (file, number, None)),
+        };
+        match location {
+            Some((file, number, current)) => {
+                let first = number.saturating_sub(source_rows / 2).max(1);
+                match self.source_lines(&file, first, first + source_rows - 1, current) {
+                    Ok(lines) => {
+                        for (number, text) in lines {
+                            source.push(PaneLine::new(text, Some(number) == current));
+                        }
+                    }
+                    Err(e) => source.push(PaneLine::new(e, false)),
+                }
+            }
+            None => source.push(PaneLine::new("[ No Source Available ]".to_string(), false)),
+        }
+
+        let mut disassembly = Vec::new();
+        let mut registers = Vec::new();
+        let regs = self
+            .inferior
+            .as_ref()
+            .and_then(|inferior| getregs(inferior.pid()).ok());
+        if let Some(regs) = regs {
+            let rip = regs.rip as usize;
+            // disassemble from the start of the function, so instruction boundaries are right
+            let start = self.function_start(rip).unwrap_or(rip);
+            let mut insns = self.disassemble.instructions_between(start, rip);
+            insns.extend(self.disassemble.instructions_at(rip, lower_rows));
+            let current = insns.iter().position(|(addr, _)| *addr == rip).unwrap_or(0);
+            let skip = current.saturating_sub(lower_rows / 2);
+            for (addr, text) in insns.into_iter().skip(skip).take(lower_rows) {
+                let marker = if self.breakpoint_set.contains_key(&addr) {
+                    "b"
+                } else {
+                    " "
+                };
+                disassembly.push(PaneLine::new(
+                    format!("{}{:#x}  {}", marker, addr, text),
+                    addr == rip,
+                ));
+            }
+
+            for name in TUI_REGISTERS.iter() {
+                let value = register_value(&regs, name).unwrap_or(0);
+                registers.push(PaneLine::new(format!("{:<7}{:#018x}", name, value), false));
+            }
+        } else {
+            disassembly.push(PaneLine::new("[ No Process ]".to_string(), false));
+        }
+
+        if let Some(tui) = self.tui.as_mut() {
+            tui.draw(&source, &disassembly, &registers);
+        }
+    }
+
+    /// address of the start of the function (or of the part of it) containing `addr`, from the
+    /// debug info if there is any, or else from the symbol table
+    fn function_start(&self, addr: usize) -> Option<usize> {
+        if let Some(func) = self.debug_data.get_function_at(addr) {
+            if func.ranges.is_empty() {
+                return Some(func.address);
+            }
+            return func
+                .ranges
+                .iter()
+                .find(|(begin, end)| *begin <= addr && addr < *end)
+                .map(|(begin, _)| *begin);
+        }
+        self.debug_data
+            .symbols()
+            .symbol_at(addr)
+            .map(|(symbol, _)| symbol.address)
+    }
+
+    /// continue by single stepping while recording, until a breakpoint is reached or the
+    /// inferior stops for another reason
+    fn record_cont(&mut self) {
+        self.inline_skip = 0;
+        let inferior = self.inferior.as_mut().unwrap();
+        let recorder = self.recorder.as_mut().unwrap();
+        loop {
+            match recorder.step(inferior, &self.disassemble, &self.breakpoint_set) {
+                Ok(Status::Stopped(signal::SIGTRAP, rip))
+                    if !self.breakpoint_set.contains_key(&rip) => {}
+                Ok(stat) => {
+                    if !matches!(stat, Status::Stopped(_, _)) {
+                        // the log can't be replayed against a process that is gone
+                        self.recorder = None;
+                    }
+                    self.print_stop(&stat);
+                    return;
+                }
+                Err(e) => {
+                    output::error(&e.to_string());
+                    return;
+                }
+            }
+        }
+    }
+
+    /// undo one recorded instruction, returns false once the start of the log is reached
+    fn reverse_step(&mut self) -> bool {
+        self.inline_skip = 0;
+        let inferior = self.inferior.as_mut().unwrap();
+        match self.recorder.as_mut().unwrap().reverse_step(inferior) {
+            Ok(true) => true,
+            Ok(false) => {
+                output::print("No more reverse-execution history.");
+                false
+            }
+            Err(e) => {
+                output::error(&e.to_string());
+                false
+            }
+        }
+    }
+
+    /// current instruction pointer of the inferior
+    fn recorder_rip(&self) -> usize {
+        getregs(self.inferior.as_ref().unwrap().pid())
+            .map(|regs| regs.rip as usize)
+            .unwrap_or(0)
+    }
+
+    /// go back to the start of the previous source line in the current frame, skipping over
+    /// any functions called from it
+    fn reverse_next(&mut self) -> Result<(), nix::Error> {
+        let regs = getregs(self.inferior.as_ref().unwrap().pid())?;
+        let start_line = self.line_number(regs.rip as usize);
+        let mut frame_sp = regs.rsp;
+
+        // find the last instruction of a different line in this frame (or a caller's)
+        let target_line = loop {
+            if !self.reverse_step() {
+                return Ok(());
+            }
+            let regs = getregs(self.inferior.as_ref().unwrap().pid())?;
+            let line = self.line_number(regs.rip as usize);
+            if regs.rsp >= frame_sp && line.is_some() && line != start_line {
+                frame_sp = regs.rsp;
+                break line;
+            }
+        };
+
+        // then go back to its first instruction, again skipping over any calls it made
+        while let Some(prev) = self.recorder.as_ref().unwrap().last_regs() {
+            let in_callee = prev.rsp < frame_sp;
+            if !in_callee && self.line_number(prev.rip as usize) != target_line {
+                break;
+            }
+            if !self.reverse_step() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn line_number(&self, addr: usize) -> Option<usize> {
+        self.debug_data
+            .get_line_from_addr(addr)
+            .map(|line| line.number)
+    }
+
+    /// print where reverse execution stopped
+    fn print_reverse_stop(&mut self) {
+        let rip = self.recorder_rip();
+        if output::is_json() {
+            output::record(
+                "stopped",
+                vec![
+                    ("reason", "reverse".into()),
+                    ("frame", self.frame_json(rip)),
+                ],
+            );
+            return;
+        }
+        match self.debug_data.get_line_from_addr(rip) {
+            Some(line) => output::print(&format!("Stopped at {} ({:#x})", line, rip)),
+            None => output::print(&format!("Stopped at {:#x}", rip)),
+        }
+        self.refresh_tui();
+    }
+
+    /// switch to a fresh copy of checkpoint `id`, so that the checkpoint itself can be restarted
+    /// again later, and re-apply the current breakpoint table to it
+    fn restart(&mut self, id: usize) {
+        let scratch_addr = self.disassemble.entry_point();
+        let checkpoint = match self.checkpoints.iter_mut().find(|c| c.id == id) {
+            Some(checkpoint) => checkpoint,
+            None => {
+                output::error(&format!("No checkpoint number {}", id));
+                return;
+            }
+        };
+        let mut inferior = match checkpoint.inferior.checkpoint(scratch_addr) {
+            Ok(inferior) => inferior,
+            Err(e) => {
+                output::error(&format!("Failed to restart checkpoint {}: {}", id, e));
+                return;
+            }
+        };
+
+        // the copy still has the breakpoints from when the checkpoint was taken
+        let mut breakpoint_set = HashMap::new();
+        for bp in checkpoint.breakpoint_set.values() {
+            inferior.write_byte(bp.addr, bp.orig_byte).ok();
+        }
+        for &bp in self.breakpoints.iter().flatten() {
+            if let Ok(orig_byte) = inferior.write_byte(bp, 0xcc) {
+                breakpoint_set.insert(
+                    bp,
+                    BreakPoint {
+                        addr: bp,
+                        orig_byte,
+                    },
+                );
+            }
+        }
+        self.breakpoint_set = breakpoint_set;
+        // the execution log belongs to the process being replaced
+        self.recorder = None;
+
+        if let Some(mut old) = self.inferior.replace(inferior) {
+            old.kill_inferior();
+        }
+        self.inline_skip = 0;
+        output::print(&format!(
+            "Switching to checkpoint {} (pid {})",
+            id,
+            self.inferior.as_ref().unwrap().pid()
+        ));
+        if let Some(line) = checkpoint.line.as_ref() {
+            output::print(&format!("Stopped at {}", line));
+        }
+        self.refresh_tui();
+    }
+
+    /// kill the current running inferior
+    fn to_kill(&mut self) {
+        self.recorder = None;
+        self.running = false;
+        self.inline_skip = 0;
+        for mut checkpoint in self.checkpoints.drain(..) {
+            checkpoint.inferior.kill_inferior();
+        }
+        if let Some(mut inferior) = self.inferior.take() {
+            self.breakpoints.clear();
+            self.temporary_breakpoints.clear();
+            self.breakpoint_commands.clear();
+            self.pending_commands.clear();
+            // the code the breakpoints replaced belongs to the killed process
+            self.breakpoint_set.clear();
+            let pid = inferior.pid();
+            inferior.kill_inferior();
+            output::print(&format!("Killing running inferior (pid {})", pid));
+        }
+    }
+
+    /// This function prompts the user to enter a command, and continues re-prompting until the user
+    /// enters a valid command. It uses DebuggerCommand::from_tokens to do the command parsing.
+    ///
+    /// You don't need to read, understand, or modify this function.
+    fn get_next_command(&mut self) -> DebuggerCommand {
+        loop {
+            // commands attached to the breakpoint just hit come first
+            if let Some(line) = self.pending_commands.pop_front() {
+                let tokens: Vec<&str> = line.split_whitespace().collect();
+                match DebuggerCommand::from_tokens(&tokens) {
+                    Some(cmd) => return cmd,
+                    None => output::error(&format!("Unrecognized command \"{}\".", line)),
+                }
+                continue;
+            }
+            if let Some(line) = self.typed_ahead.take() {
+                let tokens: Vec<&str> = line.split_whitespace().collect();
+                match DebuggerCommand::from_tokens(&tokens) {
+                    Some(cmd) => return cmd,
+                    None => output::error("Unrecognized command."),
+                }
+                continue;
+            }
+            let was_running = self.running;
+            if output::is_json() {
+                // tools get no prompt or line editing, a ready record says we are waiting
+                output::record("ready", Vec::new());
+                let line = if self.running {
+                    self.read_while_running(read_stdin_line)
+                } else {
+                    read_stdin_line()
+                };
+                let line = match line {
+                    Some(line) => line,
+                    None => return DebuggerCommand::Quit,
+                };
+                let tokens: Vec<&str> = line.split_whitespace().collect();
+                if tokens.is_empty() || was_running && self.hold_back(&line) {
+                    continue;
+                }
+                match DebuggerCommand::from_tokens(&tokens) {
+                    Some(cmd) => return cmd,
+                    None => output::error("Unrecognized command."),
+                }
+                continue;
+            }
+            // Print prompt and get next line of user input
+            let editor = Arc::clone(&self.readline);
+            let prompt = move || editor.lock().unwrap().readline("(deet) ");
+            let line = if self.running {
+                self.read_while_running(prompt)
+            } else {
+                prompt()
+            };
+            match line {
+                Err(ReadlineError::Interrupted) => {
+                    // User pressed ctrl+c. We're going to ignore it
+                    output::print("Type \"quit\" to exit");
+                }
+                Err(ReadlineError::Eof) => {
+                    // User pressed ctrl+d, which is the equivalent of "quit" for our purposes
+                    return DebuggerCommand::Quit;
+                }
+                Err(err) => {
+                    panic!("Unexpected I/O error: {:?}", err);
+                }
+                Ok(line) => {
+                    if line.trim().len() == 0 {
+                        continue;
+                    }
+                    let mut readline = self.readline.lock().unwrap();
+                    readline.add_history_entry(line.as_str());
+                    if let Err(err) = readline.save_history(&self.history_path) {
+                        output::print(&format!(
+                            "Warning: failed to save history file at {}: {}",
+                            self.history_path, err
+                        ));
+                    }
+                    drop(readline);
+                    if was_running && self.hold_back(&line) {
+                        continue;
+                    }
+                    let tokens: Vec<&str> = line.split_whitespace().collect();
+                    if let Some(cmd) = DebuggerCommand::from_tokens(&tokens) {
+                        return cmd;
+                    } else {
+                        output::print("Unrecognized command.");
+                    }
+                }
+            }
+        }
+    }
+
+    /// a breakpoint command list is not run while a line is being typed: when the program
+    /// stopped in the background meanwhile, the line just typed waits until the list has run
+    fn hold_back(&mut self, line: &str) -> bool {
+        self.poll_background(false);
+        if self.pending_commands.is_empty() {
+            return false;
+        }
+        self.typed_ahead = Some(line.to_string());
+        true
+    }
+
+    /// read a line of input for a command that takes more than one (such as `commands`), or
+    /// `None` at the end of input
+    fn read_line(&mut self, prompt: &str) -> Option<String> {
+        if output::is_json() {
+            return read_stdin_line();
+        }
+        let mut readline = self.readline.lock().unwrap();
+        match readline.readline(prompt) {
+            Ok(line) => {
+                readline.add_history_entry(line.as_str());
+                Some(line)
+            }
+            Err(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int_arg(arg: &str) -> Option<u64> {
+        match parse_call_arg(arg)? {
+            CallArg::Int(value) => Some(value),
+            CallArg::Str(_) => None,
+        }
+    }
+
+    #[test]
+    fn call_expr_without_args() {
+        let (name, args) = parse_call_expr("getpid()").unwrap();
+        assert_eq!(name, "getpid");
+        assert!(args.is_empty());
+        let (name, args) = parse_call_expr(" foo ( ) ").unwrap();
+        assert_eq!(name, "foo");
+        assert!(args.is_empty());
+    }
+
+    #[test]
+    fn call_expr_splits_args() {
+        let (name, args) = parse_call_expr("add(1, 0x2)").unwrap();
+        assert_eq!(name, "add");
+        assert_eq!(args, vec!["1", "0x2"]);
+    }
+
+    #[test]
+    fn call_expr_keeps_commas_in_literals() {
+        let (_, args) = parse_call_expr(r#"printf("%d, %d\n", ',', "a\"),")"#).unwrap();
+        assert_eq!(args, vec![r#""%d, %d\n""#, "','", r#""a\"),""#]);
+    }
+
+    #[test]
+    fn call_expr_keeps_empty_args() {
+        let (_, args) = parse_call_expr("f(1,)").unwrap();
+        assert_eq!(args, vec!["1", ""]);
+    }
+
+    #[test]
+    fn call_expr_rejects_malformed() {
+        assert!(parse_call_expr("foo").is_none());
+        assert!(parse_call_expr("foo(1").is_none());
+        assert!(parse_call_expr("(1)").is_none());
+    }
+
+    #[test]
+    fn call_args() {
+        assert_eq!(int_arg("42"), Some(42));
+        assert_eq!(int_arg("0X1f"), Some(0x1f));
+        assert_eq!(int_arg("-1"), Some(u64::MAX));
+        assert_eq!(int_arg("'a'"), Some(b'a' as u64));
+        assert_eq!(int_arg(r"'\n'"), Some(b'\n' as u64));
+        assert_eq!(int_arg("'ab'"), None);
+        assert_eq!(int_arg("x"), None);
+        match parse_call_arg(r#""a\tb""#) {
+            Some(CallArg::Str(s)) => assert_eq!(s, "a\tb"),
+            _ => panic!("expected a string argument"),
+        }
+    }
+
+    #[test]
+    fn moving_rip_resets_inline_skip() {
+        let target = std::env::temp_dir().join(format!("deet-helloworld-{}", std::process::id()));
+        let target = target.to_str().unwrap();
+        let compiled = std::process::Command::new("cc")
+            .args(["-g", "-o", target])
+            .arg(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/examples/helloworld.c"
+            ))
+            .status()
+            .unwrap();
+        assert!(compiled.success());
+        let mut debugger = Debugger::new(target);
+        debugger.inferior = Inferior::new(
+            target,
+            &Vec::new(),
+            Launch::default(),
+            &Vec::new(),
+            &mut debugger.breakpoint_set,
+        );
+        assert!(debugger.inferior.is_some());
+
+        // a short log makes `continue` stop after a few instructions
+        debugger.recorder = Some(Recorder::new(4));
+        debugger.inline_skip = 1;
+        debugger.record_cont();
+        assert_eq!(debugger.inline_skip, 0);
+
+        debugger.inline_skip = 1;
+        assert!(debugger.reverse_step());
+        assert_eq!(debugger.inline_skip, 0);
+
+        debugger.inline_skip = 1;
+        debugger.to_kill();
+        assert_eq!(debugger.inline_skip, 0);
+        std::fs::remove_file(target).unwrap();
+    }
+
+    #[test]
+    fn unescape_trailing_backslash() {
+        assert_eq!(unescape(r"a\"), "a\\");
+        assert_eq!(unescape(r"\0\q"), "\0q");
+    }
 }
@@ -1,15 +1,67 @@
 pub enum DebuggerCommand {
     Quit,
     Cont,
-    Back,
+    Back(Vec<String>),
     Break(String),
-    Info(String),
+    Info(Vec<String>),
     Run(Vec<String>),
+    RunBackground(Vec<String>),
+    ContBackground,
+    Interrupt,
     Disassemble(),
+    Call(String),
+    Print(String),
+    Display(Option<String>, String),
+    Undisplay(Vec<String>),
+    Ptype(String),
+    Checkpoint,
+    Restart(usize),
+    Record(Option<String>),
+    ReverseStepi(usize),
+    ReverseNext,
+    ReverseCont,
+    List(Option<String>),
+    Directory(Option<String>),
+    Set(Vec<String>),
+    Unset(Vec<String>),
+    Tty(Option<String>),
+    Tui(Option<String>),
+    Catch(Vec<String>),
+    Step,
+    Next,
+    Stepi(usize),
+    Nexti(usize),
+    Finish,
+    Return(String),
+    Jump(String),
+    TBreak(String),
+    Start(Vec<String>),
+    Until(Option<String>),
+    Advance(Option<String>),
+    Commands(Option<String>),
+    Printf(String),
+    Dprintf(String),
 }
 
 impl DebuggerCommand {
     pub fn from_tokens(tokens: &Vec<&str>) -> Option<DebuggerCommand> {
+        // `run&` and `continue&` (or `run &`) leave the program running in the background
+        if tokens.last().is_some_and(|token| token.ends_with('&')) {
+            let mut tokens = tokens.clone();
+            let last = tokens.pop()?.trim_end_matches('&');
+            if !last.is_empty() {
+                tokens.push(last);
+            }
+            return match tokens.first()? {
+                &"r" | &"run" => Some(DebuggerCommand::RunBackground(
+                    tokens[1..].iter().map(|s| s.to_string()).collect(),
+                )),
+                &"c" | &"cont" | &"continue" if tokens.len() == 1 => {
+                    Some(DebuggerCommand::ContBackground)
+                }
+                _ => None,
+            };
+        }
         match tokens[0] {
             "q" | "quit" => Some(DebuggerCommand::Quit),
             "r" | "run" => {
@@ -20,24 +72,211 @@ impl DebuggerCommand {
             }
             "c" | "cont" | "continue" => Some(DebuggerCommand::Cont),
 
-            "bt" | "back" | "backtrace" => Some(DebuggerCommand::Back),
+            "interrupt" => Some(DebuggerCommand::Interrupt),
+
+            "bt" | "back" | "backtrace" => Some(DebuggerCommand::Back(
+                tokens[1..].iter().map(|s| s.to_string()).collect(),
+            )),
 
             "b" | "break" => {
                 let arg = tokens[1].to_string();
                 Some(DebuggerCommand::Break(arg))
             }
 
+            "tb" | "tbreak" => Some(DebuggerCommand::TBreak(tokens.get(1)?.to_string())),
+
+            "start" => Some(DebuggerCommand::Start(
+                tokens[1..].iter().map(|s| s.to_string()).collect(),
+            )),
+
+            "u" | "until" => Some(DebuggerCommand::Until(
+                tokens.get(1).map(|arg| arg.to_string()),
+            )),
+
+            "advance" => Some(DebuggerCommand::Advance(
+                tokens.get(1).map(|arg| arg.to_string()),
+            )),
+
+            "commands" => Some(DebuggerCommand::Commands(
+                tokens.get(1).map(|arg| arg.to_string()),
+            )),
+
+            "printf" => Some(DebuggerCommand::Printf(tokens[1..].join(" "))),
+
+            "dprintf" => Some(DebuggerCommand::Dprintf(tokens[1..].join(" "))),
+
             "disassemble" => {
                 //let arg = tokens[1].to_string();
                 Some(DebuggerCommand::Disassemble())
             }
 
-            "info" => {
-                let arg = tokens[1].to_string();
-                Some(DebuggerCommand::Info(arg))
+            "call" => Some(DebuggerCommand::Call(tokens[1..].join(" "))),
+
+            "p" | "print" => Some(DebuggerCommand::Print(tokens[1..].join(" "))),
+
+            // the format may follow `display/` or be an argument of its own: `display /i $pc`
+            cmd if cmd == "display" || cmd.starts_with("display/") => {
+                let mut args = tokens[1..].to_vec();
+                let format = match cmd.strip_prefix("display/") {
+                    Some(format) => Some(format),
+                    None if args.first().is_some_and(|arg| arg.starts_with('/')) => {
+                        Some(&args.remove(0)[1..])
+                    }
+                    None => None,
+                };
+                Some(DebuggerCommand::Display(
+                    format.map(|format| format.to_string()),
+                    args.join(" "),
+                ))
+            }
+
+            "undisplay" => Some(DebuggerCommand::Undisplay(
+                tokens[1..].iter().map(|s| s.to_string()).collect(),
+            )),
+
+            "ptype" => Some(DebuggerCommand::Ptype(tokens[1..].join(" "))),
+
+            "checkpoint" => Some(DebuggerCommand::Checkpoint),
+
+            "restart" => {
+                let id = tokens.get(1)?.parse::<usize>().ok()?;
+                Some(DebuggerCommand::Restart(id))
+            }
+
+            "record" => Some(DebuggerCommand::Record(
+                tokens.get(1).map(|arg| arg.to_string()),
+            )),
+
+            "rsi" | "reverse-stepi" => {
+                let count = match tokens.get(1) {
+                    Some(count) => count.parse::<usize>().ok()?,
+                    None => 1,
+                };
+                Some(DebuggerCommand::ReverseStepi(count))
             }
+
+            "rn" | "reverse-next" => Some(DebuggerCommand::ReverseNext),
+
+            "rc" | "reverse-continue" => Some(DebuggerCommand::ReverseCont),
+
+            "l" | "list" => Some(DebuggerCommand::List(if tokens.len() > 1 {
+                Some(tokens[1..].join(" "))
+            } else {
+                None
+            })),
+
+            "dir" | "directory" => Some(DebuggerCommand::Directory(
+                tokens.get(1).map(|dir| dir.to_string()),
+            )),
+
+            "set" => Some(DebuggerCommand::Set(
+                tokens[1..].iter().map(|s| s.to_string()).collect(),
+            )),
+
+            "unset" => Some(DebuggerCommand::Unset(
+                tokens[1..].iter().map(|s| s.to_string()).collect(),
+            )),
+
+            "tty" => Some(DebuggerCommand::Tty(if tokens.len() > 1 {
+                Some(tokens[1..].join(" "))
+            } else {
+                None
+            })),
+
+            "tui" => Some(DebuggerCommand::Tui(
+                tokens.get(1).map(|arg| arg.to_string()),
+            )),
+
+            "catch" => Some(DebuggerCommand::Catch(
+                tokens[1..].iter().map(|s| s.to_string()).collect(),
+            )),
+
+            "s" | "step" => Some(DebuggerCommand::Step),
+
+            "n" | "next" => Some(DebuggerCommand::Next),
+
+            cmd @ ("si" | "stepi" | "ni" | "nexti") => {
+                let count = match tokens.get(1) {
+                    Some(count) => count.parse::<usize>().ok()?,
+                    None => 1,
+                };
+                if cmd.starts_with('s') {
+                    Some(DebuggerCommand::Stepi(count))
+                } else {
+                    Some(DebuggerCommand::Nexti(count))
+                }
+            }
+
+            "fin" | "finish" => Some(DebuggerCommand::Finish),
+
+            "return" => Some(DebuggerCommand::Return(tokens[1..].join(" "))),
+
+            "j" | "jump" => Some(DebuggerCommand::Jump(tokens.get(1)?.to_string())),
+
+            "info" => Some(DebuggerCommand::Info(
+                tokens[1..].iter().map(|s| s.to_string()).collect(),
+            )),
             // Default case:
             _ => None,
         }
     }
+
+    /// Whether the command can be used while recording. Of the commands that run the program,
+    /// only `continue` goes through the recorder; others (including `checkpoint`, which makes
+    /// the program call fork) would leave the execution log out of date, so that reverse
+    /// execution afterwards corrupts the program.
+    pub fn works_while_recording(&self) -> bool {
+        !matches!(
+            self,
+            DebuggerCommand::Step
+                | DebuggerCommand::Next
+                | DebuggerCommand::Stepi(_)
+                | DebuggerCommand::Nexti(_)
+                | DebuggerCommand::Finish
+                | DebuggerCommand::Until(_)
+                | DebuggerCommand::Advance(_)
+                | DebuggerCommand::Return(_)
+                | DebuggerCommand::Jump(_)
+                | DebuggerCommand::Checkpoint
+        )
+    }
+
+    /// Whether the command can be used while the program runs in the background, which rules
+    /// out anything that reads or changes its registers or memory. `set` and `unset` only
+    /// change deet's own settings.
+    pub fn works_while_running(&self) -> bool {
+        match self {
+            DebuggerCommand::Info(args) => matches!(
+                args.first().map(|arg| arg.as_str()),
+                Some(
+                    "b" | "breakpoints"
+                        | "record"
+                        | "checkpoints"
+                        | "functions"
+                        | "variables"
+                        | "types"
+                        | "symbol"
+                        | "proc"
+                        | "files"
+                        | "target"
+                        | "sections"
+                        | "display"
+                )
+            ),
+            _ => matches!(
+                self,
+                DebuggerCommand::Quit
+                    | DebuggerCommand::Interrupt
+                    | DebuggerCommand::Run(_)
+                    | DebuggerCommand::RunBackground(_)
+                    | DebuggerCommand::Start(_)
+                    | DebuggerCommand::Directory(_)
+                    | DebuggerCommand::Set(_)
+                    | DebuggerCommand::Unset(_)
+                    | DebuggerCommand::Tty(_)
+                    | DebuggerCommand::Commands(_)
+                    | DebuggerCommand::Undisplay(_)
+            ),
+        }
+    }
 }
@@ -1,6 +1,30 @@
-use object::{Object, ObjectSection};
+use object::target_lexicon::Architecture;
+use object::{Object, ObjectSection, SectionFlags};
 use std::fs;
+use capstone::arch::x86::X86OperandType;
 use capstone::prelude::*;
+use capstone::RegAccessType;
+
+use crate::inferior::register_value;
+use crate::output;
+use crate::symbols::SymbolTable;
+
+/// `sh_flags` bits of an ELF section header.
+const SHF_WRITE: u64 = 0x1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+const SHF_TLS: u64 = 0x400;
+
+/// An ELF section, as `info files` and `info sections` list it.
+pub struct SectionInfo {
+    pub name: String,
+    pub address: usize,
+    pub size: usize,
+    /// Where its contents are in the file, or `None` for `.bss`-like sections
+    pub file_offset: Option<usize>,
+    /// GDB's names for its flags: ALLOC, LOAD, READONLY, CODE, DATA, ...
+    pub flags: Vec<&'static str>,
+}
 
 pub struct DisassembleObject {
     dis_engine: Capstone,
@@ -27,7 +51,137 @@ impl DisassembleObject {
             
     }
 
-    pub fn disassemble(&self) {
+    /// Address of the ELF entry point (`_start`).
+    pub fn entry_point(&self) -> usize {
+        let objfile = object::File::parse(&self.filedump).expect("error on parsing file");
+        objfile.entry() as usize
+    }
+
+    /// ELF file format, in BFD's terms (`elf64-x86-64`).
+    pub fn file_format(&self) -> &'static str {
+        let objfile = object::File::parse(&self.filedump).expect("error on parsing file");
+        match (objfile.is_64(), objfile.architecture()) {
+            (true, Architecture::X86_64) => "elf64-x86-64",
+            (false, Architecture::I386) => "elf32-i386",
+            (true, _) => "elf64-little",
+            (false, _) => "elf32-little",
+        }
+    }
+
+    /// All sections of the program except the null section, in file order.
+    pub fn sections(&self) -> Vec<SectionInfo> {
+        let objfile = object::File::parse(&self.filedump).expect("error on parsing file");
+        objfile
+            .sections()
+            .filter_map(|section| {
+                let name = section.name()?;
+                if name.is_empty() {
+                    return None;
+                }
+                let sh_flags = match section.flags() {
+                    SectionFlags::Elf { sh_flags } => sh_flags,
+                    _ => 0,
+                };
+                let file_offset = section.file_range().map(|(offset, _)| offset as usize);
+                let alloc = sh_flags & SHF_ALLOC != 0;
+                let mut flags = Vec::new();
+                if alloc {
+                    flags.push("ALLOC");
+                }
+                if alloc && file_offset.is_some() {
+                    flags.push("LOAD");
+                }
+                if sh_flags & SHF_WRITE == 0 {
+                    flags.push("READONLY");
+                }
+                if sh_flags & SHF_EXECINSTR != 0 {
+                    flags.push("CODE");
+                } else if alloc && file_offset.is_some() {
+                    flags.push("DATA");
+                }
+                if sh_flags & SHF_TLS != 0 {
+                    flags.push("THREAD_LOCAL");
+                }
+                if file_offset.is_some() {
+                    flags.push("HAS_CONTENTS");
+                }
+                Some(SectionInfo {
+                    name: name.to_string(),
+                    address: section.address() as usize,
+                    size: section.size() as usize,
+                    file_offset,
+                    flags,
+                })
+            })
+            .collect()
+    }
+
+    /// Disassembles up to `count` instructions starting at `addr`. Returns each instruction's
+    /// address along with its text.
+    pub fn instructions_at(&self, addr: usize, count: usize) -> Vec<(usize, String)> {
+        self.instructions(addr, None, Some(count))
+    }
+
+    /// Disassembles the instructions in `start..end`, such as the code of a function up to some
+    /// address in it. Returns each instruction's address along with its text.
+    pub fn instructions_between(&self, start: usize, end: usize) -> Vec<(usize, String)> {
+        self.instructions(start, Some(end), None)
+    }
+
+    /// Disassembles from `addr` to `end` (or the end of its section), stopping after `count`
+    /// instructions if given.
+    fn instructions(
+        &self,
+        addr: usize,
+        end: Option<usize>,
+        count: Option<usize>,
+    ) -> Vec<(usize, String)> {
+        let objfile = object::File::parse(&self.filedump).expect("error on parsing file");
+        let addr = addr as u64;
+        let section = match objfile
+            .sections()
+            .find(|section| section.address() <= addr && addr < section.address() + section.size())
+        {
+            Some(section) => section,
+            None => return Vec::new(),
+        };
+        let data = section.data();
+        let start = (addr - section.address()) as usize;
+        // sections like .bss take up no room in the file
+        if start >= data.len() {
+            return Vec::new();
+        }
+        let stop = match end {
+            Some(end) => end.saturating_sub(section.address() as usize).clamp(start, data.len()),
+            None => data.len(),
+        };
+        let code = &data[start..stop];
+        let insns = match count {
+            Some(count) => self.dis_engine.disasm_count(code, addr, count),
+            None => self.dis_engine.disasm_all(code, addr),
+        };
+        let insns = match insns {
+            Ok(insns) => insns,
+            Err(_) => return Vec::new(),
+        };
+        insns
+            .iter()
+            .map(|insn| {
+                (
+                    insn.address() as usize,
+                    format!(
+                        "{} {}",
+                        insn.mnemonic().unwrap_or(""),
+                        insn.op_str().unwrap_or("")
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    /// Disassembles all of `.text`, with a label where each function starts and the target of
+    /// each call or jump named after its function.
+    pub fn disassemble(&self, symbols: &SymbolTable) {
         let objfile = object::File::parse(&*self.filedump).expect("error on parsing file");
         if let Some(section) = objfile.section_by_name(".text") {
             
@@ -37,11 +191,104 @@ impl DisassembleObject {
                 .expect("Failed to disassemble");
             
             for instruction in insns.as_ref() {
-                println!("{}", instruction);
+                let addr = instruction.address() as usize;
+                if let Some((symbol, 0)) = symbols.symbol_at(addr) {
+                    output::print(&format!("\n{:016x} <{}>:", addr, symbol.name));
+                }
+                let mnemonic = instruction.mnemonic().unwrap_or("");
+                let op_str = instruction.op_str().unwrap_or("");
+                let target = op_str
+                    .strip_prefix("0x")
+                    .and_then(|target| usize::from_str_radix(target, 16).ok())
+                    .filter(|_| mnemonic.starts_with("call") || mnemonic.starts_with('j'))
+                    .and_then(|target| symbols.describe(target));
+                match target {
+                    Some(name) => output::print(&format!("{} <{}>", instruction, name)),
+                    None => output::print(&format!("{}", instruction)),
+                }
             }
 
         } else {
-            eprintln!("Section not avaliable or incorrect section!");
+            output::error("Section not avaliable or incorrect section!");
+        }
+    }
+
+    /// Works out which memory the instruction at the start of `code` (located at `regs.rip`) is
+    /// about to write, given the registers it will execute with. Returns `(address, length)`
+    /// pairs. Only stores done by the CPU are found; memory written by the kernel during a
+    /// syscall is not.
+    pub fn memory_writes(&self, code: &[u8], regs: &libc::user_regs_struct) -> Vec<(usize, usize)> {
+        let mut writes = Vec::new();
+        let insns = match self.dis_engine.disasm_count(code, regs.rip, 1) {
+            Ok(insns) => insns,
+            Err(_) => return writes,
+        };
+        let insn = match insns.iter().next() {
+            Some(insn) => insn,
+            None => return writes,
+        };
+        let mnemonic = insn.mnemonic().unwrap_or("");
+
+        // push and call store below the stack pointer without listing it as an operand
+        if mnemonic.starts_with("push") || mnemonic == "call" {
+            writes.push((regs.rsp as usize - 8, 8));
         }
+
+        let detail = match self.dis_engine.insn_detail(insn) {
+            Ok(detail) => detail,
+            Err(_) => return writes,
+        };
+        let x86 = match detail.arch_detail() {
+            ArchDetail::X86Detail(x86) => x86,
+            _ => return writes,
+        };
+        for (i, operand) in x86.operands().enumerate() {
+            let mem = match operand.op_type {
+                X86OperandType::Mem(mem) => mem,
+                _ => continue,
+            };
+            // capstone reports the destination of some EVEX stores (`vmovdqu64 [rdi], zmm16`)
+            // as read, but the first operand of a move is always written
+            let stored = operand.access != Some(RegAccessType::ReadOnly)
+                || (i == 0 && mnemonic.contains("mov"));
+            if !stored || mnemonic == "lea" {
+                continue;
+            }
+
+            let reg = |id: RegId| {
+                if id.0 == 0 {
+                    return Some(0);
+                }
+                let name = self.dis_engine.reg_name(id)?;
+                if name == "rip" {
+                    // rip relative operands are relative to the next instruction
+                    return Some(regs.rip + insn.bytes().len() as u64);
+                }
+                register_value(regs, &name)
+            };
+            // in 64 bit mode only fs and gs (used for thread local storage) have a base
+            let segment = match self.dis_engine.reg_name(mem.segment()).as_deref() {
+                Some("fs") => regs.fs_base,
+                Some("gs") => regs.gs_base,
+                _ => 0,
+            };
+            let (base, index) = match (reg(mem.base()), reg(mem.index())) {
+                (Some(base), Some(index)) => (base, index),
+                _ => continue,
+            };
+            let addr = segment
+                .wrapping_add(base)
+                .wrapping_add(index.wrapping_mul(mem.scale() as u64))
+                .wrapping_add(mem.disp() as u64);
+
+            // a single step runs one iteration of a rep prefixed string instruction (none if
+            // rcx is 0), which stores one element at rdi whichever way the direction flag
+            // moves rdi afterwards
+            if mnemonic.starts_with("rep") && regs.rcx == 0 {
+                continue;
+            }
+            writes.push((addr as usize, operand.size as usize));
+        }
+        writes
     }
-}
\ No newline at end of file
+}
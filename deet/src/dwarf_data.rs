@@ -1,7 +1,11 @@
 use crate::gimli_wrapper;
+use crate::symbols::SymbolTable;
 use addr2line::Context;
-use object::Object;
+use gimli::UnwindSection;
+use object::{Object, ObjectSection};
+use std::collections::HashMap;
 use std::convert::TryInto;
+use std::path::{Path, PathBuf};
 use std::{fmt, fs};
 
 #[derive(Debug)]
@@ -12,7 +16,73 @@ pub enum Error {
 
 pub struct DwarfData {
     files: Vec<File>,
-    addr2line: Context<addr2line::gimli::EndianRcSlice<addr2line::gimli::RunTimeEndian>>,
+    types: HashMap<TypeId, Type>,
+    /// Absent when the DWARF could not be read
+    addr2line: Option<Context<gimli::EndianRcSlice<gimli::RunTimeEndian>>>,
+    /// ELF symbols, to fall back on for code without debugging information
+    symbols: SymbolTable,
+    /// Contents and address of `.eh_frame`, used to find the canonical frame address
+    eh_frame: Option<(Vec<u8>, u64)>,
+    endian: gimli::RunTimeEndian,
+    /// Separate file the DWARF was read from, for a stripped program
+    debug_file: Option<PathBuf>,
+}
+
+/// Where separate debug files are looked for unless `set debug-file-directory` says otherwise.
+pub const DEFAULT_DEBUG_FILE_DIRECTORY: &str = "/usr/lib/debug";
+
+/// DWARF register number of the return address column, which holds the program counter.
+pub const DWARF_PC_REGISTER: u16 = 16;
+
+/// DWARF register number of the stack pointer.
+pub const DWARF_SP_REGISTER: u16 = 7;
+
+/// DWARF register numbers of the registers a function must preserve for its caller (rbx, rbp
+/// and r12 to r15), which are all that is known of the caller's registers once it has been
+/// unwound to.
+pub const CALLEE_SAVED_REGISTERS: [u16; 6] = [3, 6, 12, 13, 14, 15];
+
+/// Access to the registers and memory of a stopped program, as needed to evaluate DWARF
+/// expressions.
+pub trait ProgramState {
+    /// Returns the value of a register, given its DWARF register number.
+    fn register(&self, register: u16) -> Option<u64>;
+
+    /// Reads `len` bytes of memory starting at `addr`.
+    fn read_memory(&self, addr: u64, len: usize) -> Option<Vec<u8>>;
+
+    fn pc(&self) -> u64 {
+        self.register(DWARF_PC_REGISTER).unwrap_or(0)
+    }
+}
+
+/// Reasons why the value of a variable cannot be shown.
+#[derive(Debug)]
+pub enum EvalError {
+    /// The compiler did not keep the variable around at the current program counter.
+    OptimizedOut,
+    /// The variable lives at an address that cannot be read.
+    Memory(u64),
+    /// The location needs something deet is unable to provide.
+    Unsupported(String),
+    Gimli(gimli::Error),
+}
+
+impl From<gimli::Error> for EvalError {
+    fn from(err: gimli::Error) -> Self {
+        EvalError::Gimli(err)
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::OptimizedOut => write!(f, "<optimized out>"),
+            EvalError::Memory(addr) => write!(f, "Cannot access memory at address {:#x}", addr),
+            EvalError::Unsupported(what) => write!(f, "{}", what),
+            EvalError::Gimli(err) => write!(f, "Error evaluating DWARF expression: {}", err),
+        }
+    }
 }
 
 impl fmt::Debug for DwarfData {
@@ -28,29 +98,150 @@ impl From<gimli_wrapper::Error> for Error {
 }
 
 impl DwarfData {
-    pub fn from_file(path: &str) -> Result<DwarfData, Error> {
-        let file = fs::File::open(path).or(Err(Error::ErrorOpeningFile))?;
-        let mmap = unsafe { memmap::Mmap::map(&file).or(Err(Error::ErrorOpeningFile))? };
+    /// Loads the debugging information of the program at `path`. If it has been stripped into a
+    /// separate file, that file is looked for under `debug_dirs` by build ID, and next to the
+    /// program and under `debug_dirs` by the name in `.gnu_debuglink`.
+    pub fn from_file(path: &str, debug_dirs: &[String]) -> Result<DwarfData, Error> {
+        DwarfData::load(path, Some(debug_dirs))
+    }
+
+    /// Loads only the ELF symbols and call frame information of `path`, leaving out the DWARF
+    /// (for when it can't be read).
+    pub fn from_symbols(path: &str) -> Result<DwarfData, Error> {
+        DwarfData::load(path, None)
+    }
+
+    fn load(path: &str, debug_dirs: Option<&[String]>) -> Result<DwarfData, Error> {
+        let mmap = map_file(Path::new(path))?;
         let object = object::File::parse(&*mmap)
             .or_else(|e| Err(gimli_wrapper::Error::ObjectError(e.to_string())))?;
+        let debug_file = debug_dirs.and_then(|dirs| find_debug_file(path, &object, dirs));
+        let debug_mmap = match &debug_file {
+            Some(debug_file) => Some(map_file(debug_file)?),
+            None => None,
+        };
+        let debug_object = match &debug_mmap {
+            Some(debug_mmap) => Some(
+                object::File::parse(debug_mmap)
+                    .map_err(|e| gimli_wrapper::Error::ObjectError(e.to_string()))?,
+            ),
+            None => None,
+        };
+        // the code (and `.eh_frame`) stays in the program, the DWARF moves to the debug file
+        let dwarf_object = debug_object.as_ref().unwrap_or(&object);
         let endian = if object.is_little_endian() {
             gimli::RunTimeEndian::Little
         } else {
             gimli::RunTimeEndian::Big
         };
+        let eh_frame = object
+            .section_by_name(".eh_frame")
+            .map(|section| (section.data().to_vec(), section.address()));
+        let (mut files, addr2line) = if debug_dirs.is_some() {
+            (
+                gimli_wrapper::load_file(dwarf_object, endian, path)?,
+                Some(gimli_wrapper::load_context(dwarf_object, endian)?),
+            )
+        } else {
+            (Vec::new(), None)
+        };
+        let objects: Vec<&object::File> = std::iter::once(&object).chain(&debug_object).collect();
+        let mut types = HashMap::new();
+        for file in &mut files {
+            types.extend(file.types.drain());
+        }
         Ok(DwarfData {
-            files: gimli_wrapper::load_file(&object, endian)?,
-            addr2line: Context::new(&object).or_else(|e| Err(gimli_wrapper::Error::from(e)))?,
+            files,
+            types,
+            addr2line,
+            symbols: SymbolTable::new(&objects),
+            eh_frame,
+            endian,
+            debug_file,
         })
     }
 
+    /// Returns the separate file the debugging information was read from, if any.
+    pub fn debug_file(&self) -> Option<&Path> {
+        self.debug_file.as_deref()
+    }
+
+    /// Whether there is any DWARF to give source lines, variables and types.
+    pub fn has_debug_info(&self) -> bool {
+        !self.files.is_empty()
+    }
+
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    /// Returns the address of the function called `func_name` in the ELF symbol table, or else
+    /// of its PLT entry if it is in a shared library.
+    fn get_symbol_addr(&self, func_name: &str) -> Option<usize> {
+        let find = |suffix| {
+            self.symbols
+                .iter()
+                .find(|symbol| match symbol.name.strip_suffix(suffix) {
+                    Some(name) => name_matches(name, func_name),
+                    None => false,
+                })
+                .map(|symbol| symbol.address)
+        };
+        find("").or_else(|| find("@plt"))
+    }
+
     #[allow(dead_code)]
     fn get_target_file(&self, file: &str) -> Option<&File> {
         self.files.iter().find(|f| {
-            f.name == file || (!file.contains("/") && f.name.ends_with(&format!("/{}", file)))
+            f.name == file
+                || (!file.contains("/") && f.name.ends_with(&format!("/{}", file)))
+                || Path::new(&f.directory).join(&f.name) == Path::new(file)
         })
     }
 
+    /// Returns the compilation units, each named after its main source file.
+    pub fn files(&self) -> &[File] {
+        &self.files
+    }
+
+    pub fn get_type(&self, id: TypeId) -> Option<&Type> {
+        self.types.get(&id)
+    }
+
+    pub fn types(&self) -> impl Iterator<Item = &Type> {
+        self.types.values()
+    }
+
+    /// Returns a type called `name`. Types defined in several compilation units are all the
+    /// same, so any of them does.
+    pub fn get_type_by_name(&self, name: &str) -> Option<&Type> {
+        self.types.values().find(|ty| ty.name == name)
+    }
+
+    /// Returns the addresses where the code of line `line_number` of `file` starts and ends,
+    /// or None if it has no code.
+    pub fn get_line_range(&self, file: &str, line_number: usize) -> Option<(usize, usize)> {
+        let mut rows: Vec<&Line> = self.get_target_file(file)?.lines.iter().collect();
+        rows.sort_by_key(|row| row.address);
+        let start = rows.iter().position(|row| row.number == line_number)?;
+        let end = rows[start..]
+            .iter()
+            .find(|row| row.number != line_number)
+            .map(|row| row.address)
+            .unwrap_or(rows[start].address);
+        Some((rows[start].address, end))
+    }
+
+    /// Returns the compilation directory of the compilation unit `file` belongs to.
+    pub fn get_comp_dir(&self, file: &str) -> Option<&str> {
+        let target_file = self.get_target_file(file).or_else(|| {
+            self.files
+                .iter()
+                .find(|f| !f.directory.is_empty() && file.starts_with(&f.directory))
+        })?;
+        Some(&target_file.directory)
+    }
+
     #[allow(dead_code)]
     pub fn get_addr_for_line(&self, file: Option<&str>, line_number: usize) -> Option<usize> {
         let target_file = match file {
@@ -68,112 +259,617 @@ impl DwarfData {
 
     #[allow(dead_code)]
     pub fn get_addr_for_function(&self, file: Option<&str>, func_name: &str) -> Option<usize> {
+        // functions that are only ever inlined still get an entry without any code
         match file {
             Some(filename) => Some(
                 self.get_target_file(filename)?
                     .functions
                     .iter()
-                    .find(|func| func.name == func_name)?
+                    .find(|func| name_matches(&func.name, func_name) && func.address != 0)?
                     .address,
             ),
             None => {
                 for file in &self.files {
-                    if let Some(func) = file.functions.iter().find(|func| func.name == func_name) {
+                    if let Some(func) = file
+                        .functions
+                        .iter()
+                        .find(|func| name_matches(&func.name, func_name) && func.address != 0)
+                    {
                         return Some(func.address);
                     }
                 }
-                None
+                self.get_symbol_addr(func_name)
             }
         }
     }
 
+    /// Returns every address the body of a function starts at: its out of line copy, if it has
+    /// one, and each place it was inlined at.
+    pub fn get_addrs_for_function(&self, func_name: &str) -> Vec<usize> {
+        let mut addrs = Vec::new();
+        for func in self.files.iter().flat_map(|file| file.functions.iter()) {
+            if name_matches(&func.name, func_name) && func.address != 0 {
+                addrs.push(func.address);
+            }
+            for call in &func.inlined_calls {
+                if name_matches(&call.name, func_name) && !addrs.contains(&call.entry_pc) {
+                    addrs.push(call.entry_pc);
+                }
+            }
+        }
+        if addrs.is_empty() {
+            addrs.extend(self.get_symbol_addr(func_name));
+        }
+        addrs
+    }
+
+    pub fn get_function(&self, func_name: &str) -> Option<&Function> {
+        self.files
+            .iter()
+            .flat_map(|file| file.functions.iter())
+            .find(|func| name_matches(&func.name, func_name) && func.address != 0)
+    }
+
+    /// Returns the function whose code contains `addr`.
+    pub fn get_function_at(&self, addr: usize) -> Option<&Function> {
+        self.files
+            .iter()
+            .flat_map(|file| file.functions.iter())
+            .find(|func| func.contains(addr))
+    }
+
+    /// Returns the address where the body of `func` starts, past the code that sets up its stack
+    /// frame: the second line table row of the function.
+    pub fn get_body_addr(&self, func: &Function) -> usize {
+        self.files
+            .iter()
+            .flat_map(|file| file.lines.iter())
+            .filter(|line| line.address > func.address && func.contains(line.address))
+            .map(|line| line.address)
+            .min()
+            .unwrap_or(func.address)
+    }
+
+    /// Whether a row of the line table starts at `addr`, i.e. it is the first instruction
+    /// generated for (part of) a line.
+    pub fn is_line_start(&self, addr: usize) -> bool {
+        self.files
+            .iter()
+            .flat_map(|file| file.lines.iter())
+            // line 0 marks code the compiler generated without a source line
+            .any(|line| line.address == addr && line.number != 0)
+    }
+
+    /// Returns the inlined calls the code at `addr` is part of, outermost first.
+    pub fn get_inlined_calls_at(&self, addr: usize) -> Vec<&InlinedCall> {
+        match self.get_function_at(addr) {
+            Some(func) => func
+                .inlined_calls
+                .iter()
+                .filter(|call| call.contains(addr))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Describes the code at `addr` as a list of frames, innermost first: one for each inlined
+    /// call it is part of, then one for the function they were all inlined into. Each frame
+    /// comes with the line it is executing, which for the callers of an inlined function is
+    /// the line of the call.
+    pub fn get_frames_at(&self, addr: usize) -> Vec<(String, Option<Line>)> {
+        let mut frames = Vec::new();
+        let mut line = self.get_line_from_addr(addr);
+        for call in self.get_inlined_calls_at(addr).iter().rev() {
+            let call_line = Line {
+                file: call.call_file.clone(),
+                number: call.call_line,
+                address: addr,
+            };
+            frames.push((call.name.clone(), line.replace(call_line)));
+        }
+        // without a line, the offset into the function is all there is to say where it is
+        let name = match line {
+            Some(_) => self.get_function_from_addr(addr),
+            None => self
+                .symbols
+                .describe(addr)
+                .or_else(|| self.get_function_from_addr(addr)),
+        };
+        if let Some(name) = name {
+            frames.push((name, line));
+        }
+        frames
+    }
+
+    /// Looks up a variable the way the program sees it at `pc`: locals of the function being
+    /// executed first, then globals.
+    pub fn get_variable(&self, pc: usize, name: &str) -> Option<(&Variable, Option<&Function>)> {
+        if let Some(func) = self.get_function_at(pc) {
+            // variables of inlined functions, innermost call first
+            for call in self.get_inlined_calls_at(pc).iter().rev() {
+                if let Some(var) = call.variables.iter().rev().find(|var| var.name == name) {
+                    return Some((var, Some(func)));
+                }
+            }
+            // later declarations are in inner scopes, and shadow earlier ones
+            if let Some(var) = func.variables.iter().rev().find(|var| var.name == name) {
+                return Some((var, Some(func)));
+            }
+        }
+        self.files
+            .iter()
+            .flat_map(|file| file.global_variables.iter())
+            .find(|var| var.name == name)
+            .map(|var| (var, None))
+    }
+
+    /// Works out the canonical frame address of the frame `state` describes, from the call
+    /// frame information in `.eh_frame`.
+    pub fn get_cfa(&self, state: &dyn ProgramState) -> Option<u64> {
+        self.unwind_row(state.pc(), |row| cfa_of(row, state))
+    }
+
+    /// Works out the registers of the caller of the frame `state` describes, from the call frame
+    /// information: the program counter it returns to, its stack pointer, and the callee-saved
+    /// registers this frame saved. Returns DWARF register numbers and values, or `None` without
+    /// unwind information for the frame.
+    pub fn unwind_frame(&self, state: &dyn ProgramState) -> Option<Vec<(u16, u64)>> {
+        self.unwind_row(state.pc(), |row| {
+            let cfa = cfa_of(row, state)?;
+            let mut regs = vec![(DWARF_SP_REGISTER, cfa)];
+            for register in CALLEE_SAVED_REGISTERS
+                .iter()
+                .chain(std::iter::once(&DWARF_PC_REGISTER))
+            {
+                let value = match row.register(gimli::Register(*register)) {
+                    gimli::RegisterRule::Offset(offset) => {
+                        let bytes = state.read_memory((cfa as i64 + offset) as u64, 8)?;
+                        u64::from_le_bytes(bytes.as_slice().try_into().unwrap())
+                    }
+                    gimli::RegisterRule::ValOffset(offset) => (cfa as i64 + offset) as u64,
+                    gimli::RegisterRule::Register(other) => state.register(other.0)?,
+                    // the outermost frame has no return address
+                    _ if *register == DWARF_PC_REGISTER => return None,
+                    _ => continue,
+                };
+                regs.push((*register, value));
+            }
+            Some(regs)
+        })
+    }
+
+    /// Runs `f` on the row of the call frame information that describes the code at `pc`.
+    fn unwind_row<T>(
+        &self,
+        pc: u64,
+        f: impl FnOnce(&gimli::UnwindTableRow<gimli::EndianSlice<gimli::RunTimeEndian>>) -> Option<T>,
+    ) -> Option<T> {
+        let (data, address) = self.eh_frame.as_ref()?;
+        let eh_frame = gimli::EhFrame::new(data, self.endian);
+        let bases = gimli::BaseAddresses::default().set_eh_frame(*address);
+        let mut ctx = gimli::UninitializedUnwindContext::new();
+        let row = eh_frame
+            .unwind_info_for_address(&bases, &mut ctx, pc, gimli::EhFrame::cie_from_offset)
+            .ok()?;
+        f(&row)
+    }
+
+    /// Reads the bytes of a variable's value. `func` is the function the variable belongs to,
+    /// which provides the frame base for local variables.
+    pub fn read_variable(
+        &self,
+        var: &Variable,
+        func: Option<&Function>,
+        state: &dyn ProgramState,
+    ) -> Result<Vec<u8>, EvalError> {
+        if let Location::Value(bytes) = &var.location {
+            return Ok(bytes.clone());
+        }
+        let cfa = self.get_cfa(state);
+        let frame_base = match func.and_then(|func| func.frame_base.as_ref()) {
+            Some(location) => {
+                let expr = location.at(state.pc() as usize)?;
+                match gimli_wrapper::evaluate_location(expr, state, None, cfa)? {
+                    gimli_wrapper::Place::Register(register) => state.register(register),
+                    gimli_wrapper::Place::Address(addr) => Some(addr),
+                }
+            }
+            None => None,
+        };
+        let expr = var.location.at(state.pc() as usize)?;
+        gimli_wrapper::read_location(expr, state, frame_base, cfa, var.entity_type.size)
+    }
+
     #[allow(dead_code)]
     pub fn get_line_from_addr(&self, curr_addr: usize) -> Option<Line> {
         let location = self
             .addr2line
-            .find_location(curr_addr.try_into().unwrap())
-            .ok()??;
+            .as_ref()
+            .and_then(|context| context.find_location(curr_addr.try_into().unwrap()).ok())
+            .flatten()
+            .filter(|location| location.file.is_some() && location.line.is_some());
+        if let Some(location) = location {
+            return Some(Line {
+                file: location.file?.to_string(),
+                number: location.line?.try_into().unwrap(),
+                address: curr_addr,
+            });
+        }
+        // addr2line skips DWARF 5 skeleton units, so look in our own line tables
+        let file = self
+            .files
+            .iter()
+            .find(|file| file.functions.iter().any(|func| func.contains(curr_addr)))?;
+        let line = file
+            .lines
+            .iter()
+            .filter(|line| line.address <= curr_addr)
+            .max_by_key(|line| line.address)?;
         Some(Line {
-            file: location.file?.to_string(),
-            number: location.line?.try_into().unwrap(),
+            file: Path::new(&file.directory)
+                .join(&file.name)
+                .to_string_lossy()
+                .to_string(),
+            number: line.number,
             address: curr_addr,
         })
     }
 
+    /// Returns the name of the function whose code contains `curr_addr`. For code inlined from
+    /// other functions, this is the function it was inlined into. Code without debugging
+    /// information is named after the ELF symbol it belongs to.
     #[allow(dead_code)]
     pub fn get_function_from_addr(&self, curr_addr: usize) -> Option<String> {
-        let frame = self
+        if let Some(func) = self.get_function_at(curr_addr) {
+            return Some(func.name.clone());
+        }
+        self.get_dwarf_function_from_addr(curr_addr).or_else(|| {
+            self.symbols
+                .symbol_at(curr_addr)
+                .map(|(symbol, _)| symbol.name.clone())
+        })
+    }
+
+    fn get_dwarf_function_from_addr(&self, curr_addr: usize) -> Option<String> {
+        // frames are listed innermost first, so the last one is the actual function
+        let mut frames = self
             .addr2line
+            .as_ref()?
             .find_frames(curr_addr.try_into().unwrap())
-            .ok()?
-            .next()
-            .ok()??;
-        Some(frame.function?.raw_name().ok()?.to_string())
+            .ok()?;
+        let mut function = None;
+        while let Some(frame) = frames.next().ok()? {
+            function = frame.function;
+        }
+        Some(function?.demangle().ok()?.to_string())
     }
+}
 
-    #[allow(dead_code)]
-    pub fn print(&self) {
-        for file in &self.files {
-            println!("------");
-            println!("{}", file.name);
-            println!("------");
-
-            println!("Global variables:");
-            for var in &file.global_variables {
-                println!(
-                    "  * {} ({}, located at {}, declared at line {})",
-                    var.name, var.entity_type.name, var.location, var.line_number
-                );
-            }
+fn map_file(path: &Path) -> Result<memmap::Mmap, Error> {
+    let file = fs::File::open(path).or(Err(Error::ErrorOpeningFile))?;
+    unsafe { memmap::Mmap::map(&file).or(Err(Error::ErrorOpeningFile)) }
+}
 
-            println!("Functions:");
-            for func in &file.functions {
-                println!(
-                    "  * {} (declared on line {}, located at {:#x}, {} bytes long)",
-                    func.name, func.line_number, func.address, func.text_length
-                );
-                for var in &func.variables {
-                    println!(
-                        "    * Variable: {} ({}, located at {}, declared at line {})",
-                        var.name, var.entity_type.name, var.location, var.line_number
-                    );
-                }
-            }
+/// Looks for the separate debug file of the program at `path` the way GDB does: first as
+/// `<dir>/.build-id/xx/yyyy.debug` for its build ID, then by the name in `.gnu_debuglink` in the
+/// program's directory, its `.debug` subdirectory, and the same directory under each of `dirs`.
+/// A file found through `.gnu_debuglink` must match its CRC.
+fn find_debug_file(path: &str, object: &object::File, dirs: &[String]) -> Option<PathBuf> {
+    if let Some(build_id) = object.build_id().filter(|id| id.len() > 1) {
+        let hex: String = build_id.iter().map(|b| format!("{:02x}", b)).collect();
+        let found = dirs
+            .iter()
+            .map(|dir| {
+                Path::new(dir)
+                    .join(".build-id")
+                    .join(&hex[..2])
+                    .join(format!("{}.debug", &hex[2..]))
+            })
+            .find(|candidate| candidate.is_file());
+        if found.is_some() {
+            return found;
+        }
+    }
 
-            println!("Line numbers:");
-            for line in &file.lines {
-                println!("  * {} (at {:#x})", line.number, line.address);
-            }
+    let (name, crc) = object.gnu_debuglink()?;
+    let name = std::str::from_utf8(name).ok()?;
+    let program = fs::canonicalize(path).ok()?;
+    let program_dir = program.parent()?;
+    let mut candidates = vec![
+        program_dir.join(name),
+        program_dir.join(".debug").join(name),
+    ];
+    for dir in dirs {
+        // the program's directory is absolute, so it can't be joined as it is
+        let relative = program_dir.strip_prefix("/").unwrap_or(program_dir);
+        candidates.push(Path::new(dir).join(relative).join(name));
+    }
+    candidates.into_iter().find(|candidate| {
+        fs::canonicalize(candidate).ok().as_ref() != Some(&program)
+            && fs::read(candidate).ok().map(|data| crc32(&data)) == Some(crc)
+    })
+}
+
+/// The CRC-32 (as used by zlib) that `.gnu_debuglink` records for the debug file.
+fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        let mut crc = i as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
         }
+        *entry = crc;
     }
+    !data.iter().fold(!0u32, |crc, byte| {
+        table[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Whether `query` names the function `name`, either in full or leaving out any of the
+/// namespaces, modules or classes it is in (`method` or `Class::method` for `ns::Class::method`),
+/// and the template arguments of template instances.
+fn name_matches(name: &str, query: &str) -> bool {
+    let suffix_matches = |name: &str| {
+        name == query || (name.ends_with(query) && name[..name.len() - query.len()].ends_with("::"))
+    };
+    suffix_matches(name) || suffix_matches(&strip_template_args(name))
+}
+
+/// Whether `name` is the program's `main`, which in Rust is a `main` inside the crate's module.
+pub fn is_main(name: &str) -> bool {
+    name == "main" || name.ends_with("::main")
+}
+
+/// Leaves out everything between angle brackets, so `ns::twice<int>` becomes `ns::twice`.
+fn strip_template_args(name: &str) -> String {
+    let mut depth = 0;
+    let mut stripped = String::new();
+    for c in name.chars() {
+        match c {
+            '<' => depth += 1,
+            '>' if depth > 0 => depth -= 1,
+            _ if depth == 0 => stripped.push(c),
+            _ => {}
+        }
+    }
+    stripped
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct Type {
     pub name: String,
     pub size: usize,
+    /// Language of the compilation unit the type comes from, which decides how values look
+    pub language: Option<gimli::DwLang>,
+    pub kind: TypeKind,
+}
+
+/// Key of a type in `DwarfData`'s table of types.
+pub type TypeId = usize;
+
+#[derive(Debug, Clone, Default)]
+pub enum TypeKind {
+    /// Numbers, booleans and characters
+    #[default]
+    Base,
+    /// Pointers and references. The target is `None` for `void *`.
+    Pointer(Option<TypeId>),
+    /// Structures, classes, unions and Rust enums
+    Struct(StructType),
+    Array {
+        element: TypeId,
+        /// `None` for arrays of unknown size (`int a[]`)
+        count: Option<usize>,
+    },
+    /// C style enumerations, with the name and value of each enumerator
+    Enum(Vec<(String, i64)>),
+    /// typedefs, and types with qualifiers such as `const`. The target is `None` for `const void`.
+    Alias(Option<TypeId>),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StructType {
+    pub members: Vec<Member>,
+    /// Parameters of generic types, such as `T` for `Vec<T>`
+    pub type_params: Vec<(String, TypeId)>,
+    /// Set for Rust enums, whose fields depend on which variant the value holds
+    pub variants: Option<Variants>,
+    /// `struct`, `class` or `union`, or `enum` for Rust enums
+    pub keyword: &'static str,
+}
+
+#[derive(Debug, Clone)]
+pub struct Member {
+    /// Empty for the base classes of a C++ class
+    pub name: String,
+    pub type_id: TypeId,
+    /// Offset from the start of the enclosing value
+    pub offset: usize,
+}
+
+/// The variants of a Rust enum (DW_TAG_variant_part).
+#[derive(Debug, Clone)]
+pub struct Variants {
+    /// Where the discriminant is stored. With niche layouts, this overlaps the fields of one of
+    /// the variants.
+    pub discr: Member,
+    /// The discriminant value of each variant, and its fields as a member of the enum type. The
+    /// variant without a value is the one for all values not listed.
+    pub variants: Vec<(Option<u64>, Member)>,
 }
 
 impl Type {
-    pub fn new(name: String, size: usize) -> Self {
+    pub fn new(name: String, size: usize, language: Option<gimli::DwLang>) -> Self {
         Type {
             name: name,
             size: size,
+            language,
+            kind: TypeKind::Base,
+        }
+    }
+
+    pub fn is_rust(&self) -> bool {
+        self.language == Some(gimli::DW_LANG_Rust)
+    }
+
+    /// Whether the type is a const version of another (which is named after it) rather than a
+    /// typedef.
+    pub fn is_qualified(&self) -> bool {
+        matches!(self.kind, TypeKind::Alias(_))
+            && (self.name.starts_with("const ") || self.name.ends_with("*const"))
+    }
+
+    /// Whether the type is from C, where structures and enums are named with their keyword.
+    pub fn is_c(&self) -> bool {
+        matches!(
+            self.language,
+            Some(gimli::DW_LANG_C)
+                | Some(gimli::DW_LANG_C89)
+                | Some(gimli::DW_LANG_C99)
+                | Some(gimli::DW_LANG_C11)
+        )
+    }
+
+    /// Whether values of this type are passed and returned in SSE registers.
+    pub fn is_float(&self) -> bool {
+        match self.name.as_str() {
+            "float" | "double" => true,
+            "f32" | "f64" => self.is_rust(),
+            _ => false,
+        }
+    }
+
+    /// Formats the low `size` bytes of a raw register or memory value as a value of this type.
+    pub fn format_value(&self, raw: u64) -> String {
+        let bits = self.size * 8;
+        let value = if bits == 0 || bits >= 64 {
+            raw
+        } else {
+            raw & ((1 << bits) - 1)
+        };
+        if self.is_float() && self.size == 4 {
+            return format!("{}", f32::from_bits(value as u32));
+        }
+        if self.is_float() {
+            return format!("{}", f64::from_bits(value));
+        }
+        if self.name == "_Bool" || self.name == "bool" {
+            return format!("{}", value != 0);
+        }
+        // a Rust char is a whole unicode scalar value
+        if self.is_rust() && self.name == "char" {
+            return match std::char::from_u32(value as u32) {
+                Some(c) => format!("{:?}", c),
+                None => value.to_string(),
+            };
+        }
+        let unsigned = if self.is_rust() {
+            self.name.starts_with('u')
+        } else {
+            self.name.contains("unsigned")
+        };
+        let number = if unsigned {
+            value.to_string()
+        } else if bits == 0 || bits >= 64 {
+            (value as i64).to_string()
+        } else {
+            // sign extend from the type's width
+            (((value << (64 - bits)) as i64) >> (64 - bits)).to_string()
+        };
+        if !self.is_rust() && self.name.contains("char") && self.size == 1 {
+            format!("{} {:?}", number, value as u8 as char)
+        } else {
+            number
+        }
+    }
+}
+
+/// Computes the canonical frame address a row of call frame information gives for `state`.
+fn cfa_of(
+    row: &gimli::UnwindTableRow<gimli::EndianSlice<gimli::RunTimeEndian>>,
+    state: &dyn ProgramState,
+) -> Option<u64> {
+    match row.cfa() {
+        gimli::CfaRule::RegisterAndOffset { register, offset } => {
+            Some((state.register(register.0)? as i64 + offset) as u64)
         }
+        gimli::CfaRule::Expression(_) => None,
+    }
+}
+
+/// A DWARF expression, copied out of the debug info so that it can be evaluated once the
+/// program is running.
+#[derive(Clone)]
+pub struct DwarfExpr {
+    pub bytecode: Vec<u8>,
+    pub encoding: gimli::Encoding,
+}
+
+impl fmt::Display for DwarfExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", gimli_wrapper::format_expression(self))
     }
 }
 
 #[derive(Clone)]
 pub enum Location {
-    Address(usize),
-    FramePointerOffset(isize),
+    /// A single expression that is valid wherever the variable is in scope.
+    Expression(DwarfExpr),
+    /// A location list: the expression to use depends on the program counter.
+    List(Vec<(usize, usize, DwarfExpr)>),
+    /// The variable has no storage, only a known constant value.
+    Value(Vec<u8>),
+    /// The variable was optimized away entirely.
+    OptimizedOut,
+}
+
+impl Location {
+    /// Picks the expression that describes the location at `pc`.
+    pub fn at(&self, pc: usize) -> Result<&DwarfExpr, EvalError> {
+        match self {
+            Location::Expression(expr) => Ok(expr),
+            Location::List(entries) => entries
+                .iter()
+                .find(|(begin, end, _)| *begin <= pc && pc < *end)
+                .map(|(_, _, expr)| expr)
+                .ok_or(EvalError::OptimizedOut),
+            Location::Value(_) | Location::OptimizedOut => Err(EvalError::OptimizedOut),
+        }
+    }
+
+    /// Returns the address of a variable with static storage (DW_OP_addr).
+    pub fn static_address(&self) -> Option<usize> {
+        match self {
+            Location::Expression(expr)
+                if expr.bytecode.len() == 9 && expr.bytecode[0] == gimli::DW_OP_addr.0 =>
+            {
+                Some(u64::from_le_bytes(expr.bytecode[1..].try_into().unwrap()) as usize)
+            }
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for Location {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match *self {
-            Location::Address(addr) => write!(f, "Address({:#x})", addr),
-            Location::FramePointerOffset(offset) => write!(f, "FramePointerOffset({})", offset),
+        match self {
+            Location::Expression(expr) => write!(f, "{}", expr),
+            Location::List(entries) => {
+                write!(f, "location list [")?;
+                for (i, (begin, end, expr)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{:#x}-{:#x}: {}", begin, end, expr)?;
+                }
+                write!(f, "]")
+            }
+            Location::Value(bytes) => write!(f, "constant {:02x?}", bytes),
+            Location::OptimizedOut => write!(f, "<optimized out>"),
         }
     }
 }
@@ -191,6 +887,7 @@ pub struct Variable {
     pub entity_type: Type,
     pub location: Location,
     pub line_number: usize, // Line number in source file
+    pub is_parameter: bool,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -200,12 +897,61 @@ pub struct Function {
     pub text_length: usize,
     pub line_number: usize, // Line number in source file
     pub variables: Vec<Variable>,
+    pub return_type: Option<Type>, // None for void functions
+    pub frame_base: Option<Location>,
+    /// Address ranges of a function whose code is not contiguous (DW_AT_ranges). Empty when the
+    /// function is just `address..address + text_length`.
+    pub ranges: Vec<(usize, usize)>,
+    /// Calls to other functions the compiler inlined into this one, in the order they appear
+    /// in the debug info, so that an inlined call comes before the calls inlined into it.
+    pub inlined_calls: Vec<InlinedCall>,
+}
+
+/// A copy of a function's code inlined at a call site (DW_TAG_inlined_subroutine).
+#[derive(Debug, Default, Clone)]
+pub struct InlinedCall {
+    /// Name of the function that was inlined
+    pub name: String,
+    pub ranges: Vec<(usize, usize)>,
+    /// Address of the first instruction of the inlined function's body
+    pub entry_pc: usize,
+    /// Location of the call the function was inlined at
+    pub call_file: String,
+    pub call_line: usize,
+    /// Parameters and locals of the inlined function
+    pub variables: Vec<Variable>,
+}
+
+impl InlinedCall {
+    /// Whether the code at `addr` belongs to this copy of the function.
+    pub fn contains(&self, addr: usize) -> bool {
+        self.ranges
+            .iter()
+            .any(|(begin, end)| *begin <= addr && addr < *end)
+    }
+}
+
+impl Function {
+    /// Whether the code at `addr` belongs to this function.
+    pub fn contains(&self, addr: usize) -> bool {
+        if self.ranges.is_empty() {
+            self.address <= addr && addr < self.address + self.text_length
+        } else {
+            self.ranges
+                .iter()
+                .any(|(begin, end)| *begin <= addr && addr < *end)
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct File {
     pub name: String,
+    pub directory: String, // Compilation directory, relative paths are relative to it
+    pub language: Option<gimli::DwLang>,
     pub global_variables: Vec<Variable>,
+    /// Types of the unit, only until they are moved to `DwarfData`
+    pub types: HashMap<TypeId, Type>,
     pub functions: Vec<Function>,
     pub lines: Vec<Line>,
 }
@@ -222,3 +968,18 @@ impl fmt::Display for Line {
         write!(f, "{}:{}", self.file, self.number)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_known_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414f_a339
+        );
+    }
+}
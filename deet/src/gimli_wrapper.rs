@@ -10,13 +10,22 @@ use gimli::{UnitOffset, UnitSectionOffset};
 use object::Object;
 use std::borrow;
 //use std::io::{BufWriter, Write};
-use crate::dwarf_data::{File, Function, Line, Location, Type, Variable};
+use crate::dwarf_data::{
+    DwarfExpr, EvalError, File, Function, InlinedCall, Line, Location, Member, ProgramState,
+    StructType, Type, TypeId, TypeKind, Variable, Variants,
+};
+use crate::output;
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::fmt::Write;
+use std::rc::Rc;
 use std::{io, path};
 
-pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<Vec<File>, Error> {
+pub fn load_file(
+    object: &object::File,
+    endian: gimli::RunTimeEndian,
+    path: &str,
+) -> Result<Vec<File>, Error> {
     // Load a section and return as `Cow<[u8]>`.
     let load_section = |id: gimli::SectionId| -> Result<borrow::Cow<[u8]>, gimli::Error> {
         Ok(object
@@ -39,208 +48,1174 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
     // Create `EndianSlice`s for all of the sections.
     let dwarf = dwarf_cow.borrow(&borrow_section);
 
-    // Define a mapping from type offsets to type structs
-    let mut offset_to_type: HashMap<usize, Type> = HashMap::new();
+    // Split units of the whole program may be packaged next to it, in `<program>.dwp`
+    let package = SplitDwarfFile::open(path::Path::new(&format!("{}.dwp", path)));
 
     let mut compilation_units: Vec<File> = Vec::new();
+    let mut split_units = 0;
 
     // Iterate over the compilation units.
     let mut iter = dwarf.units();
     while let Some(header) = iter.next()? {
         let unit = dwarf.unit(header)?;
+        let (dwo_id, dwo_name) = match skeleton_info(&unit, &dwarf)? {
+            Some(info) => info,
+            None => {
+                let mut file = load_unit(&unit, &dwarf, 0)?;
+                load_lines(&unit, &dwarf, &mut file)?;
+                compilation_units.push(file);
+                continue;
+            }
+        };
 
-        // Iterate over the Debugging Information Entries (DIEs) in the unit.
-        let mut depth = 0;
-        let mut entries = unit.entries();
-        while let Some((delta_depth, entry)) = entries.next_dfs()? {
-            depth += delta_depth;
-            // Update the offset_to_type mapping for types
-            // Update the variable list for formal params/variables
-            match entry.tag() {
-                gimli::DW_TAG_compile_unit => {
-                    let name = if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_name) {
-                        if let Ok(DebugValue::Str(name)) = get_attr_value(&attr, &unit, &dwarf) {
-                            name
-                        } else {
-                            "<unknown>".to_string()
-                        }
-                    } else {
-                        "<unknown>".to_string()
-                    };
-                    compilation_units.push(File {
-                        name,
-                        global_variables: Vec::new(),
-                        functions: Vec::new(),
-                        lines: Vec::new(),
-                    });
+        // The unit is a skeleton, and most of its debug info lives in a .dwo file or in the
+        // package. The line table stays in the executable, with the skeleton.
+        let dwo_path = match unit.comp_dir {
+            Some(ref dir) => path::Path::new(dir.to_string_lossy().as_ref()).join(&dwo_name),
+            None => path::PathBuf::from(&dwo_name),
+        };
+        let dwo_file;
+        let split_file = match package.as_ref().filter(|package| package.contains(dwo_id)) {
+            Some(package) => Some(package),
+            None => {
+                dwo_file = SplitDwarfFile::open(&dwo_path).or_else(|| {
+                    // the build directory may be gone; look next to the program instead
+                    let dir = path::Path::new(path).parent()?;
+                    SplitDwarfFile::open(&dir.join(dwo_path.file_name()?))
+                });
+                dwo_file.as_ref()
+            }
+        };
+        let split_dwarf = split_file.and_then(|split_file| split_file.dwarf(dwo_id, &dwarf));
+        // offsets start over in each .dwo file, so keep the types of split units apart
+        split_units += 1;
+        let type_base = split_units << 40;
+        let split_unit = match split_dwarf {
+            Some(split_dwarf) => load_split_unit(&unit, &split_dwarf, dwo_id, type_base)?,
+            None => None,
+        };
+        let mut file = match split_unit {
+            Some(file) => file,
+            None => {
+                output::error(&format!(
+                    "warning: Could not find DWO CU {}(0x{:016x}) referenced by CU at offset 0x{:x}",
+                    dwo_name,
+                    dwo_id.0,
+                    section_offset(UnitOffset(0), &unit)
+                ));
+                let mut file = load_unit(&unit, &dwarf, 0)?;
+                if let Some(name) = primary_file_name(&unit, &dwarf) {
+                    file.name = name;
+                }
+                file
+            }
+        };
+        load_lines(&unit, &dwarf, &mut file)?;
+        compilation_units.push(file);
+    }
+    Ok(compilation_units)
+}
+
+/// Builds the addr2line context used to map addresses to lines, from the same sections.
+pub fn load_context(
+    object: &object::File,
+    endian: gimli::RunTimeEndian,
+) -> Result<addr2line::Context<gimli::EndianRcSlice<gimli::RunTimeEndian>>, Error> {
+    let load_section = |id: gimli::SectionId| -> Result<_, gimli::Error> {
+        let data = object
+            .section_data_by_name(id.name())
+            .unwrap_or(borrow::Cow::Borrowed(&[][..]));
+        Ok(gimli::EndianRcSlice::new(Rc::from(&*data), endian))
+    };
+    let load_section_sup = |_| Ok(gimli::EndianRcSlice::new(Rc::from(&[][..]), endian));
+    let dwarf = gimli::Dwarf::load(&load_section, &load_section_sup)?;
+    Ok(addr2line::Context::from_dwarf(dwarf)?)
+}
+
+/// If `unit` is the skeleton of a split unit, returns the id and file name of the .dwo it was
+/// split into. DWARF 5 marks skeletons in the unit header, the GNU extension for DWARF 4 with
+/// `DW_AT_GNU_dwo_id`.
+fn skeleton_info<R: Reader>(
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+) -> Result<Option<(gimli::DwoId, String)>, Error> {
+    let mut dwo_id = match unit.header.type_() {
+        gimli::UnitType::Skeleton(dwo_id) => Some(dwo_id),
+        _ => None,
+    };
+    let mut dwo_name = None;
+    let mut entries = unit.entries();
+    if let Some((_, root)) = entries.next_dfs()? {
+        let mut attrs = root.attrs();
+        while let Some(attr) = attrs.next()? {
+            match (attr.name(), attr.value()) {
+                (gimli::DW_AT_GNU_dwo_id, gimli::AttributeValue::DwoId(id)) => dwo_id = Some(id),
+                (gimli::DW_AT_dwo_name, value) | (gimli::DW_AT_GNU_dwo_name, value) => {
+                    dwo_name = Some(
+                        dwarf
+                            .attr_string(unit, value)?
+                            .to_string_lossy()?
+                            .to_string(),
+                    )
                 }
-                gimli::DW_TAG_base_type => {
-                    let name = if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_name) {
-                        if let Ok(DebugValue::Str(name)) = get_attr_value(&attr, &unit, &dwarf) {
-                            name
-                        } else {
-                            "<unknown>".to_string()
+                _ => {}
+            }
+        }
+    }
+    Ok(dwo_id.and_then(|dwo_id| Some((dwo_id, dwo_name?))))
+}
+
+/// DWARF 5 skeletons don't have a DW_AT_name, but the line table names the unit's primary
+/// source file as file 0.
+fn primary_file_name<R: Reader>(unit: &gimli::Unit<R>, dwarf: &gimli::Dwarf<R>) -> Option<String> {
+    let header = unit.line_program.as_ref()?.header();
+    if header.version() < 5 {
+        return None;
+    }
+    let entry = header.file(0)?;
+    let mut name = path::PathBuf::new();
+    if let Some(dir) = entry.directory(header) {
+        name.push(
+            dwarf
+                .attr_string(unit, dir)
+                .ok()?
+                .to_string_lossy()
+                .ok()?
+                .as_ref(),
+        );
+    }
+    name.push(
+        dwarf
+            .attr_string(unit, entry.path_name())
+            .ok()?
+            .to_string_lossy()
+            .ok()?
+            .as_ref(),
+    );
+    Some(name.to_string_lossy().to_string())
+}
+
+/// Loads the split unit `dwo_id` that `skeleton` stands in for. Returns None if `split_dwarf`
+/// doesn't have it.
+fn load_split_unit<R: Reader>(
+    skeleton: &gimli::Unit<R>,
+    split_dwarf: &gimli::Dwarf<R>,
+    dwo_id: gimli::DwoId,
+    type_base: usize,
+) -> Result<Option<File>, Error> {
+    let mut units = split_dwarf.units();
+    while let Some(header) = units.next()? {
+        let mut unit = split_dwarf.unit(header)?;
+        let unit_dwo_id = match unit.header.type_() {
+            gimli::UnitType::SplitCompilation(id) => Some(id),
+            // DWARF 4 split units repeat the id in their root DIE
+            _ => {
+                let mut entries = unit.entries();
+                match entries.next_dfs()? {
+                    Some((_, root)) => match root.attr_value(gimli::DW_AT_GNU_dwo_id)? {
+                        Some(gimli::AttributeValue::DwoId(id)) => Some(id),
+                        _ => None,
+                    },
+                    None => None,
+                }
+            }
+        };
+        if unit_dwo_id != Some(dwo_id) {
+            continue;
+        }
+        // addresses live in the executable's .debug_addr, at the skeleton's base
+        unit.copy_relocated_attributes(skeleton);
+        if unit.comp_dir.is_none() {
+            unit.comp_dir = skeleton.comp_dir.clone();
+        }
+        return Ok(Some(load_unit(&unit, split_dwarf, type_base)?));
+    }
+    Ok(None)
+}
+
+/// Builds the `File` for a compilation unit from its DIEs.
+/// `type_base` is added to the offsets of the unit's types to make up their `TypeId`s.
+fn load_unit<R: Reader>(
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+    type_base: usize,
+) -> Result<File, Error> {
+    let mut file = File {
+        name: "<unknown>".to_string(),
+        directory: match unit.comp_dir {
+            Some(ref dir) => dir.to_string_lossy()?.to_string(),
+            None => String::new(),
+        },
+        language: None,
+        global_variables: Vec::new(),
+        types: HashMap::new(),
+        functions: Vec::new(),
+        lines: Vec::new(),
+    };
+
+    // Define a mapping from type ids to type structs
+    let mut offset_to_type: HashMap<TypeId, Type> = HashMap::new();
+    // Names and type offsets of all named DIEs, for entries that only refer to another DIE
+    // through DW_AT_abstract_origin or DW_AT_specification (common in optimized code)
+    let mut offset_to_origin: HashMap<usize, (String, Option<usize>, u64)> = HashMap::new();
+
+    // Collect the unit's types before anything else, since DIEs may refer to types that are
+    // declared after them
+    let mut depth = 0;
+    // names of the namespaces and types enclosing the current DIE, with their depth
+    let mut scopes: Vec<(isize, String)> = Vec::new();
+    let mut entries = unit.entries();
+    while let Some((delta_depth, entry)) = entries.next_dfs()? {
+        depth += delta_depth;
+        while let Some((scope_depth, _)) = scopes.last() {
+            if *scope_depth < depth {
+                break;
+            }
+            scopes.pop();
+        }
+        if let Some(gimli::AttributeValue::Language(language)) =
+            entry.attr_value(gimli::DW_AT_language)?
+        {
+            file.language = Some(language);
+        }
+        let name = match linkage_name(entry, unit, dwarf, file.language) {
+            Some(name) => Some(name),
+            None => match entry.attr(gimli::DW_AT_name) {
+                Ok(Some(attr)) => match get_attr_value(&attr, unit, dwarf) {
+                    Ok(DebugValue::Str(name)) => Some(name),
+                    _ => None,
+                },
+                _ => None,
+            },
+        };
+        if let Some(name) = name {
+            let type_offset = match entry.attr(gimli::DW_AT_type) {
+                Ok(Some(attr)) => match get_attr_value(&attr, unit, dwarf) {
+                    Ok(DebugValue::Size(offset)) => Some(offset),
+                    _ => None,
+                },
+                _ => None,
+            };
+            let line = match entry.attr(gimli::DW_AT_decl_line) {
+                Ok(Some(attr)) => attr.udata_value().unwrap_or(0),
+                _ => 0,
+            };
+            offset_to_origin.insert(
+                section_offset(entry.offset(), unit),
+                (name, type_offset, line),
+            );
+        }
+        if entry.tag() == gimli::DW_TAG_base_type {
+            let name = if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_name) {
+                if let Ok(DebugValue::Str(name)) = get_attr_value(&attr, unit, dwarf) {
+                    name
+                } else {
+                    "<unknown>".to_string()
+                }
+            } else {
+                "<unknown>".to_string()
+            };
+            let byte_size = if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_byte_size) {
+                if let Ok(DebugValue::Uint(byte_size)) = get_attr_value(&attr, unit, dwarf) {
+                    byte_size
+                } else {
+                    // TODO: report error?
+                    0
+                }
+            } else {
+                // TODO: report error?
+                0
+            };
+            let type_offset = section_offset(entry.offset(), unit);
+            offset_to_type.insert(
+                type_base + type_offset,
+                Type::new(name, byte_size.try_into().unwrap(), file.language),
+            );
+        } else if let Some(kind) = load_type(entry, unit, dwarf, type_base)? {
+            let mut dtype = Type::new(String::new(), 0, file.language);
+            if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_name) {
+                if let Ok(DebugValue::Str(name)) = get_attr_value(&attr, unit, dwarf) {
+                    dtype.name = scoped_name(&scopes, name, file.language);
+                }
+            }
+            if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_byte_size) {
+                if let Ok(DebugValue::Uint(byte_size)) = get_attr_value(&attr, unit, dwarf) {
+                    dtype.size = byte_size.try_into().unwrap();
+                }
+            }
+            if let TypeKind::Pointer(_) = kind {
+                // pointer types usually leave their size out
+                if dtype.size == 0 {
+                    dtype.size = unit.header.address_size().into();
+                }
+            }
+            dtype.kind = kind;
+            let type_offset = section_offset(entry.offset(), unit);
+            offset_to_type.insert(type_base + type_offset, dtype);
+        }
+
+        // C++ and Rust name types after the namespaces and types they are declared in
+        match entry.tag() {
+            gimli::DW_TAG_namespace
+            | gimli::DW_TAG_structure_type
+            | gimli::DW_TAG_class_type
+            | gimli::DW_TAG_union_type
+            | gimli::DW_TAG_enumeration_type => {
+                if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_name) {
+                    if let Ok(DebugValue::Str(name)) = get_attr_value(&attr, unit, dwarf) {
+                        scopes.push((depth, name));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    complete_types(&mut offset_to_type);
+
+    // Iterate over the Debugging Information Entries (DIEs) in the unit.
+    let mut depth = 0;
+    // DIE depth and index of the inlined calls enclosing the current DIE
+    let mut inlined_calls: Vec<(isize, usize)> = Vec::new();
+    let mut entries = unit.entries();
+    while let Some((delta_depth, entry)) = entries.next_dfs()? {
+        depth += delta_depth;
+        while let Some(&(call_depth, _)) = inlined_calls.last() {
+            if call_depth < depth {
+                break;
+            }
+            inlined_calls.pop();
+        }
+        // Update the variable list for formal params/variables
+        match entry.tag() {
+            gimli::DW_TAG_compile_unit | gimli::DW_TAG_skeleton_unit => {
+                if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_name) {
+                    if let Ok(DebugValue::Str(name)) = get_attr_value(&attr, unit, dwarf) {
+                        file.name = name;
+                    }
+                }
+                // rustc names units after the crate root and the codegen unit, as in
+                // "src/main.rs/@/foo.3a1fbbbh-cgu.0"
+                if file.language == Some(gimli::DW_LANG_Rust) {
+                    if let Some(index) = file.name.find("/@/") {
+                        file.name.truncate(index);
+                    }
+                }
+            }
+            gimli::DW_TAG_subprogram => {
+                let mut func: Function = Default::default();
+                let mut attrs = entry.attrs();
+                while let Some(attr) = attrs.next()? {
+                    let val = get_attr_value(&attr, unit, dwarf);
+                    //println!("   {}: {:?}", attr.name(), val);
+                    match attr.name() {
+                        gimli::DW_AT_name => {
+                            if let Ok(DebugValue::Str(name)) = val {
+                                func.name = name;
+                            }
                         }
-                    } else {
-                        "<unknown>".to_string()
-                    };
-                    let byte_size = if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_byte_size) {
-                        if let Ok(DebugValue::Uint(byte_size)) =
-                            get_attr_value(&attr, &unit, &dwarf)
-                        {
-                            byte_size
-                        } else {
-                            // TODO: report error?
-                            0
+                        gimli::DW_AT_decl_line => {
+                            if let Ok(DebugValue::Uint(line_number)) = val {
+                                func.line_number = line_number.try_into().unwrap();
+                            }
                         }
-                    } else {
-                        // TODO: report error?
-                        0
-                    };
-                    let type_offset = entry.offset().0;
-                    offset_to_type
-                        .insert(type_offset, Type::new(name, byte_size.try_into().unwrap()));
-                }
-                gimli::DW_TAG_subprogram => {
-                    let mut func: Function = Default::default();
-                    let mut attrs = entry.attrs();
-                    while let Some(attr) = attrs.next()? {
-                        let val = get_attr_value(&attr, &unit, &dwarf);
-                        //println!("   {}: {:?}", attr.name(), val);
-                        match attr.name() {
-                            gimli::DW_AT_name => {
-                                if let Ok(DebugValue::Str(name)) = val {
-                                    func.name = name;
-                                }
+                        gimli::DW_AT_type => {
+                            if let Ok(DebugValue::Size(offset)) = val {
+                                func.return_type =
+                                    offset_to_type.get(&(type_base + offset)).cloned();
                             }
-                            gimli::DW_AT_high_pc => {
-                                if let Ok(DebugValue::Uint(high_pc)) = val {
-                                    func.text_length = high_pc.try_into().unwrap();
+                        }
+                        gimli::DW_AT_abstract_origin | gimli::DW_AT_specification => {
+                            if let Ok(DebugValue::Size(offset)) = val {
+                                if let Some((name, type_offset, line)) =
+                                    offset_to_origin.get(&offset)
+                                {
+                                    if func.name.is_empty() {
+                                        func.name = name.clone();
+                                    }
+                                    // a definition only repeats the line of its declaration if
+                                    // it differs
+                                    if func.line_number == 0 {
+                                        func.line_number = (*line).try_into().unwrap();
+                                    }
+                                    if func.return_type.is_none() {
+                                        func.return_type = type_offset
+                                            .and_then(|offset| {
+                                                offset_to_type.get(&(type_base + offset))
+                                            })
+                                            .cloned();
+                                    }
                                 }
                             }
-                            gimli::DW_AT_low_pc => {
-                                //println!("low pc {:?}", attr.value());
-                                if let Ok(DebugValue::Uint(low_pc)) = val {
-                                    func.address = low_pc.try_into().unwrap();
+                        }
+                        gimli::DW_AT_frame_base => {
+                            func.frame_base = get_location(&attr, unit, dwarf);
+                        }
+                        _ => {}
+                    }
+                }
+                // C++ and Rust functions are shown with the namespaces or modules they are in
+                if let Some(name) = linkage_name(entry, unit, dwarf, file.language) {
+                    func.name = name;
+                }
+                // functions split in several pieces (e.g. a cold path moved out of the way) have
+                // several ranges; the entry point is at the start of the first one
+                let ranges = get_pc_ranges(entry, unit, dwarf)?;
+                if let Some(&(begin, end)) = ranges.first() {
+                    func.address = begin;
+                    func.text_length = end - begin;
+                }
+                if ranges.len() > 1 {
+                    func.ranges = ranges;
+                }
+                file.functions.push(func);
+            }
+            gimli::DW_TAG_inlined_subroutine => {
+                let mut call: InlinedCall = Default::default();
+                let mut entry_pc = None;
+                let mut attrs = entry.attrs();
+                while let Some(attr) = attrs.next()? {
+                    match attr.name() {
+                        gimli::DW_AT_abstract_origin => {
+                            if let Ok(DebugValue::Size(offset)) = get_attr_value(&attr, unit, dwarf)
+                            {
+                                if let Some((name, _, _)) = offset_to_origin.get(&offset) {
+                                    call.name = name.clone();
                                 }
                             }
-                            gimli::DW_AT_decl_line => {
-                                if let Ok(DebugValue::Uint(line_number)) = val {
-                                    func.line_number = line_number.try_into().unwrap();
+                        }
+                        gimli::DW_AT_entry_pc => entry_pc = Some(attr.value()),
+                        gimli::DW_AT_call_file => {
+                            if let gimli::AttributeValue::FileIndex(index) = attr.value() {
+                                if let Some(ref program) = unit.line_program {
+                                    let comp_dir = path::Path::new(&file.directory);
+                                    if let Some(path) = line_file_path(
+                                        unit,
+                                        dwarf,
+                                        program.header(),
+                                        index,
+                                        comp_dir,
+                                    )? {
+                                        call.call_file = path.to_string_lossy().to_string();
+                                    }
                                 }
                             }
-                            _ => {}
                         }
+                        gimli::DW_AT_call_line => {
+                            if let Some(line) = attr.udata_value() {
+                                call.call_line = line.try_into().unwrap();
+                            }
+                        }
+                        _ => {}
                     }
-                    compilation_units.last_mut().unwrap().functions.push(func);
                 }
-                gimli::DW_TAG_formal_parameter | gimli::DW_TAG_variable => {
-                    let mut name = String::new();
-                    let mut entity_type: Option<Type> = None;
-                    let mut location: Option<Location> = None;
-                    let mut line_number = 0;
-                    let mut attrs = entry.attrs();
-                    while let Some(attr) = attrs.next()? {
-                        let val = get_attr_value(&attr, &unit, &dwarf);
-                        //println!("   {}: {:?}", attr.name(), val);
-                        match attr.name() {
-                            gimli::DW_AT_name => {
-                                if let Ok(DebugValue::Str(attr_name)) = val {
-                                    name = attr_name;
+                call.ranges = get_pc_ranges(entry, unit, dwarf)?;
+                let low_pc = call.ranges.first().map_or(0, |(begin, _)| *begin);
+                call.entry_pc = match entry_pc {
+                    // an offset from the start of the code since DWARF 5
+                    Some(gimli::AttributeValue::Udata(offset)) => low_pc + offset as usize,
+                    Some(value) => match dwarf.attr_address(unit, value)? {
+                        Some(addr) => addr as usize,
+                        None => low_pc,
+                    },
+                    None => low_pc,
+                };
+                if call.call_file.is_empty() {
+                    call.call_file = path::Path::new(&file.directory)
+                        .join(&file.name)
+                        .to_string_lossy()
+                        .to_string();
+                }
+                if let Some(func) = file.functions.last_mut() {
+                    if !call.ranges.is_empty() {
+                        inlined_calls.push((depth, func.inlined_calls.len()));
+                        func.inlined_calls.push(call);
+                    }
+                }
+            }
+            gimli::DW_TAG_formal_parameter | gimli::DW_TAG_variable => {
+                let mut name = String::new();
+                let mut entity_type: Option<Type> = None;
+                let mut location: Option<Location> = None;
+                let mut line_number = 0;
+                let mut declaration = false;
+                let mut attrs = entry.attrs();
+                while let Some(attr) = attrs.next()? {
+                    let val = get_attr_value(&attr, unit, dwarf);
+                    //println!("   {}: {:?}", attr.name(), val);
+                    match attr.name() {
+                        gimli::DW_AT_name => {
+                            if let Ok(DebugValue::Str(attr_name)) = val {
+                                name = attr_name;
+                            }
+                        }
+                        gimli::DW_AT_type => {
+                            if let Ok(DebugValue::Size(offset)) = val {
+                                if let Some(dtype) = offset_to_type.get(&(type_base + offset)) {
+                                    entity_type = Some(dtype.clone());
                                 }
                             }
-                            gimli::DW_AT_type => {
-                                if let Ok(DebugValue::Size(offset)) = val {
-                                    if let Some(dtype) = offset_to_type.get(&offset).clone() {
-                                        entity_type = Some(dtype.clone());
+                        }
+                        gimli::DW_AT_location => {
+                            if let Some(loc) = get_location(&attr, unit, dwarf) {
+                                location = Some(loc);
+                            }
+                        }
+                        gimli::DW_AT_abstract_origin | gimli::DW_AT_specification => {
+                            if let Ok(DebugValue::Size(offset)) = val {
+                                if let Some((origin_name, type_offset, line)) =
+                                    offset_to_origin.get(&offset)
+                                {
+                                    if name.is_empty() {
+                                        name = origin_name.clone();
+                                    }
+                                    if line_number == 0 {
+                                        line_number = *line;
+                                    }
+                                    if entity_type.is_none() {
+                                        entity_type = type_offset
+                                            .and_then(|offset| {
+                                                offset_to_type.get(&(type_base + offset))
+                                            })
+                                            .cloned();
                                     }
                                 }
                             }
-                            gimli::DW_AT_location => {
-                                if let Some(loc) = get_location(&attr, &unit) {
-                                    location = Some(loc);
-                                }
+                        }
+                        gimli::DW_AT_const_value => {
+                            if let Some(bytes) = get_const_value(&attr) {
+                                location = Some(Location::Value(bytes));
                             }
-                            gimli::DW_AT_decl_line => {
-                                if let Ok(DebugValue::Uint(num)) = val {
-                                    line_number = num;
-                                }
+                        }
+                        gimli::DW_AT_declaration => declaration = true,
+                        gimli::DW_AT_decl_line => {
+                            if let Ok(DebugValue::Uint(num)) = val {
+                                line_number = num;
                             }
-                            _ => {}
                         }
+                        _ => {}
                     }
-                    if entity_type.is_some() && location.is_some() {
-                        let var = Variable {
-                            name,
-                            entity_type: entity_type.unwrap(),
-                            location: location.unwrap(),
-                            line_number: line_number.try_into().unwrap(),
-                        };
-                        if depth == 1 {
-                            compilation_units
-                                .last_mut()
-                                .unwrap()
-                                .global_variables
-                                .push(var);
-                        } else if depth > 1 {
-                            compilation_units
-                                .last_mut()
-                                .unwrap()
-                                .functions
-                                .last_mut()
-                                .unwrap()
-                                .variables
-                                .push(var);
+                }
+                // a variable without a location has been optimized away, unless the entry
+                // only declares a variable that is defined elsewhere
+                if let Some(entity_type) =
+                    entity_type.filter(|_| location.is_some() || !declaration)
+                {
+                    let var = Variable {
+                        name,
+                        entity_type,
+                        location: location.unwrap_or(Location::OptimizedOut),
+                        line_number: line_number.try_into().unwrap(),
+                        is_parameter: entry.tag() == gimli::DW_TAG_formal_parameter,
+                    };
+                    if depth == 1 {
+                        file.global_variables.push(var);
+                    } else if depth > 1 {
+                        if let Some(func) = file.functions.last_mut() {
+                            match inlined_calls.last() {
+                                Some((_, index)) => func.inlined_calls[*index].variables.push(var),
+                                None => func.variables.push(var),
+                            }
                         }
                     }
                 }
-                // NOTE: :You may consider supporting other types by extending this
-                // match statement
-                _ => {}
             }
+            // NOTE: :You may consider supporting other types by extending this
+            // match statement
+            _ => {}
         }
+    }
+    file.types = offset_to_type;
+    Ok(file)
+}
+
+/// Adds the rows of `unit`'s line table that belong to `file` itself (rather than to headers it
+/// includes) to `file.lines`.
+fn load_lines<R: Reader>(
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+    file: &mut File,
+) -> Result<(), Error> {
+    let program = match unit.line_program.clone() {
+        Some(program) => program,
+        None => return Ok(()),
+    };
+    // Paths in the line table may be relative to the compilation directory, and name the
+    // file differently from DW_AT_name (e.g. "./foo.c" vs "foo.c"), so compare full paths.
+    let comp_dir = path::PathBuf::from(&file.directory);
+    let file_path = normalize_path(&comp_dir.join(&file.name));
+
+    // Iterate over the line program rows.
+    let mut rows = program.rows();
+    let mut last_file_index = None;
+    let mut in_file = false;
+    while let Some((header, row)) = rows.next_row()? {
+        if row.end_sequence() {
+            continue;
+        }
+        if last_file_index != Some(row.file_index()) {
+            last_file_index = Some(row.file_index());
+            let path = line_file_path(unit, dwarf, header, row.file_index(), &comp_dir)?
+                .unwrap_or_else(|| comp_dir.clone());
+            in_file = normalize_path(&path) == file_path;
+        }
+
+        // Determine line/column. DWARF line/column is never 0, so we use that
+        // but other applications may want to display this differently.
+        let line = row.line().unwrap_or(0);
+
+        if in_file {
+            file.lines.push(Line {
+                file: file.name.clone(),
+                number: line.try_into().unwrap(),
+                address: row.address().try_into().unwrap(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Returns the id of the type a DIE refers to with DW_AT_type.
+fn type_ref<R: Reader>(
+    entry: &gimli::DebuggingInformationEntry<R>,
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+    type_base: usize,
+) -> Option<TypeId> {
+    match get_attr_value(&entry.attr(gimli::DW_AT_type).ok()??, unit, dwarf) {
+        Ok(DebugValue::Size(offset)) => Some(type_base + offset),
+        _ => None,
+    }
+}
 
-        // Get line numbers
-        if let Some(program) = unit.line_program.clone() {
-            // Iterate over the line program rows.
-            let mut rows = program.rows();
-            while let Some((header, row)) = rows.next_row()? {
-                if !row.end_sequence() {
-                    // Determine the path. Real applications should cache this for performance.
-                    let mut path = path::PathBuf::new();
-                    if let Some(file) = row.file(header) {
-                        if let Some(dir) = file.directory(header) {
-                            path.push(dwarf.attr_string(&unit, dir)?.to_string_lossy().as_ref());
+/// Reads the member a DW_TAG_member or DW_TAG_inheritance DIE describes.
+fn load_member<R: Reader>(
+    entry: &gimli::DebuggingInformationEntry<R>,
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+    type_base: usize,
+) -> Option<Member> {
+    let name = match entry.attr(gimli::DW_AT_name).ok()? {
+        Some(attr) => match get_attr_value(&attr, unit, dwarf) {
+            Ok(DebugValue::Str(name)) => name,
+            _ => String::new(),
+        },
+        None => String::new(),
+    };
+    // only constant offsets are supported, not the location expressions of DWARF 2
+    let offset = match entry.attr(gimli::DW_AT_data_member_location).ok()? {
+        Some(attr) => attr.udata_value()?,
+        None => 0,
+    };
+    Some(Member {
+        name,
+        type_id: type_ref(entry, unit, dwarf, type_base)?,
+        offset: offset.try_into().unwrap(),
+    })
+}
+
+/// Reads a type DIE other than a base type: pointers, typedefs and qualifiers, and composite
+/// types along with their members. Returns `None` for DIEs that are not types.
+fn load_type<R: Reader>(
+    entry: &gimli::DebuggingInformationEntry<R>,
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+    type_base: usize,
+) -> Result<Option<TypeKind>, Error> {
+    let target = type_ref(entry, unit, dwarf, type_base);
+    let tag = entry.tag();
+    match tag {
+        gimli::DW_TAG_pointer_type
+        | gimli::DW_TAG_reference_type
+        | gimli::DW_TAG_rvalue_reference_type => return Ok(Some(TypeKind::Pointer(target))),
+        gimli::DW_TAG_typedef
+        | gimli::DW_TAG_const_type
+        | gimli::DW_TAG_volatile_type
+        | gimli::DW_TAG_restrict_type
+        | gimli::DW_TAG_atomic_type => return Ok(Some(TypeKind::Alias(target))),
+        gimli::DW_TAG_array_type
+        | gimli::DW_TAG_enumeration_type
+        | gimli::DW_TAG_structure_type
+        | gimli::DW_TAG_class_type
+        | gimli::DW_TAG_union_type => {}
+        _ => return Ok(None),
+    }
+
+    let mut count = None;
+    let mut enumerators = Vec::new();
+    let mut struct_type = StructType {
+        keyword: match tag {
+            gimli::DW_TAG_class_type => "class",
+            gimli::DW_TAG_union_type => "union",
+            _ => "struct",
+        },
+        ..StructType::default()
+    };
+    let mut tree = unit.entries_tree(Some(entry.offset()))?;
+    let mut children = tree.root()?.children();
+    while let Some(child) = children.next()? {
+        let child_entry = child.entry();
+        match child_entry.tag() {
+            // arrays with several dimensions are flattened into one
+            gimli::DW_TAG_subrange_type => {
+                let dimension = match child_entry.attr(gimli::DW_AT_count)? {
+                    Some(attr) => attr.udata_value(),
+                    None => match child_entry.attr(gimli::DW_AT_upper_bound)? {
+                        Some(attr) => attr.udata_value().map(|bound| bound + 1),
+                        None => None,
+                    },
+                };
+                count = match (count, dimension) {
+                    (None, dimension) => dimension,
+                    (Some(count), Some(dimension)) => Some(count * dimension),
+                    (Some(_), None) => None,
+                };
+            }
+            gimli::DW_TAG_enumerator => {
+                let name = match child_entry.attr(gimli::DW_AT_name)? {
+                    Some(attr) => match get_attr_value(&attr, unit, dwarf) {
+                        Ok(DebugValue::Str(name)) => name,
+                        _ => continue,
+                    },
+                    None => continue,
+                };
+                if let Some(attr) = child_entry.attr(gimli::DW_AT_const_value)? {
+                    if let Some(value) = attr.sdata_value() {
+                        enumerators.push((name, value));
+                    } else if let Some(value) = attr.udata_value() {
+                        enumerators.push((name, value as i64));
+                    }
+                }
+            }
+            gimli::DW_TAG_member | gimli::DW_TAG_inheritance => {
+                // static members are declarations without a location in the value
+                if child_entry.attr(gimli::DW_AT_external)?.is_some() {
+                    continue;
+                }
+                if let Some(member) = load_member(child_entry, unit, dwarf, type_base) {
+                    struct_type.members.push(member);
+                }
+            }
+            gimli::DW_TAG_template_type_parameter => {
+                let name = match child_entry.attr(gimli::DW_AT_name)? {
+                    Some(attr) => match get_attr_value(&attr, unit, dwarf) {
+                        Ok(DebugValue::Str(name)) => name,
+                        _ => continue,
+                    },
+                    None => continue,
+                };
+                if let Some(id) = type_ref(child_entry, unit, dwarf, type_base) {
+                    struct_type.type_params.push((name, id));
+                }
+            }
+            gimli::DW_TAG_variant_part => {
+                let discr_offset = match child_entry.attr_value(gimli::DW_AT_discr)? {
+                    Some(gimli::AttributeValue::UnitRef(offset)) => Some(offset),
+                    _ => None,
+                };
+                let mut discr = None;
+                let mut variants = Vec::new();
+                let mut parts = child.children();
+                while let Some(part) = parts.next()? {
+                    let part_entry = part.entry();
+                    match part_entry.tag() {
+                        gimli::DW_TAG_member if Some(part_entry.offset()) == discr_offset => {
+                            discr = load_member(part_entry, unit, dwarf, type_base);
+                        }
+                        gimli::DW_TAG_variant => {
+                            let value = match part_entry.attr(gimli::DW_AT_discr_value)? {
+                                Some(attr) => attr
+                                    .udata_value()
+                                    .or_else(|| attr.sdata_value().map(|value| value as u64)),
+                                None => None,
+                            };
+                            let mut fields = part.children();
+                            while let Some(field) = fields.next()? {
+                                if field.entry().tag() != gimli::DW_TAG_member {
+                                    continue;
+                                }
+                                if let Some(member) =
+                                    load_member(field.entry(), unit, dwarf, type_base)
+                                {
+                                    variants.push((value, member));
+                                }
+                            }
                         }
-                        path.push(
-                            dwarf
-                                .attr_string(&unit, file.path_name())?
-                                .to_string_lossy()
-                                .as_ref(),
-                        );
+                        _ => {}
                     }
+                }
+                if let Some(discr) = discr {
+                    struct_type.variants = Some(Variants { discr, variants });
+                    struct_type.keyword = "enum";
+                }
+            }
+            _ => {}
+        }
+    }
 
-                    // Get the File
-                    let file = compilation_units
-                        .iter_mut()
-                        .find(|f| f.name == path.as_os_str().to_str().unwrap());
-
-                    // Determine line/column. DWARF line/column is never 0, so we use that
-                    // but other applications may want to display this differently.
-                    let line = row.line().unwrap_or(0);
-
-                    if let Some(file) = file {
-                        file.lines.push(Line {
-                            file: file.name.clone(),
-                            number: line.try_into().unwrap(),
-                            address: row.address().try_into().unwrap(),
-                        });
+    Ok(Some(match tag {
+        gimli::DW_TAG_array_type => match target {
+            Some(element) => TypeKind::Array {
+                element,
+                count: count.map(|count| count.try_into().unwrap()),
+            },
+            None => return Ok(None),
+        },
+        gimli::DW_TAG_enumeration_type => TypeKind::Enum(enumerators),
+        _ => TypeKind::Struct(struct_type),
+    }))
+}
+
+/// Prefixes the name of a type with the namespaces and types it is declared in, for the
+/// languages that have them.
+fn scoped_name(
+    scopes: &[(isize, String)],
+    name: String,
+    language: Option<gimli::DwLang>,
+) -> String {
+    match language {
+        Some(gimli::DW_LANG_Rust)
+        | Some(gimli::DW_LANG_C_plus_plus)
+        | Some(gimli::DW_LANG_C_plus_plus_03)
+        | Some(gimli::DW_LANG_C_plus_plus_11)
+        | Some(gimli::DW_LANG_C_plus_plus_14) => {
+            let mut scoped: Vec<&str> = scopes.iter().map(|(_, scope)| scope.as_str()).collect();
+            scoped.push(&name);
+            scoped.join("::")
+        }
+        _ => name,
+    }
+}
+
+/// Fills in what types built on other types only get from them: the sizes of typedefs and
+/// arrays, and the names of C pointer and qualified types (which the debug info leaves out).
+fn complete_types(types: &mut HashMap<TypeId, Type>) {
+    loop {
+        let mut updates = Vec::new();
+        for (id, dtype) in types.iter() {
+            let (size, name) = match dtype.kind {
+                TypeKind::Alias(Some(target)) => match types.get(&target) {
+                    // a const pointer is written `char *const`, with the qualifier after the `*`
+                    Some(target) if target.name.ends_with('*') => {
+                        (target.size, format!("{}const", target.name))
+                    }
+                    Some(target) => (target.size, format!("const {}", target.name)),
+                    None => continue,
+                },
+                TypeKind::Pointer(target) => {
+                    let target = target.and_then(|target| types.get(&target));
+                    match target {
+                        Some(target) if target.name.ends_with('*') => {
+                            (dtype.size, format!("{}*", target.name))
+                        }
+                        Some(target) if !target.name.is_empty() => {
+                            (dtype.size, format!("{} *", target.name))
+                        }
+                        Some(_) => continue,
+                        None => (dtype.size, "void *".to_string()),
                     }
                 }
+                TypeKind::Array {
+                    element,
+                    count: Some(count),
+                } => match types.get(&element) {
+                    Some(element) => (element.size * count, String::new()),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            // typedefs always have a name; other qualifiers are not told apart
+            let name_missing = dtype.name.is_empty() && !name.is_empty() && name != "const ";
+            if (dtype.size == 0 && size != 0) || name_missing {
+                updates.push((*id, size, name_missing.then_some(name)));
+            }
+        }
+        if updates.is_empty() {
+            break;
+        }
+        for (id, size, name) in updates {
+            let dtype = types.get_mut(&id).unwrap();
+            if dtype.size == 0 {
+                dtype.size = size;
+            }
+            if let Some(name) = name {
+                dtype.name = name;
             }
         }
     }
-    Ok(compilation_units)
+}
+
+/// Returns the qualified name of a function or variable, by demangling its linkage name.
+fn linkage_name<R: Reader>(
+    entry: &gimli::DebuggingInformationEntry<R>,
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+    language: Option<gimli::DwLang>,
+) -> Option<String> {
+    let attr = match entry.attr(gimli::DW_AT_linkage_name).ok()? {
+        Some(attr) => attr,
+        None => entry.attr(gimli::DW_AT_MIPS_linkage_name).ok()??,
+    };
+    match get_attr_value(&attr, unit, dwarf) {
+        Ok(DebugValue::Str(name)) => demangle(&name, language),
+        _ => None,
+    }
+}
+
+/// Demangles a C++ or Rust symbol name, using the scheme of `language`, or whichever fits if the
+/// language is not known. Parameter lists and the hashes Rust appends are left out, giving names
+/// like `ns::Class::method` or `mycrate::module::func`.
+pub fn demangle(name: &str, language: Option<gimli::DwLang>) -> Option<String> {
+    let rust = || {
+        rustc_demangle::try_demangle(name)
+            .ok()
+            .map(|name| format!("{:#}", name))
+    };
+    let cpp = || {
+        let options = cpp_demangle::DemangleOptions::new()
+            .no_params()
+            .no_return_type();
+        cpp_demangle::Symbol::new(name)
+            .ok()?
+            .demangle(&options)
+            .ok()
+    };
+    match language {
+        Some(gimli::DW_LANG_Rust) => rust(),
+        Some(gimli::DW_LANG_C_plus_plus)
+        | Some(gimli::DW_LANG_C_plus_plus_03)
+        | Some(gimli::DW_LANG_C_plus_plus_11)
+        | Some(gimli::DW_LANG_C_plus_plus_14) => cpp(),
+        Some(_) => None,
+        None => rust().or_else(cpp),
+    }
+}
+
+/// Returns the path of the file with index `index` in a unit's line table, joining relative
+/// directories onto the compilation directory.
+fn line_file_path<R: Reader>(
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+    header: &gimli::LineProgramHeader<R>,
+    index: u64,
+    comp_dir: &path::Path,
+) -> Result<Option<path::PathBuf>, Error> {
+    let entry = match header.file(index) {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+    let mut path = comp_dir.to_path_buf();
+    if let Some(dir) = entry.directory(header) {
+        path.push(dwarf.attr_string(unit, dir)?.to_string_lossy()?.as_ref());
+    }
+    path.push(
+        dwarf
+            .attr_string(unit, entry.path_name())?
+            .to_string_lossy()?
+            .as_ref(),
+    );
+    Ok(Some(path))
+}
+
+/// Returns the code addresses a subprogram or inlined call covers, given either by
+/// DW_AT_low_pc and DW_AT_high_pc or by DW_AT_ranges.
+fn get_pc_ranges<R: Reader>(
+    entry: &gimli::DebuggingInformationEntry<R>,
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+) -> Result<Vec<(usize, usize)>, Error> {
+    let mut pc_ranges = Vec::new();
+    if let Some(value) = entry.attr_value(gimli::DW_AT_ranges)? {
+        if let Some(mut ranges) = dwarf.attr_ranges(unit, value)? {
+            while let Some(range) = ranges.next()? {
+                if range.begin < range.end {
+                    pc_ranges.push((
+                        range.begin.try_into().unwrap(),
+                        range.end.try_into().unwrap(),
+                    ));
+                }
+            }
+        }
+        return Ok(pc_ranges);
+    }
+    let low_pc = match entry.attr_value(gimli::DW_AT_low_pc)? {
+        Some(value) => dwarf.attr_address(unit, value)?,
+        None => None,
+    };
+    if let Some(low_pc) = low_pc {
+        let high_pc = match entry.attr_value(gimli::DW_AT_high_pc)? {
+            // the length of the code, since DWARF 4
+            Some(gimli::AttributeValue::Udata(length)) => low_pc + length,
+            // the address right after the code
+            Some(value) => dwarf.attr_address(unit, value)?.unwrap_or(low_pc),
+            None => low_pc,
+        };
+        pc_ranges.push((low_pc.try_into().unwrap(), high_pc.try_into().unwrap()));
+    }
+    Ok(pc_ranges)
+}
+
+/// Drops `.` components, so that equivalent paths compare equal.
+fn normalize_path(path: &path::Path) -> path::PathBuf {
+    path.components()
+        .filter(|component| *component != path::Component::CurDir)
+        .collect()
+}
+
+/// Tags in `.debug_cu_index` naming the section a column of the index refers to.
+const DW_SECT_INFO: u32 = 1;
+const DW_SECT_ABBREV: u32 = 3;
+const DW_SECT_LINE: u32 = 4;
+/// `.debug_loclists.dwo` in DWARF 5 packages, `.debug_loc.dwo` in GNU (version 2) ones
+const DW_SECT_LOC: u32 = 5;
+const DW_SECT_STR_OFFSETS: u32 = 6;
+/// Only in DWARF 5 packages
+const DW_SECT_RNGLISTS: u32 = 8;
+
+/// The units of a `.dwp` package, each with the `(section, offset, size)` of its part of each
+/// section.
+type UnitIndex = Vec<(gimli::DwoId, Vec<(gimli::SectionId, usize, usize)>)>;
+
+/// Debug info split out of the executable with `-gsplit-dwarf`: either a `.dwo` file holding a
+/// single unit, or a `.dwp` package combining the units of a whole program.
+struct SplitDwarfFile {
+    endian: gimli::RunTimeEndian,
+    sections: HashMap<gimli::SectionId, Vec<u8>>,
+    /// Only for packages
+    units: Option<UnitIndex>,
+}
+
+impl SplitDwarfFile {
+    fn open(path: &path::Path) -> Option<SplitDwarfFile> {
+        let data = std::fs::read(path).ok()?;
+        let object = object::File::parse(&data).ok()?;
+        let endian = if object.is_little_endian() {
+            gimli::RunTimeEndian::Little
+        } else {
+            gimli::RunTimeEndian::Big
+        };
+        let mut sections = HashMap::new();
+        for id in &[
+            gimli::SectionId::DebugAbbrev,
+            gimli::SectionId::DebugInfo,
+            gimli::SectionId::DebugLine,
+            gimli::SectionId::DebugLoc,
+            gimli::SectionId::DebugLocLists,
+            gimli::SectionId::DebugRngLists,
+            gimli::SectionId::DebugStr,
+            gimli::SectionId::DebugStrOffsets,
+        ] {
+            if let Some(data) = object.section_data_by_name(id.dwo_name()?) {
+                sections.insert(*id, data.into_owned());
+            }
+        }
+        let units = match object.section_data_by_name(".debug_cu_index") {
+            Some(index) => Some(parse_cu_index(&index, endian).ok()?),
+            None => None,
+        };
+        Some(SplitDwarfFile {
+            endian,
+            sections,
+            units,
+        })
+    }
+
+    fn contains(&self, dwo_id: gimli::DwoId) -> bool {
+        match self.units {
+            Some(ref units) => units.iter().any(|(id, _)| *id == dwo_id),
+            None => true,
+        }
+    }
+
+    /// Returns the sections holding unit `dwo_id`, with the executable's `.debug_addr`, which
+    /// split units take their addresses from.
+    fn dwarf<'a>(
+        &'a self,
+        dwo_id: gimli::DwoId,
+        parent: &gimli::Dwarf<gimli::EndianSlice<'a, gimli::RunTimeEndian>>,
+    ) -> Option<gimli::Dwarf<gimli::EndianSlice<'a, gimli::RunTimeEndian>>> {
+        let contributions = match self.units {
+            Some(ref units) => Some(&units.iter().find(|(id, _)| *id == dwo_id)?.1),
+            None => None,
+        };
+        let load_section = |id: gimli::SectionId| -> Result<_, ()> {
+            let mut data = self.sections.get(&id).map(|data| &data[..]).unwrap_or(&[]);
+            if let Some(&(_, offset, size)) = contributions
+                .and_then(|sections| sections.iter().find(|(section, _, _)| *section == id))
+            {
+                data = data.get(offset..offset + size).ok_or(())?;
+            }
+            Ok(gimli::EndianSlice::new(data, self.endian))
+        };
+        let load_section_sup = |_| Ok(gimli::EndianSlice::new(&[][..], self.endian));
+        let mut dwarf = gimli::Dwarf::load(load_section, load_section_sup).ok()?;
+        dwarf.file_type = gimli::DwarfFileType::Dwo;
+        dwarf.debug_addr = parent.debug_addr;
+        Some(dwarf)
+    }
+}
+
+/// Parses the unit index of a `.dwp` package.
+fn parse_cu_index(data: &[u8], endian: gimli::RunTimeEndian) -> Result<UnitIndex, gimli::Error> {
+    use gimli::Reader as _;
+    let mut header = gimli::EndianSlice::new(data, endian);
+    // a 2 byte version followed by 2 bytes of padding in DWARF 5, a 4 byte one before that
+    let version = header.read_u32()? & 0xffff;
+    let section_count = header.read_u32()? as usize;
+    let unit_count = header.read_u32()? as usize;
+    let slot_count = header.read_u32()? as usize;
+
+    let mut hashes = header;
+    let mut indices = header;
+    indices.skip(slot_count * 8)?;
+    let mut columns = indices;
+    columns.skip(slot_count * 4)?;
+    let mut section_ids = Vec::new();
+    for _ in 0..section_count {
+        section_ids.push(match (columns.read_u32()?, version) {
+            (DW_SECT_INFO, _) => Some(gimli::SectionId::DebugInfo),
+            (DW_SECT_ABBREV, _) => Some(gimli::SectionId::DebugAbbrev),
+            (DW_SECT_LINE, _) => Some(gimli::SectionId::DebugLine),
+            (DW_SECT_LOC, 5) => Some(gimli::SectionId::DebugLocLists),
+            (DW_SECT_LOC, _) => Some(gimli::SectionId::DebugLoc),
+            (DW_SECT_STR_OFFSETS, _) => Some(gimli::SectionId::DebugStrOffsets),
+            (DW_SECT_RNGLISTS, 5) => Some(gimli::SectionId::DebugRngLists),
+            _ => None,
+        });
+    }
+    let offsets = columns;
+    let mut sizes = offsets;
+    // the counts come straight from the file, so a corrupt header must not overflow
+    let table_size = unit_count
+        .checked_mul(section_count * 4)
+        .ok_or(gimli::Error::BadLength)?;
+    sizes.skip(table_size)?;
+
+    let mut units = Vec::new();
+    for _ in 0..slot_count {
+        let dwo_id = gimli::DwoId(hashes.read_u64()?);
+        let row = indices.read_u32()? as usize;
+        if row == 0 {
+            continue;
+        }
+        if row > unit_count {
+            return Err(gimli::Error::OffsetOutOfBounds);
+        }
+        let mut row_offsets = offsets;
+        row_offsets.skip((row - 1) * section_count * 4)?;
+        let mut row_sizes = sizes;
+        row_sizes.skip((row - 1) * section_count * 4)?;
+        let mut contributions = Vec::new();
+        for id in &section_ids {
+            let offset = row_offsets.read_u32()? as usize;
+            let size = row_sizes.read_u32()? as usize;
+            if let Some(id) = id {
+                contributions.push((*id, offset, size));
+            }
+        }
+        units.push((dwo_id, contributions));
+    }
+    Ok(units)
 }
 
 #[derive(Debug, Clone)]
@@ -255,7 +1230,6 @@ pub enum DebugValue {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Error {
     GimliError(gimli::Error),
-    Addr2lineError(addr2line::gimli::Error),
     ObjectError(String),
     IoError,
 }
@@ -266,12 +1240,6 @@ impl From<gimli::Error> for Error {
     }
 }
 
-impl From<addr2line::gimli::Error> for Error {
-    fn from(err: addr2line::gimli::Error) -> Self {
-        Error::Addr2lineError(err)
-    }
-}
-
 impl From<io::Error> for Error {
     fn from(_: io::Error) -> Self {
         Error::IoError
@@ -291,25 +1259,216 @@ impl<'input, Endian> Reader for gimli::EndianSlice<'input, Endian> where
 
 trait Reader: gimli::Reader<Offset = usize> + Send + Sync {}
 
-fn get_location<R: Reader>(attr: &gimli::Attribute<R>, unit: &gimli::Unit<R>) -> Option<Location> {
-    if let gimli::AttributeValue::Exprloc(ref data) = attr.value() {
-        let encoding = unit.encoding();
-        let mut pc = data.0.clone();
-        if pc.len() > 0 {
-            if let Ok(op) = gimli::Operation::parse(&mut pc, encoding) {
-                match op {
-                    gimli::Operation::FrameOffset { offset } => {
-                        return Some(Location::FramePointerOffset(offset.try_into().unwrap()));
-                    }
-                    gimli::Operation::Address { address } => {
-                        return Some(Location::Address(address.try_into().unwrap()));
-                    }
-                    _ => {}
-                }
+/// Converts a unit-relative DIE offset to the section offset that `DW_AT_type` references are
+/// resolved to by `get_attr_value`.
+fn section_offset<R: Reader>(offset: UnitOffset, unit: &gimli::Unit<R>) -> usize {
+    match offset.to_unit_section_offset(unit) {
+        UnitSectionOffset::DebugInfoOffset(goff) => goff.0,
+        UnitSectionOffset::DebugTypesOffset(goff) => goff.0,
+    }
+}
+
+/// Copies a DWARF expression out of the section data. Operations that index into `.debug_addr`
+/// (used by split DWARF) are replaced by the address itself, since the expression is evaluated
+/// long after the sections are gone.
+fn owned_expression<R: Reader>(
+    data: &gimli::Expression<R>,
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+) -> Option<DwarfExpr> {
+    let encoding = unit.encoding();
+    let mut bytecode = Vec::new();
+    let mut pc = data.0.clone();
+    while pc.len() != 0 {
+        let start = pc.clone();
+        let op = gimli::Operation::parse(&mut pc, encoding).ok()?;
+        match op {
+            gimli::Operation::AddressIndex { index } => {
+                bytecode.push(gimli::DW_OP_addr.0);
+                bytecode.extend_from_slice(&dwarf.address(unit, index).ok()?.to_le_bytes());
+            }
+            gimli::Operation::ConstantIndex { index } => {
+                bytecode.push(gimli::DW_OP_const8u.0);
+                bytecode.extend_from_slice(&dwarf.address(unit, index).ok()?.to_le_bytes());
+            }
+            _ => {
+                let len = start.len() - pc.len();
+                bytecode.extend_from_slice(&start.clone().split(len).ok()?.to_slice().ok()?);
             }
         }
     }
-    None
+    Some(DwarfExpr { bytecode, encoding })
+}
+
+/// Reads a location attribute (`DW_AT_location`, `DW_AT_frame_base`), which is either a single
+/// expression or a reference to a location list in `.debug_loc`/`.debug_loclists`.
+fn get_location<R: Reader>(
+    attr: &gimli::Attribute<R>,
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+) -> Option<Location> {
+    if let gimli::AttributeValue::Exprloc(ref data) = attr.value() {
+        return owned_expression(data, unit, dwarf).map(Location::Expression);
+    }
+    let mut locations = dwarf.attr_locations(unit, attr.value()).ok()??;
+    let mut entries = Vec::new();
+    while let Ok(Some(entry)) = locations.next() {
+        entries.push((
+            entry.range.begin.try_into().ok()?,
+            entry.range.end.try_into().ok()?,
+            owned_expression(&entry.data, unit, dwarf)?,
+        ));
+    }
+    Some(Location::List(entries))
+}
+
+/// Reads `DW_AT_const_value`, given to variables the compiler replaced by a constant.
+fn get_const_value<R: Reader>(attr: &gimli::Attribute<R>) -> Option<Vec<u8>> {
+    Some(match attr.value() {
+        gimli::AttributeValue::Data1(value) => vec![value],
+        gimli::AttributeValue::Data2(value) => value.to_le_bytes().to_vec(),
+        gimli::AttributeValue::Data4(value) => value.to_le_bytes().to_vec(),
+        gimli::AttributeValue::Data8(value) => value.to_le_bytes().to_vec(),
+        gimli::AttributeValue::Sdata(value) => value.to_le_bytes().to_vec(),
+        gimli::AttributeValue::Udata(value) => value.to_le_bytes().to_vec(),
+        gimli::AttributeValue::Block(data) => data.to_slice().ok()?.to_vec(),
+        _ => return None,
+    })
+}
+
+/// Where a DWARF location description says a value lives.
+pub enum Place {
+    Register(u16),
+    Address(u64),
+}
+
+/// Runs a DWARF expression against the state of the stopped program, supplying registers,
+/// memory, the frame base and the CFA as the evaluation asks for them.
+fn evaluate<'a>(
+    expr: &'a DwarfExpr,
+    state: &dyn ProgramState,
+    frame_base: Option<u64>,
+    cfa: Option<u64>,
+) -> Result<Vec<gimli::Piece<gimli::EndianSlice<'a, gimli::LittleEndian>>>, EvalError> {
+    let bytecode = gimli::EndianSlice::new(&expr.bytecode, gimli::LittleEndian);
+    let mut eval = gimli::Expression(bytecode).evaluation(expr.encoding);
+    let mut result = eval.evaluate()?;
+    loop {
+        result = match result {
+            gimli::EvaluationResult::Complete => return Ok(eval.result()),
+            gimli::EvaluationResult::RequiresMemory { address, size, .. } => {
+                let bytes = state
+                    .read_memory(address, size as usize)
+                    .ok_or(EvalError::Memory(address))?;
+                let mut word = [0u8; 8];
+                word[..bytes.len()].copy_from_slice(&bytes);
+                eval.resume_with_memory(gimli::Value::Generic(u64::from_le_bytes(word)))?
+            }
+            gimli::EvaluationResult::RequiresRegister { register, .. } => {
+                let value = state.register(register.0).ok_or_else(|| {
+                    EvalError::Unsupported(format!("Cannot read DWARF register {}", register.0))
+                })?;
+                eval.resume_with_register(gimli::Value::Generic(value))?
+            }
+            gimli::EvaluationResult::RequiresFrameBase => {
+                let frame_base = frame_base.ok_or_else(|| {
+                    EvalError::Unsupported("Could not find the frame base".to_string())
+                })?;
+                eval.resume_with_frame_base(frame_base)?
+            }
+            gimli::EvaluationResult::RequiresCallFrameCfa => {
+                let cfa = cfa.ok_or_else(|| {
+                    EvalError::Unsupported("Could not compute the CFA".to_string())
+                })?;
+                eval.resume_with_call_frame_cfa(cfa)?
+            }
+            gimli::EvaluationResult::RequiresRelocatedAddress(address) => {
+                eval.resume_with_relocated_address(address)?
+            }
+            // the value a register had on entry to the function is gone by now
+            gimli::EvaluationResult::RequiresEntryValue(_) => return Err(EvalError::OptimizedOut),
+            gimli::EvaluationResult::RequiresTls(_) => {
+                return Err(EvalError::Unsupported(
+                    "Cannot access thread-local variables".to_string(),
+                ))
+            }
+            other => {
+                return Err(EvalError::Unsupported(format!(
+                    "Unsupported DWARF expression: {:?}",
+                    other
+                )))
+            }
+        };
+    }
+}
+
+/// Evaluates a location description that names a single place, such as `DW_AT_frame_base`.
+pub fn evaluate_location(
+    expr: &DwarfExpr,
+    state: &dyn ProgramState,
+    frame_base: Option<u64>,
+    cfa: Option<u64>,
+) -> Result<Place, EvalError> {
+    let pieces = evaluate(expr, state, frame_base, cfa)?;
+    match pieces.first().map(|piece| piece.location) {
+        Some(gimli::Location::Register { register }) => Ok(Place::Register(register.0)),
+        Some(gimli::Location::Address { address }) => Ok(Place::Address(address)),
+        Some(gimli::Location::Value { value }) => Ok(Place::Address(value.to_u64(!0)?)),
+        _ => Err(EvalError::OptimizedOut),
+    }
+}
+
+/// Evaluates a variable's location and reads its `size` bytes, putting the value together from
+/// all of its pieces.
+pub fn read_location(
+    expr: &DwarfExpr,
+    state: &dyn ProgramState,
+    frame_base: Option<u64>,
+    cfa: Option<u64>,
+    size: usize,
+) -> Result<Vec<u8>, EvalError> {
+    let mut bytes = Vec::new();
+    for piece in evaluate(expr, state, frame_base, cfa)? {
+        let piece_size = match piece.size_in_bits {
+            Some(bits) => (bits as usize).div_ceil(8),
+            None => size,
+        };
+        let mut piece_bytes = match piece.location {
+            gimli::Location::Empty => return Err(EvalError::OptimizedOut),
+            gimli::Location::Register { register } => state
+                .register(register.0)
+                .ok_or_else(|| {
+                    EvalError::Unsupported(format!("Cannot read DWARF register {}", register.0))
+                })?
+                .to_le_bytes()
+                .to_vec(),
+            gimli::Location::Address { address } => state
+                .read_memory(address, piece_size)
+                .ok_or(EvalError::Memory(address))?,
+            gimli::Location::Value { value } => match value {
+                gimli::Value::F32(value) => value.to_bits().to_le_bytes().to_vec(),
+                gimli::Value::F64(value) => value.to_bits().to_le_bytes().to_vec(),
+                value => value.to_u64(!0)?.to_le_bytes().to_vec(),
+            },
+            gimli::Location::Bytes { value } => value.slice().to_vec(),
+            gimli::Location::ImplicitPointer { .. } => {
+                return Err(EvalError::Unsupported("<synthetic pointer>".to_string()))
+            }
+        };
+        piece_bytes.resize(piece_size, 0);
+        bytes.extend(piece_bytes);
+    }
+    Ok(bytes)
+}
+
+/// Formats an expression the way dwarfdump does, e.g. `DW_OP_fbreg -20`.
+pub fn format_expression(expr: &DwarfExpr) -> String {
+    let mut text = String::new();
+    let bytecode = gimli::EndianSlice::new(&expr.bytecode, gimli::LittleEndian);
+    if dump_exprloc(&mut text, expr.encoding, &gimli::Expression(bytecode)).is_err() {
+        text.push_str("<invalid expression>");
+    }
+    text.trim_end().to_string()
 }
 
 // based on dwarf_dump.rs
@@ -338,6 +1497,17 @@ fn get_attr_value<R: Reader>(
                 Ok(DebugValue::Str(format!("<.debug_str+0x{:08x}>", offset.0)))
             }
         }
+        // DWARF 5 strings in .debug_line_str, or found through .debug_str_offsets
+        gimli::AttributeValue::DebugLineStrRef(_)
+        | gimli::AttributeValue::DebugStrOffsetsIndex(_)
+        | gimli::AttributeValue::DebugStrRefSup(_) => {
+            let s = dwarf.attr_string(unit, value)?;
+            Ok(DebugValue::Str(format!("{}", s.to_string_lossy()?)))
+        }
+        // DWARF 5 addresses stored in .debug_addr
+        gimli::AttributeValue::DebugAddrIndex(index) => {
+            Ok(DebugValue::Uint(dwarf.address(unit, index)?))
+        }
         gimli::AttributeValue::Sdata(data) => Ok(DebugValue::Int(data)),
         gimli::AttributeValue::Addr(data) => Ok(DebugValue::Uint(data)),
         gimli::AttributeValue::Udata(data) => Ok(DebugValue::Uint(data)),
@@ -604,3 +1774,67 @@ fn dump_op<R: Reader, W: Write>(
     };
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a little-endian DWARF 5 `.debug_cu_index` header followed by `rest`.
+    fn index(section_count: u32, unit_count: u32, slot_count: u32, rest: &[u32]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for word in [5, section_count, unit_count, slot_count]
+            .iter()
+            .chain(rest)
+        {
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn cu_index_empty() {
+        let endian = gimli::RunTimeEndian::Little;
+        assert!(parse_cu_index(&[], endian).is_err());
+        assert_eq!(
+            parse_cu_index(&index(0, 0, 0, &[]), endian).unwrap(),
+            Vec::new()
+        );
+    }
+
+    #[test]
+    fn cu_index_one_unit() {
+        #[rustfmt::skip]
+        let rest = [
+            // hashes of the two slots, as 64 bit values
+            0x1234, 0, 0, 0,
+            // row of each slot, 0 for an empty one
+            1, 0,
+            // column headers
+            DW_SECT_INFO, DW_SECT_ABBREV,
+            // offsets, then sizes
+            0x10, 0x30,
+            0x20, 0x40,
+        ];
+        let units = parse_cu_index(&index(2, 1, 2, &rest), gimli::RunTimeEndian::Little).unwrap();
+        assert_eq!(
+            units,
+            vec![(
+                gimli::DwoId(0x1234),
+                vec![
+                    (gimli::SectionId::DebugInfo, 0x10, 0x20),
+                    (gimli::SectionId::DebugAbbrev, 0x30, 0x40),
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn cu_index_oversized() {
+        let endian = gimli::RunTimeEndian::Little;
+        assert!(parse_cu_index(&index(u32::MAX, u32::MAX, u32::MAX, &[]), endian).is_err());
+        assert!(parse_cu_index(&index(1, u32::MAX, 0, &[DW_SECT_INFO]), endian).is_err());
+        // a row past the end of the table
+        let rest = [0x1234, 0, 2, DW_SECT_INFO, 0x10, 0x20];
+        assert!(parse_cu_index(&index(1, 1, 1, &rest), endian).is_err());
+    }
+}
@@ -1,18 +1,22 @@
+use nix::errno::Errno;
 use nix::sys::ptrace::getregs;
 use nix::sys::ptrace::{self, setregs};
 use nix::sys::signal;
 use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
 use nix::unistd::Pid;
 use std::collections::HashMap;
+use std::convert::TryInto;
 use std::fmt;
+use std::fs::{self, File};
 use std::mem::size_of;
 use std::os::unix::process::CommandExt;
 use std::process::Child;
-use std::process::Command;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
 
 use crate::debugger::BreakPoint;
 
-use crate::dwarf_data::{self, DwarfData};
+use crate::dwarf_data::{self, DwarfData, ProgramState};
 
 #[derive(PartialEq)]
 pub enum Status {
@@ -26,6 +30,17 @@ pub enum Status {
     /// Indicates the inferior exited due to a signal. Contains the signal that killed the
     /// process.
     Signaled(signal::Signal),
+
+    /// Indicates the inferior stopped on entering or leaving a system call. Contains the
+    /// current instruction pointer, just after the syscall instruction.
+    Syscall(SyscallStop, usize),
+}
+
+/// Which side of a system call a `Status::Syscall` stop is on.
+#[derive(PartialEq, Clone, Copy)]
+pub enum SyscallStop {
+    Entry,
+    Exit,
 }
 
 impl fmt::Display for Status {
@@ -34,7 +49,47 @@ impl fmt::Display for Status {
             Self::Exited(code) => write!(f, "exited (status {})", code),
             Self::Stopped(sig, _eip) => write!(f, "stopped (signal {})", sig),
             Self::Signaled(sig) => write!(f, "{}", sig),
+            Self::Syscall(SyscallStop::Entry, _) => write!(f, "entered a system call"),
+            Self::Syscall(SyscallStop::Exit, _) => write!(f, "returned from a system call"),
+        }
+    }
+}
+
+/// Set when Ctrl+C is pressed while the inferior runs.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Pid of the running inferior if it is in a session of its own, which Ctrl+C on deet's terminal
+/// doesn't reach; 0 otherwise.
+static RUNNING_ELSEWHERE: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+    let pid = RUNNING_ELSEWHERE.load(Ordering::SeqCst);
+    if pid != 0 {
+        unsafe { libc::kill(pid, libc::SIGSTOP) };
+    }
+}
+
+/// Makes Ctrl+C stop the running inferior rather than deet. An inferior on deet's terminal gets
+/// the SIGINT too, which stops it since it is traced; one on another terminal is sent SIGSTOP.
+/// Unlike ignoring SIGINT, a handler is reset by exec, so programs deet starts can still be
+/// interrupted when they are not being debugged.
+pub fn catch_interrupts() -> Result<(), nix::Error> {
+    unsafe { signal::signal(signal::SIGINT, signal::SigHandler::Handler(on_interrupt)) }?;
+    Ok(())
+}
+
+/// Reports a stop caused by Ctrl+C or `interrupt` as SIGINT, whichever signal stopped the
+/// inferior. The signal is not delivered when it goes on, as resuming doesn't pass on the signal
+/// it stopped with.
+fn interrupted(status: Status) -> Status {
+    match status {
+        Status::Stopped(signal::SIGINT, rip) | Status::Stopped(signal::SIGSTOP, rip)
+            if INTERRUPTED.swap(false, Ordering::SeqCst) =>
+        {
+            Status::Stopped(signal::SIGINT, rip)
         }
+        status => status,
     }
 }
 
@@ -47,12 +102,267 @@ fn child_traceme() -> Result<(), std::io::Error> {
     )))
 }
 
+/// Makes the process the leader of a new session, with the terminal on its stdin as the
+/// controlling terminal, so that it can read from the terminal and get its signals.
+fn child_new_session() -> Result<(), std::io::Error> {
+    nix::unistd::setsid().or(Err(std::io::Error::other("setsid failed")))?;
+    // stdin may have been redirected away from the terminal, which is fine
+    unsafe { libc::ioctl(0, libc::TIOCSCTTY, 0) };
+    Ok(())
+}
+
 fn align_addr_to_word(addr: usize) -> usize {
     addr & (-(size_of::<usize>() as isize) as usize)
 }
 
+/// Looks up a general purpose register by name (`rax`, `r8`, `eax`, `rip`, ...).
+pub fn register_value(regs: &libc::user_regs_struct, name: &str) -> Option<u64> {
+    let value = match name.trim_start_matches('$') {
+        "rax" | "eax" => regs.rax,
+        "rbx" | "ebx" => regs.rbx,
+        "rcx" | "ecx" => regs.rcx,
+        "rdx" | "edx" => regs.rdx,
+        "rsi" | "esi" => regs.rsi,
+        "rdi" | "edi" => regs.rdi,
+        "rbp" | "ebp" => regs.rbp,
+        "rsp" | "esp" => regs.rsp,
+        "r8" | "r8d" => regs.r8,
+        "r9" | "r9d" => regs.r9,
+        "r10" | "r10d" => regs.r10,
+        "r11" | "r11d" => regs.r11,
+        "r12" | "r12d" => regs.r12,
+        "r13" | "r13d" => regs.r13,
+        "r14" | "r14d" => regs.r14,
+        "r15" | "r15d" => regs.r15,
+        "rip" | "pc" => regs.rip,
+        "eflags" => regs.eflags,
+        _ => return None,
+    };
+    if name.trim_start_matches('$').starts_with('e') || name.ends_with('d') {
+        Some(value & 0xffff_ffff)
+    } else {
+        Some(value)
+    }
+}
+
+/// Looks up a register by its DWARF register number, as used in location expressions and call
+/// frame information. Numbers 17 to 32 are xmm0-xmm15, of which the low 64 bits are returned.
+pub fn dwarf_register_value(
+    regs: &libc::user_regs_struct,
+    fpregs: &libc::user_fpregs_struct,
+    register: u16,
+) -> Option<u64> {
+    Some(match register {
+        0 => regs.rax,
+        1 => regs.rdx,
+        2 => regs.rcx,
+        3 => regs.rbx,
+        4 => regs.rsi,
+        5 => regs.rdi,
+        6 => regs.rbp,
+        7 => regs.rsp,
+        8 => regs.r8,
+        9 => regs.r9,
+        10 => regs.r10,
+        11 => regs.r11,
+        12 => regs.r12,
+        13 => regs.r13,
+        14 => regs.r14,
+        15 => regs.r15,
+        16 => regs.rip,
+        17..=32 => {
+            let words = &fpregs.xmm_space[(register as usize - 17) * 4..];
+            (words[0] as u64) | ((words[1] as u64) << 32)
+        }
+        _ => return None,
+    })
+}
+
+/// nix has no wrapper for PTRACE_GETFPREGS, so read the x87/SSE state with libc directly.
+pub fn getfpregs(pid: Pid) -> Result<libc::user_fpregs_struct, nix::Error> {
+    let mut fpregs: libc::user_fpregs_struct = unsafe { std::mem::zeroed() };
+    let res = unsafe {
+        libc::ptrace(
+            libc::PTRACE_GETFPREGS,
+            pid.as_raw(),
+            std::ptr::null_mut::<libc::c_void>(),
+            &mut fpregs as *mut libc::user_fpregs_struct as *mut libc::c_void,
+        )
+    };
+    Errno::result(res)?;
+    Ok(fpregs)
+}
+
+pub fn setfpregs(pid: Pid, mut fpregs: libc::user_fpregs_struct) -> Result<(), nix::Error> {
+    let res = unsafe {
+        libc::ptrace(
+            libc::PTRACE_SETFPREGS,
+            pid.as_raw(),
+            std::ptr::null_mut::<libc::c_void>(),
+            &mut fpregs as *mut libc::user_fpregs_struct as *mut libc::c_void,
+        )
+    };
+    Errno::result(res)?;
+    Ok(())
+}
+
+/// Options every tracee runs with. TRACESYSGOOD makes syscall stops distinguishable from
+/// SIGTRAPs caused by the program.
+const TRACE_OPTIONS: ptrace::Options = ptrace::Options::PTRACE_O_TRACESYSGOOD;
+
+/// Values of `ptrace_syscall_info.op`, which libc does not define.
+const PTRACE_SYSCALL_INFO_ENTRY: u8 = 1;
+const PTRACE_SYSCALL_INFO_EXIT: u8 = 2;
+
+/// Asks the kernel whether a tracee in a syscall stop is entering or leaving the call.
+fn syscall_stop_kind(pid: Pid) -> Result<SyscallStop, nix::Error> {
+    let mut info: libc::ptrace_syscall_info = unsafe { std::mem::zeroed() };
+    let res = unsafe {
+        libc::ptrace(
+            libc::PTRACE_GET_SYSCALL_INFO,
+            pid.as_raw(),
+            size_of::<libc::ptrace_syscall_info>(),
+            &mut info as *mut libc::ptrace_syscall_info as *mut libc::c_void,
+        )
+    };
+    Errno::result(res)?;
+    match info.op {
+        PTRACE_SYSCALL_INFO_ENTRY => Ok(SyscallStop::Entry),
+        PTRACE_SYSCALL_INFO_EXIT => Ok(SyscallStop::Exit),
+        _ => Err(nix::Error::Sys(Errno::EINVAL)),
+    }
+}
+
+/// Encoding of the x86-64 `syscall` instruction.
+const SYSCALL_INSN: [u8; 2] = [0x0f, 0x05];
+
+/// Size of the area below the stack pointer that the SysV ABI lets leaf functions use without
+/// adjusting rsp. An injected call frame must not clobber it.
+const RED_ZONE_SIZE: usize = 128;
+
+/// An argument for a function called in the inferior with `Inferior::call_function`.
+pub enum CallArg {
+    /// Passed as-is in an integer register.
+    Int(u64),
+    /// Copied (NUL terminated) onto the inferior's stack and passed as a pointer.
+    Str(String),
+}
+
+/// The result of running a function inside the inferior.
+pub enum CallOutcome {
+    /// The function returned normally. Contains the registers it returned with, which hold its
+    /// return value.
+    Returned {
+        regs: Box<libc::user_regs_struct>,
+        fpregs: Box<libc::user_fpregs_struct>,
+    },
+
+    /// Something else stopped the inferior before the function returned (a breakpoint, a
+    /// signal, or the process exiting).
+    Interrupted(Status),
+}
+
+/// Frames `backtrace` goes up before giving up, in case the stack is corrupt
+const MAX_FRAMES: usize = 4096;
+
+/// The registers of a stopped inferior, together with access to its memory.
+pub struct FrameState<'a> {
+    inferior: &'a Inferior,
+    pub regs: libc::user_regs_struct,
+    pub fpregs: libc::user_fpregs_struct,
+    /// Set for the frames of callers, of which only the callee-saved registers are known
+    outer: bool,
+}
+
+impl ProgramState for FrameState<'_> {
+    fn register(&self, register: u16) -> Option<u64> {
+        let known = !self.outer
+            || register == dwarf_data::DWARF_PC_REGISTER
+            || register == dwarf_data::DWARF_SP_REGISTER
+            || dwarf_data::CALLEE_SAVED_REGISTERS.contains(&register);
+        if !known {
+            return None;
+        }
+        dwarf_register_value(&self.regs, &self.fpregs, register)
+    }
+
+    fn read_memory(&self, addr: u64, len: usize) -> Option<Vec<u8>> {
+        self.inferior.read_memory(addr as usize, len).ok()
+    }
+}
+
+/// How to start an inferior, besides its arguments.
+#[derive(Default)]
+pub struct Launch {
+    /// The whole environment of the inferior, or `None` for deet's own
+    pub env: Option<Vec<(String, String)>>,
+    /// Working directory, or `None` for deet's own
+    pub cwd: Option<String>,
+    /// Files for the standard streams; `None` shares deet's
+    pub stdin: Option<File>,
+    pub stdout: Option<File>,
+    pub stderr: Option<File>,
+    /// Whether the inferior runs in a session of its own, on a terminal other than deet's
+    pub new_session: bool,
+}
+
 pub struct Inferior {
-    child: Child,
+    pid: Pid,
+    /// The process deet spawned itself. Checkpoints are forked from it and have no `Child`.
+    child: Option<Child>,
+    /// Whether it runs in a session of its own, on a terminal other than deet's
+    own_session: bool,
+}
+
+impl<'a> FrameState<'a> {
+    /// Returns the state of the frame that called this one, or `None` for the outermost frame.
+    /// Uses the call frame information, falling back to following the frame pointer for code
+    /// that has none.
+    pub fn caller(&self, debug_data: &DwarfData) -> Option<FrameState<'a>> {
+        let mut regs = self.regs;
+        match debug_data.unwind_frame(self) {
+            Some(unwound) => {
+                for (register, value) in unwound {
+                    match register {
+                        3 => regs.rbx = value,
+                        6 => regs.rbp = value,
+                        7 => regs.rsp = value,
+                        12 => regs.r12 = value,
+                        13 => regs.r13 = value,
+                        14 => regs.r14 = value,
+                        15 => regs.r15 = value,
+                        16 => regs.rip = value,
+                        _ => {}
+                    }
+                }
+            }
+            // at the first instruction of a function, only the return address has been pushed
+            None if matches!(
+                debug_data.symbols().symbol_at(self.regs.rip as usize),
+                Some((_, 0))
+            ) =>
+            {
+                let ret = self.inferior.read_memory(self.regs.rsp as usize, 8).ok()?;
+                regs.rip = u64::from_le_bytes(ret[..].try_into().unwrap());
+                regs.rsp = self.regs.rsp + 8;
+            }
+            None => {
+                if self.regs.rbp == 0 {
+                    return None;
+                }
+                let frame = self.inferior.read_memory(self.regs.rbp as usize, 16).ok()?;
+                regs.rbp = u64::from_le_bytes(frame[..8].try_into().unwrap());
+                regs.rip = u64::from_le_bytes(frame[8..].try_into().unwrap());
+                regs.rsp = self.regs.rbp + 16;
+            }
+        }
+        Some(FrameState {
+            inferior: self.inferior,
+            regs,
+            fpregs: self.fpregs,
+            outer: true,
+        })
+    }
 }
 
 impl Inferior {
@@ -61,12 +371,44 @@ impl Inferior {
     pub fn new(
         target: &str,
         args: &Vec<String>,
+        launch: Launch,
         breakset: &Vec<usize>,
         breakpoint_set: &mut HashMap<usize, BreakPoint>,
     ) -> Option<Inferior> {
-        let mut command = Command::new(target);
+        let mut command = match &launch.cwd {
+            // a relative path to the program would be looked up in the new directory
+            Some(cwd) => {
+                let target = if target.contains('/') {
+                    fs::canonicalize(target).ok()?
+                } else {
+                    target.into()
+                };
+                let mut command = Command::new(target);
+                command.current_dir(cwd);
+                command
+            }
+            None => Command::new(target),
+        };
+        if let Some(env) = launch.env {
+            command.env_clear().envs(env);
+        }
+        if let Some(stdin) = launch.stdin {
+            command.stdin(Stdio::from(stdin));
+        }
+        if let Some(stdout) = launch.stdout {
+            command.stdout(Stdio::from(stdout));
+        }
+        if let Some(stderr) = launch.stderr {
+            command.stderr(Stdio::from(stderr));
+        }
+        let new_session = launch.new_session;
         unsafe {
-            command.args(args).pre_exec(|| child_traceme());
+            command.args(args).pre_exec(move || {
+                if new_session {
+                    child_new_session()?;
+                }
+                child_traceme()
+            });
         };
 
         if let Ok(child_process) = command.spawn() {
@@ -84,8 +426,12 @@ impl Inferior {
                         // make 0xcc, int, interupt to the address
                         // in order to make a 'break point'
                         let mut final_inferior = Inferior {
-                            child: child_process,
+                            pid: child_pid,
+                            child: Some(child_process),
+                            own_session: new_session,
                         };
+                        ptrace::setoptions(child_pid, TRACE_OPTIONS)
+                            .expect("unable to set ptrace options");
                         for breakpoint in breakset {
                             let ret_byte = final_inferior
                                 .write_byte(*breakpoint, 0xcc)
@@ -115,78 +461,274 @@ impl Inferior {
 
     /// Returns the pid of this inferior.
     pub fn pid(&self) -> Pid {
-        nix::unistd::Pid::from_raw(self.child.id() as i32)
+        self.pid
     }
 
     /// Calls waitpid on this inferior and returns a Status to indicate the state of the process
     /// after the waitpid call.
     pub fn wait(&self, options: Option<WaitPidFlag>) -> Result<Status, nix::Error> {
-        Ok(match waitpid(self.pid(), options)? {
+        self.status(waitpid(self.pid(), options)?)
+    }
+
+    fn status(&self, wait_status: WaitStatus) -> Result<Status, nix::Error> {
+        Ok(match wait_status {
             WaitStatus::Exited(_pid, exit_code) => Status::Exited(exit_code),
             WaitStatus::Signaled(_pid, signal, _core_dumped) => Status::Signaled(signal),
             WaitStatus::Stopped(_pid, signal) => {
                 let regs = ptrace::getregs(self.pid())?;
                 Status::Stopped(signal, regs.rip as usize)
             }
-            other => panic!("waitpid returned unexpected status: {:?}", other),
+            WaitStatus::PtraceSyscall(_pid) => {
+                let regs = ptrace::getregs(self.pid())?;
+                Status::Syscall(syscall_stop_kind(self.pid())?, regs.rip as usize)
+            }
+            // a ptrace event stop (such as a fork the program makes while a checkpoint is being
+            // taken) has nothing to report
+            _ => return Err(nix::Error::Sys(Errno::EINVAL)),
         })
     }
 
+    /// Waits for the inferior to stop after resuming it.
+    fn wait_running(&self) -> Result<Status, nix::Error> {
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        if self.own_session {
+            RUNNING_ELSEWHERE.store(self.pid().as_raw(), Ordering::SeqCst);
+        }
+        let status = self.wait(None);
+        RUNNING_ELSEWHERE.store(0, Ordering::SeqCst);
+        Ok(interrupted(status?))
+    }
+
     /// continue execute trapped process
     pub fn cont_exec(
         &mut self,
         breakpoint_set: &HashMap<usize, BreakPoint>,
     ) -> Result<Status, nix::Error> {
-        let mut regs = getregs(self.pid())?;
-        let rip_val = regs.rip as usize;
+        self.resume(breakpoint_set, false, None)
+    }
 
-        if let Some(bpoint) = breakpoint_set.get(&(rip_val - 1)) {
-            self.write_byte(rip_val - 1, bpoint.orig_byte).ok();
-            regs.rip = (rip_val - 1) as u64;
+    /// Like `cont_exec`, but also stops when the inferior enters or leaves a system call.
+    /// `sig` is a signal to deliver to the inferior as it resumes.
+    pub fn syscall_exec(
+        &mut self,
+        breakpoint_set: &HashMap<usize, BreakPoint>,
+        sig: Option<signal::Signal>,
+    ) -> Result<Status, nix::Error> {
+        self.resume(breakpoint_set, true, sig)
+    }
 
-            setregs(self.pid(), regs).ok();
+    /// Continues the inferior without waiting for it to stop; `poll` tells when it has. Returns
+    /// how it stopped if it did so right away, while stepping off the breakpoint it was at.
+    pub fn cont_background(
+        &mut self,
+        breakpoint_set: &HashMap<usize, BreakPoint>,
+        syscalls: bool,
+    ) -> Result<Option<Status>, nix::Error> {
+        let status = self.start(breakpoint_set, syscalls, None)?;
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        Ok(status)
+    }
+
+    /// Checks whether the inferior continued by `cont_background` has stopped, without waiting.
+    /// Returns how it stopped, or None if it is still running.
+    pub fn poll(
+        &mut self,
+        breakpoint_set: &HashMap<usize, BreakPoint>,
+    ) -> Result<Option<Status>, nix::Error> {
+        match waitpid(self.pid(), Some(WaitPidFlag::WNOHANG))? {
+            WaitStatus::StillAlive => Ok(None),
+            wait_status => {
+                let status = interrupted(self.status(wait_status)?);
+                self.at_breakpoint(status, breakpoint_set).map(Some)
+            }
+        }
+    }
 
-            ptrace::step(self.pid(), None).ok();
+    /// Stops the inferior running in the background, as Ctrl+C would. `poll` reports it as
+    /// stopped by SIGINT.
+    pub fn interrupt(&self) -> Result<(), nix::Error> {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+        signal::kill(self.pid(), signal::SIGSTOP)
+    }
 
-            match self.wait(None).ok().unwrap() {
-                Status::Exited(code) => return Ok(Status::Exited(code)),
-                Status::Signaled(sig) => return Ok(Status::Signaled(sig)),
-                Status::Stopped(_sig, _reg) => {
-                    self.write_byte(rip_val - 1, 0xcc).ok();
-                }
+    fn resume(
+        &mut self,
+        breakpoint_set: &HashMap<usize, BreakPoint>,
+        syscalls: bool,
+        sig: Option<signal::Signal>,
+    ) -> Result<Status, nix::Error> {
+        if let Some(status) = self.start(breakpoint_set, syscalls, sig)? {
+            return Ok(status);
+        }
+        let status = self.wait_running()?;
+        self.at_breakpoint(status, breakpoint_set)
+    }
+
+    /// Lets the inferior go, stepping it off the breakpoint it is at first. Returns how it
+    /// stopped if it did so during that step.
+    fn start(
+        &mut self,
+        breakpoint_set: &HashMap<usize, BreakPoint>,
+        syscalls: bool,
+        sig: Option<signal::Signal>,
+    ) -> Result<Option<Status>, nix::Error> {
+        let rip_val = getregs(self.pid())?.rip as usize;
+
+        if breakpoint_set.contains_key(&rip_val) {
+            match self.step_instruction(breakpoint_set)? {
+                Status::Stopped(_sig, _rip) => (),
+                other => return Ok(Some(other)),
             }
         }
 
-        ptrace::cont(self.pid(), None)?;
-        self.wait(None)
+        if syscalls {
+            ptrace::syscall(self.pid(), sig)?;
+        } else {
+            ptrace::cont(self.pid(), sig)?;
+        }
+        Ok(None)
+    }
+
+    /// Rewinds over the int3 if the inferior stopped at a breakpoint, so that it is at the
+    /// breakpoint's address.
+    fn at_breakpoint(
+        &self,
+        status: Status,
+        breakpoint_set: &HashMap<usize, BreakPoint>,
+    ) -> Result<Status, nix::Error> {
+        if let Status::Stopped(signal::SIGTRAP, rip) = status {
+            if breakpoint_set.contains_key(&(rip - 1)) {
+                let mut regs = getregs(self.pid())?;
+                regs.rip = (rip - 1) as u64;
+                setregs(self.pid(), regs)?;
+                return Ok(Status::Stopped(signal::SIGTRAP, rip - 1));
+            }
+        }
+        Ok(status)
+    }
+
+    /// Executes a single instruction. If there is a breakpoint on it, its original byte is put
+    /// back while stepping and the breakpoint is re-inserted afterwards.
+    pub fn step_instruction(
+        &mut self,
+        breakpoint_set: &HashMap<usize, BreakPoint>,
+    ) -> Result<Status, nix::Error> {
+        let rip_val = getregs(self.pid())?.rip as usize;
+        let bpoint = breakpoint_set.get(&rip_val);
+
+        if let Some(bpoint) = bpoint {
+            self.write_byte(rip_val, bpoint.orig_byte)?;
+        }
+        ptrace::step(self.pid(), None)?;
+        let status = self.wait_running()?;
+        if let (Some(_), Status::Stopped(_sig, _rip)) = (bpoint, &status) {
+            self.write_byte(rip_val, 0xcc)?;
+        }
+        Ok(status)
     }
 
     /// kill the inferior and reap it
     pub fn kill_inferior(&mut self) {
-        self.child.kill().ok();
-        self.child.wait().ok();
+        match self.child.as_mut() {
+            Some(child) => {
+                child.kill().ok();
+                child.wait().ok();
+            }
+            None => {
+                signal::kill(self.pid, signal::SIGKILL).ok();
+                waitpid(self.pid, None).ok();
+            }
+        }
     }
 
-    /// print the backtrace of current debugging process
-    pub fn print_backtrace(&self, debug_data: &mut DwarfData) -> Result<(), nix::Error> {
-        let mut rip_value = getregs(self.pid()).ok().unwrap().rip as usize;
-        let mut rbp_value = getregs(self.pid()).ok().unwrap().rbp as usize;
-        let mut line = debug_data.get_line_from_addr(rip_value).unwrap();
-        let mut func_name = debug_data.get_function_from_addr(rip_value).unwrap();
-
-        loop {
-            println!("{} ({})", func_name, line);
-            if func_name.contains("main") {
+    /// Snapshots the stopped inferior by making it call fork(). The `syscall` instruction is
+    /// injected at `scratch_addr`, which must be code that the program will not run on its own
+    /// (such as the ELF entry point). The returned copy stays stopped until it is resumed; both
+    /// processes are left with their registers and memory as they were before the call.
+    pub fn checkpoint(&mut self, scratch_addr: usize) -> Result<Inferior, nix::Error> {
+        let saved_regs = getregs(self.pid())?;
+        ptrace::setoptions(
+            self.pid(),
+            TRACE_OPTIONS | ptrace::Options::PTRACE_O_TRACEFORK,
+        )?;
+        let orig_bytes = [
+            self.write_byte(scratch_addr, SYSCALL_INSN[0])?,
+            self.write_byte(scratch_addr + 1, SYSCALL_INSN[1])?,
+        ];
+
+        let mut regs = saved_regs;
+        regs.rip = scratch_addr as u64;
+        regs.rax = libc::SYS_fork as u64;
+        setregs(self.pid(), regs)?;
+        ptrace::step(self.pid(), None)?;
+        let mut fork_status = waitpid(self.pid(), None)?;
+        while let WaitStatus::Stopped(_, sig) = fork_status {
+            if sig == signal::SIGTRAP {
                 break;
             }
-
-            rip_value = ptrace::read(self.pid(), (rbp_value + 8) as ptrace::AddressType)? as usize;
-            rbp_value = ptrace::read(self.pid(), rbp_value as ptrace::AddressType)? as usize;
-            line = debug_data.get_line_from_addr(rip_value).unwrap();
-            func_name = debug_data.get_function_from_addr(rip_value).unwrap();
+            // pending signals (e.g. SIGCHLD from an earlier copy exiting) are discarded, they
+            // were caused by deet rather than by the program
+            ptrace::step(self.pid(), None)?;
+            fork_status = waitpid(self.pid(), None)?;
         }
 
-        Ok(())
+        let result = match fork_status {
+            WaitStatus::PtraceEvent(_, _, libc::PTRACE_EVENT_FORK) => {
+                let snapshot_pid = Pid::from_raw(ptrace::getevent(self.pid())? as i32);
+                // let the parent leave the syscall, and collect the initial SIGSTOP that new
+                // tracees are created with
+                ptrace::step(self.pid(), None)?;
+                waitpid(self.pid(), None)?;
+                waitpid(snapshot_pid, None)?;
+
+                // the copy was made while the syscall instruction was in place
+                let mut snapshot = Inferior {
+                    pid: snapshot_pid,
+                    child: None,
+                    own_session: self.own_session,
+                };
+                snapshot.write_byte(scratch_addr, orig_bytes[0])?;
+                snapshot.write_byte(scratch_addr + 1, orig_bytes[1])?;
+                setregs(snapshot_pid, saved_regs)?;
+                // the copy inherits the fork tracing set up above
+                ptrace::setoptions(snapshot_pid, TRACE_OPTIONS)?;
+                Ok(snapshot)
+            }
+            WaitStatus::Stopped(_, signal::SIGTRAP) => {
+                // fork() failed, rax holds the negated errno
+                let errno = -(getregs(self.pid())?.rax as i64);
+                Err(nix::Error::Sys(Errno::from_i32(errno as i32)))
+            }
+            _ => Err(nix::Error::Sys(Errno::ECHILD)),
+        };
+
+        self.write_byte(scratch_addr, orig_bytes[0])?;
+        self.write_byte(scratch_addr + 1, orig_bytes[1])?;
+        setregs(self.pid(), saved_regs)?;
+        // forks made by the program itself should not be traced
+        ptrace::setoptions(self.pid(), TRACE_OPTIONS)?;
+        result
+    }
+
+    /// Returns the frames of the call stack, innermost first, up to main.
+    pub fn stack_frames(&self, debug_data: &DwarfData) -> Result<Vec<FrameState<'_>>, nix::Error> {
+        let mut frames = vec![self.frame_state()?];
+        while frames.len() < MAX_FRAMES {
+            let frame = frames.last().unwrap();
+            // whatever called main (or code without debug info) is of no interest
+            match debug_data.get_function_from_addr(frame.regs.rip as usize) {
+                Some(name) if !dwarf_data::is_main(&name) => (),
+                _ => break,
+            }
+            match frame.caller(debug_data) {
+                // the stack grows down, so callers must be further up
+                Some(caller) if caller.regs.rsp > frame.regs.rsp && caller.regs.rip != 0 => {
+                    frames.push(caller)
+                }
+                _ => break,
+            }
+        }
+        Ok(frames)
     }
 
     pub fn get_stop_line(&self, debug_data: &mut DwarfData) -> Option<dwarf_data::Line> {
@@ -196,6 +738,113 @@ impl Inferior {
         line
     }
 
+    /// Calls the function at `func_addr` inside the stopped inferior following the SysV x86-64
+    /// calling convention. The function returns to `trap_addr`, where a temporary breakpoint is
+    /// placed; `trap_addr` must be code that the program will not run on its own (such as the ELF
+    /// entry point). All registers are restored once the call is over.
+    pub fn call_function(
+        &mut self,
+        func_addr: usize,
+        args: &[CallArg],
+        trap_addr: usize,
+    ) -> Result<CallOutcome, nix::Error> {
+        let saved_regs = getregs(self.pid())?;
+        let saved_fpregs = getfpregs(self.pid())?;
+
+        // Build the call frame below the red zone of whatever the inferior is running
+        let mut sp = saved_regs.rsp as usize - RED_ZONE_SIZE;
+        let mut arg_values = Vec::new();
+        for arg in args {
+            match arg {
+                CallArg::Int(value) => arg_values.push(*value),
+                CallArg::Str(string) => {
+                    let mut bytes = string.as_bytes().to_vec();
+                    bytes.push(0);
+                    sp = align_addr_to_word(sp - bytes.len());
+                    self.write_memory(sp, &bytes)?;
+                    arg_values.push(sp as u64);
+                }
+            }
+        }
+
+        // rsp + 8 must be 16 byte aligned on entry to the callee, i.e. after the return address
+        // has been pushed
+        sp = (sp & !0xf) - size_of::<usize>();
+        self.write_memory(sp, &(trap_addr as u64).to_le_bytes())?;
+
+        let mut regs = saved_regs;
+        regs.rsp = sp as u64;
+        regs.rip = func_addr as u64;
+        // no vector registers are used for arguments (matters for varargs functions)
+        regs.rax = 0;
+        // keep the kernel from restarting an interrupted syscall on our behalf
+        regs.orig_rax = u64::MAX;
+        let mut arg_regs = [
+            &mut regs.rdi,
+            &mut regs.rsi,
+            &mut regs.rdx,
+            &mut regs.rcx,
+            &mut regs.r8,
+            &mut regs.r9,
+        ];
+        for (reg, value) in arg_regs.iter_mut().zip(arg_values) {
+            **reg = value;
+        }
+
+        let orig_byte = self.write_byte(trap_addr, 0xcc)?;
+        setregs(self.pid(), regs)?;
+        ptrace::cont(self.pid(), None)?;
+        let status = self.wait_running()?;
+
+        let outcome = match status {
+            Status::Stopped(signal::SIGTRAP, rip) if rip == trap_addr + 1 => {
+                CallOutcome::Returned {
+                    regs: Box::new(getregs(self.pid())?),
+                    fpregs: Box::new(getfpregs(self.pid())?),
+                }
+            }
+            Status::Stopped(_, _) => CallOutcome::Interrupted(status),
+            // the process is gone, nothing left to restore
+            _ => return Ok(CallOutcome::Interrupted(status)),
+        };
+
+        self.write_byte(trap_addr, orig_byte)?;
+        setregs(self.pid(), saved_regs)?;
+        setfpregs(self.pid(), saved_fpregs)?;
+        Ok(outcome)
+    }
+
+    /// Captures the current registers, for evaluating DWARF expressions.
+    pub fn frame_state(&self) -> Result<FrameState<'_>, nix::Error> {
+        Ok(FrameState {
+            inferior: self,
+            regs: getregs(self.pid())?,
+            fpregs: getfpregs(self.pid())?,
+            outer: false,
+        })
+    }
+
+    /// Reads `len` bytes of the inferior's memory starting at `addr`.
+    pub fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, nix::Error> {
+        let mut bytes = Vec::with_capacity(len + 2 * size_of::<usize>());
+        let mut word_addr = align_addr_to_word(addr);
+        while word_addr < addr + len {
+            let word = ptrace::read(self.pid(), word_addr as ptrace::AddressType)? as u64;
+            bytes.extend_from_slice(&word.to_le_bytes());
+            word_addr += size_of::<usize>();
+        }
+        let start = addr - align_addr_to_word(addr);
+        Ok(bytes[start..start + len].to_vec())
+    }
+
+    /// Writes `data` into the inferior's memory starting at `addr`.
+    pub fn write_memory(&mut self, addr: usize, data: &[u8]) -> Result<(), nix::Error> {
+        for (offset, byte) in data.iter().enumerate() {
+            self.write_byte(addr + offset, *byte)?;
+        }
+        Ok(())
+    }
+
     pub fn write_byte(&mut self, addr: usize, val: u8) -> Result<u8, nix::Error> {
         let aligned_addr = align_addr_to_word(addr);
         let byte_offset = addr - aligned_addr;
@@ -3,23 +3,67 @@ mod debugger_command;
 mod dwarf_data;
 mod gimli_wrapper;
 mod inferior;
+mod output;
+mod pretty;
+mod printf;
+mod procfs;
 mod disassembler;
+mod record;
+mod regex;
+mod source;
+mod symbols;
+mod syscall;
+mod tui;
 
 use crate::debugger::Debugger;
-use nix::sys::signal::{signal, SigHandler, Signal};
 use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+
+fn usage(program: &str) -> ! {
+    println!(
+        "Usage: {} [--interpreter=console|json] <target program>\n       {} --trace-syscalls[=<log file>] <target program> [args...]",
+        program, program
+    );
+    std::process::exit(1);
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("--interpreter=json") => {
+            output::set_json(true);
+            args.remove(1);
+        }
+        Some("--interpreter=console") => {
+            args.remove(1);
+        }
+        _ => (),
+    }
+    if let Some(trace) = args.get(1).filter(|arg| arg.starts_with("--trace-syscalls")) {
+        if args.len() < 3 {
+            usage(&args[0]);
+        }
+        let mut log: Box<dyn Write> = match trace.strip_prefix("--trace-syscalls=") {
+            Some(path) => match File::create(path) {
+                Ok(file) => Box::new(file),
+                Err(err) => {
+                    eprintln!("Could not open {}: {}", path, err);
+                    std::process::exit(1);
+                }
+            },
+            None => Box::new(io::stderr()),
+        };
+        let code = syscall::trace(&args[2], &args[3..].to_vec(), &mut *log);
+        std::process::exit(code);
+    }
     if args.len() != 2 {
-        println!("Usage: {} <target program>", args[0]);
-        std::process::exit(1);
+        usage(&args[0]);
     }
     let target = &args[1];
 
-    // Disable handling of ctrl+c in this process (so that ctrl+c only gets delivered to child
-    // processes)
-    unsafe { signal(Signal::SIGINT, SigHandler::SigIgn) }.expect("Error disabling SIGINT handling");
+    // Make ctrl+c interrupt the program being debugged instead of quitting
+    inferior::catch_interrupts().expect("Error setting up SIGINT handling");
 
     Debugger::new(target).run();
 }
@@ -0,0 +1,154 @@
+//! All of deet's own output goes through this module, so that it can be switched between plain
+//! text for people and one JSON object per line (`--interpreter=json`) for tools driving deet.
+
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Switches all output to JSON lines.
+pub fn set_json(enabled: bool) {
+    JSON.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// A JSON value, just enough to describe deet's records.
+pub enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Uint(u64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Self {
+        Json::Str(s.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Self {
+        Json::Str(s)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Self {
+        Json::Uint(n as u64)
+    }
+}
+
+impl From<i32> for Json {
+    fn from(n: i32) -> Self {
+        Json::Int(n as i64)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Self {
+        Json::Bool(b)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        match value {
+            Some(value) => value.into(),
+            None => Json::Null,
+        }
+    }
+}
+
+fn write_escaped(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Int(n) => write!(f, "{}", n),
+            Json::Uint(n) => write!(f, "{}", n),
+            Json::Str(s) => write_escaped(f, s),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_escaped(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// Writes a record of the given type, with extra fields, as a single line of JSON.
+pub fn record(kind: &str, mut fields: Vec<(&'static str, Json)>) {
+    fields.insert(0, ("type", kind.into()));
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    writeln!(stdout, "{}", Json::Object(fields)).ok();
+    stdout.flush().ok();
+}
+
+/// Prints a line of regular output.
+pub fn print(text: &str) {
+    if is_json() {
+        record("output", vec![("text", text.into())]);
+    } else {
+        println!("{}", text);
+    }
+}
+
+/// Prints text as it is, without ending the line unless the text does (for `printf`).
+pub fn write(text: &str) {
+    if is_json() {
+        record("output", vec![("text", text.into())]);
+    } else {
+        print!("{}", text);
+        io::stdout().flush().ok();
+    }
+}
+
+/// Reports an error. In JSON mode this becomes an `error` record on stdout, so that tools only
+/// have one stream to read.
+pub fn error(text: &str) {
+    if is_json() {
+        record("error", vec![("message", text.into())]);
+    } else {
+        eprintln!("{}", text);
+    }
+}
@@ -0,0 +1,765 @@
+//! Formatting of values for `print`. Values are laid out from their DWARF types (structures,
+//! arrays, pointers, enums), and types with a registered pretty-printer, such as the Rust
+//! standard library's collections, are shown the way their `Debug` implementation would.
+//! Types themselves are described here too, for `ptype` and the `info` commands.
+
+use crate::dwarf_data::{
+    DwarfData, EvalError, Member, ProgramState, StructType, Type, TypeId, TypeKind,
+};
+use std::cell::Cell;
+use std::convert::TryInto;
+
+/// Elements of arrays and collections shown before the rest is cut off with `...`
+const MAX_ELEMENTS: usize = 200;
+/// How deep nested values are followed before they are abbreviated to `{...}`
+const MAX_DEPTH: usize = 8;
+
+/// A value read from the inferior, along with its type.
+pub struct Value<'a> {
+    pub ty: &'a Type,
+    pub bytes: Vec<u8>,
+}
+
+impl<'a> Value<'a> {
+    pub fn new(ty: &'a Type, bytes: Vec<u8>) -> Self {
+        Value { ty, bytes }
+    }
+
+    /// The value as an unsigned integer, from its first (up to) 8 bytes.
+    pub fn as_u64(&self) -> u64 {
+        let mut raw = [0u8; 8];
+        let len = self.bytes.len().min(raw.len());
+        raw[..len].copy_from_slice(&self.bytes[..len]);
+        u64::from_le_bytes(raw)
+    }
+}
+
+/// Shows values of the types it is registered for (see `Printers::register`).
+pub trait PrettyPrinter {
+    fn format(&self, value: &Value, formatter: &ValueFormatter) -> Result<String, EvalError>;
+}
+
+/// The pretty-printers known to the debugger, keyed on the name of the types they show.
+pub struct Printers {
+    printers: Vec<(String, Box<dyn PrettyPrinter>)>,
+}
+
+impl Printers {
+    /// Returns the printers for the Rust standard library types.
+    pub fn new() -> Self {
+        let mut printers = Printers {
+            printers: Vec::new(),
+        };
+        printers.register("alloc::vec::Vec", Box::new(VecPrinter));
+        printers.register(
+            "alloc::collections::vec_deque::VecDeque",
+            Box::new(VecPrinter),
+        );
+        printers.register("alloc::string::String", Box::new(StrPrinter));
+        printers.register("&str", Box::new(StrPrinter));
+        printers.register("&mut str", Box::new(StrPrinter));
+        printers.register("&[*", Box::new(SlicePrinter));
+        printers.register("&mut [*", Box::new(SlicePrinter));
+        printers.register("alloc::boxed::Box", Box::new(BoxPrinter));
+        printers.register(
+            "std::collections::hash::map::HashMap",
+            Box::new(HashPrinter { set: false }),
+        );
+        printers.register(
+            "hashbrown::map::HashMap",
+            Box::new(HashPrinter { set: false }),
+        );
+        printers.register(
+            "std::collections::hash::set::HashSet",
+            Box::new(HashPrinter { set: true }),
+        );
+        printers.register(
+            "hashbrown::set::HashSet",
+            Box::new(HashPrinter { set: true }),
+        );
+        printers
+    }
+
+    /// Registers a printer for the type named `type_name`, including all instances of it if it
+    /// is generic (`alloc::vec::Vec` covers `alloc::vec::Vec<i32, alloc::alloc::Global>`). A
+    /// name ending in `*` matches every type it is a prefix of. Printers registered later take
+    /// precedence.
+    pub fn register(&mut self, type_name: &str, printer: Box<dyn PrettyPrinter>) {
+        self.printers.push((type_name.to_string(), printer));
+    }
+
+    fn find(&self, type_name: &str) -> Option<&dyn PrettyPrinter> {
+        self.printers
+            .iter()
+            .rev()
+            .find(|(key, _)| match key.strip_suffix('*') {
+                Some(prefix) => type_name.starts_with(prefix),
+                None => {
+                    type_name == key
+                        || (type_name.starts_with(key.as_str())
+                            && type_name[key.len()..].starts_with('<'))
+                }
+            })
+            .map(|(_, printer)| printer.as_ref())
+    }
+}
+
+/// Formats values of a stopped inferior. Printers use it to read memory and to format the values
+/// their types contain.
+pub struct ValueFormatter<'a> {
+    debug_data: &'a DwarfData,
+    state: &'a dyn ProgramState,
+    printers: &'a Printers,
+    depth: Cell<usize>,
+    /// Whether a pointer printed on its own comes with its type, as in `(int *) 0x4004`
+    pointer_types: bool,
+}
+
+impl<'a> ValueFormatter<'a> {
+    pub fn new(
+        debug_data: &'a DwarfData,
+        state: &'a dyn ProgramState,
+        printers: &'a Printers,
+    ) -> Self {
+        ValueFormatter {
+            debug_data,
+            state,
+            printers,
+            depth: Cell::new(0),
+            pointer_types: true,
+        }
+    }
+
+    /// Leaves out the type of pointers, the way backtraces show arguments.
+    pub fn brief(mut self) -> Self {
+        self.pointer_types = false;
+        self
+    }
+
+    /// Formats a value, with the printer registered for its type if there is one.
+    pub fn format(&self, value: &Value) -> Result<String, EvalError> {
+        if self.depth.get() >= MAX_DEPTH {
+            return Ok("{...}".to_string());
+        }
+        self.depth.set(self.depth.get() + 1);
+        let formatted = self.format_value(value);
+        self.depth.set(self.depth.get() - 1);
+        formatted
+    }
+
+    /// Formats the value of a member or element, showing errors in its place so that the rest
+    /// of the enclosing value is still printed.
+    fn format_inner(&self, value: &Value) -> String {
+        match self.format(value) {
+            Ok(formatted) => formatted,
+            Err(err) => format!("<{}>", err),
+        }
+    }
+
+    fn format_value(&self, value: &Value) -> Result<String, EvalError> {
+        // a printer may be registered for a typedef as well as the type behind it
+        let mut ty = value.ty;
+        loop {
+            if let Some(printer) = self.printers.find(&ty.name) {
+                return printer.format(&Value::new(ty, value.bytes.clone()), self);
+            }
+            match ty.kind {
+                TypeKind::Alias(Some(target)) => match self.debug_data.get_type(target) {
+                    Some(target) => ty = target,
+                    None => break,
+                },
+                _ => break,
+            }
+        }
+        let value = Value::new(ty, value.bytes.clone());
+        match &ty.kind {
+            TypeKind::Base | TypeKind::Alias(_) => Ok(ty.format_value(value.as_u64())),
+            TypeKind::Pointer(target) => self.format_pointer(&value, *target),
+            TypeKind::Struct(struct_type) => match &struct_type.variants {
+                Some(_) => self.format_enum(&value, struct_type),
+                None => Ok(self.format_struct(&value, struct_type)),
+            },
+            TypeKind::Array { element, count } => {
+                let element = self.get_type(*element)?;
+                let count = count.unwrap_or(0);
+                if !ty.is_rust() && is_c_char(element) {
+                    let bytes = &value.bytes[..count.min(value.bytes.len())];
+                    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+                    return Ok(format!("{:?}", String::from_utf8_lossy(&bytes[..end])));
+                }
+                let elements = (0..count.min(MAX_ELEMENTS))
+                    .map(|i| self.slice(&value, i * element.size, element))
+                    .collect();
+                Ok(self.format_list(elements, count, ty.is_rust()))
+            }
+            TypeKind::Enum(enumerators) => {
+                let number = value.as_u64() as i64;
+                let number = match ty.size {
+                    1 => number as i8 as i64,
+                    2 => number as i16 as i64,
+                    4 => number as i32 as i64,
+                    _ => number,
+                };
+                Ok(
+                    match enumerators.iter().find(|(_, value)| *value == number) {
+                        Some((name, _)) if ty.is_rust() => format!("{}::{}", ty.name, name),
+                        Some((name, _)) => name.clone(),
+                        None => number.to_string(),
+                    },
+                )
+            }
+        }
+    }
+
+    fn format_pointer(&self, value: &Value, target: Option<TypeId>) -> Result<String, EvalError> {
+        let addr = value.as_u64();
+        if value.ty.is_rust() {
+            return Ok(format!("{:#x}", addr));
+        }
+        let target = target.and_then(|id| self.debug_data.get_type(id));
+        if let Some(target) = target.map(|target| self.resolve(target)) {
+            if is_c_char(target) && addr != 0 {
+                let string = match self.read_c_string(addr) {
+                    Ok(string) => format!("{:?}", string),
+                    Err(err) => format!("<{}>", err),
+                };
+                return Ok(format!("{:#x} {}", addr, string));
+            }
+        }
+        // pointers inside other values go without their type
+        if self.pointer_types && self.depth.get() == 1 {
+            Ok(format!("({}) {:#x}", value.ty.name, addr))
+        } else {
+            Ok(format!("{:#x}", addr))
+        }
+    }
+
+    fn format_struct(&self, value: &Value, struct_type: &StructType) -> String {
+        let ty = value.ty;
+        let fields: Vec<(&str, String)> = struct_type
+            .members
+            .iter()
+            .map(|member| {
+                let formatted = match self.member_value(value, member) {
+                    Ok(member_value) => self.format_inner(&member_value),
+                    Err(err) => format!("<{}>", err),
+                };
+                (member.name.as_str(), formatted)
+            })
+            .collect();
+        if !ty.is_rust() {
+            let fields: Vec<String> = fields
+                .iter()
+                .zip(struct_type.members.iter())
+                .map(|((name, formatted), member)| {
+                    if name.is_empty() {
+                        // base class
+                        let base = self.get_type(member.type_id).map(|base| base.name.as_str());
+                        format!("<{}> = {}", base.unwrap_or(""), formatted)
+                    } else {
+                        format!("{} = {}", name, formatted)
+                    }
+                })
+                .collect();
+            return format!("{{{}}}", fields.join(", "));
+        }
+        format_rust_fields(&ty.name, fields)
+    }
+
+    /// Formats a Rust enum with fields, as the variant its discriminant selects.
+    fn format_enum(&self, value: &Value, struct_type: &StructType) -> Result<String, EvalError> {
+        let variants = struct_type.variants.as_ref().unwrap();
+        let discr = self.member_value(value, &variants.discr)?.as_u64();
+        let variant = variants
+            .variants
+            .iter()
+            .find(|(discr_value, _)| *discr_value == Some(discr))
+            .or_else(|| {
+                variants
+                    .variants
+                    .iter()
+                    .find(|(discr_value, _)| discr_value.is_none())
+            });
+        let member = match variant {
+            Some((_, member)) => member,
+            None => return Ok(format!("<invalid discriminant {}>", discr)),
+        };
+        let variant_value = self.member_value(value, member)?;
+        let fields = match &self.resolve(variant_value.ty).kind {
+            TypeKind::Struct(variant_type) => variant_type
+                .members
+                .iter()
+                .map(|field| {
+                    let formatted = match self.member_value(&variant_value, field) {
+                        Ok(field_value) => self.format_inner(&field_value),
+                        Err(err) => format!("<{}>", err),
+                    };
+                    (field.name.as_str(), formatted)
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        Ok(format_rust_fields(&member.name, fields))
+    }
+
+    fn format_list(
+        &self,
+        elements: Vec<Result<Value, EvalError>>,
+        count: usize,
+        rust: bool,
+    ) -> String {
+        let mut formatted: Vec<String> = elements
+            .iter()
+            .map(|element| match element {
+                Ok(element) => self.format_inner(element),
+                Err(err) => format!("<{}>", err),
+            })
+            .collect();
+        if count > formatted.len() {
+            formatted.push("...".to_string());
+        }
+        if rust {
+            format!("[{}]", formatted.join(", "))
+        } else {
+            format!("{{{}}}", formatted.join(", "))
+        }
+    }
+
+    /// Formats `count` elements of type `element` stored at `addr`, as a Rust list.
+    pub fn format_elements(
+        &self,
+        addr: u64,
+        element: &'a Type,
+        count: usize,
+    ) -> Result<String, EvalError> {
+        let shown = count.min(MAX_ELEMENTS);
+        let bytes = self.read_memory(addr, shown * element.size)?;
+        let buffer = Value::new(element, bytes);
+        let elements = (0..shown)
+            .map(|i| self.slice(&buffer, i * element.size, element))
+            .collect();
+        Ok(self.format_list(elements, count, true))
+    }
+
+    pub fn get_type(&self, id: TypeId) -> Result<&'a Type, EvalError> {
+        self.debug_data
+            .get_type(id)
+            .ok_or_else(|| EvalError::Unsupported(format!("Unknown type {:#x}", id)))
+    }
+
+    /// Looks through typedefs and qualifiers for the type they stand for.
+    pub fn resolve(&self, mut ty: &'a Type) -> &'a Type {
+        while let TypeKind::Alias(Some(target)) = ty.kind {
+            match self.debug_data.get_type(target) {
+                Some(target) => ty = target,
+                None => break,
+            }
+        }
+        ty
+    }
+
+    /// Returns the parameter of a generic type, such as `T` of `Vec<T>`.
+    pub fn type_param(&self, ty: &Type, name: &str) -> Option<&'a Type> {
+        match &self.resolve(ty).kind {
+            TypeKind::Struct(struct_type) => struct_type
+                .type_params
+                .iter()
+                .find(|(param, _)| param == name)
+                .and_then(|(_, id)| self.debug_data.get_type(*id)),
+            _ => None,
+        }
+    }
+
+    /// Returns the member `name` of a structure.
+    pub fn member(&self, value: &Value<'a>, name: &str) -> Result<Value<'a>, EvalError> {
+        let struct_type = match &self.resolve(value.ty).kind {
+            TypeKind::Struct(struct_type) => struct_type,
+            _ => return Err(self.missing_member(value.ty, name)),
+        };
+        match struct_type
+            .members
+            .iter()
+            .find(|member| member.name == name)
+        {
+            Some(member) => self.member_value(value, member),
+            None => Err(self.missing_member(value.ty, name)),
+        }
+    }
+
+    /// Returns the member `name` of a structure or of the structures nested in it, nearest
+    /// first. Printers use it to reach fields that are wrapped in private helper types, which
+    /// change between Rust versions.
+    pub fn find_member(&self, value: &Value<'a>, name: &str) -> Result<Value<'a>, EvalError> {
+        let mut queue = vec![Value::new(value.ty, value.bytes.clone())];
+        while !queue.is_empty() {
+            let mut next = Vec::new();
+            for current in queue {
+                if let TypeKind::Struct(struct_type) = &self.resolve(current.ty).kind {
+                    for member in &struct_type.members {
+                        let member_value = self.member_value(&current, member)?;
+                        if member.name == name {
+                            return Ok(member_value);
+                        }
+                        next.push(member_value);
+                    }
+                }
+            }
+            queue = next;
+        }
+        Err(self.missing_member(value.ty, name))
+    }
+
+    /// Returns the address held by a pointer, or by structures that only wrap one (such as
+    /// `NonNull<T>`).
+    pub fn pointer(&self, value: &Value<'a>) -> Result<u64, EvalError> {
+        let inner = self.unwrap_fields(value)?;
+        match self.resolve(inner.ty).kind {
+            TypeKind::Pointer(_) => Ok(inner.as_u64()),
+            _ => Err(EvalError::Unsupported(format!(
+                "{} is not a pointer",
+                value.ty.name
+            ))),
+        }
+    }
+
+    /// Returns the number held by an integer, or by structures that only wrap one.
+    pub fn integer(&self, value: &Value<'a>) -> Result<u64, EvalError> {
+        let inner = self.unwrap_fields(value)?;
+        match self.resolve(inner.ty).kind {
+            TypeKind::Base | TypeKind::Enum(_) => Ok(inner.as_u64()),
+            _ => Err(EvalError::Unsupported(format!(
+                "{} is not an integer",
+                value.ty.name
+            ))),
+        }
+    }
+
+    /// Digs through structures with a single field, not counting zero-sized ones such as
+    /// `PhantomData`.
+    fn unwrap_fields(&self, value: &Value<'a>) -> Result<Value<'a>, EvalError> {
+        let mut current = Value::new(value.ty, value.bytes.clone());
+        while let TypeKind::Struct(struct_type) = &self.resolve(current.ty).kind {
+            let mut sized = Vec::new();
+            for member in &struct_type.members {
+                if self.get_type(member.type_id)?.size != 0 {
+                    sized.push(member);
+                }
+            }
+            if sized.len() != 1 || struct_type.variants.is_some() {
+                break;
+            }
+            current = self.member_value(&current, sized[0])?;
+        }
+        Ok(current)
+    }
+
+    /// Reads a value of type `ty` from the inferior's memory.
+    pub fn read(&self, ty: &'a Type, addr: u64) -> Result<Value<'a>, EvalError> {
+        Ok(Value::new(ty, self.read_memory(addr, ty.size)?))
+    }
+
+    pub fn read_memory(&self, addr: u64, len: usize) -> Result<Vec<u8>, EvalError> {
+        self.state
+            .read_memory(addr, len)
+            .ok_or(EvalError::Memory(addr))
+    }
+
+    /// Reads a NUL-terminated string, of at most `MAX_ELEMENTS` bytes.
+    pub fn read_c_string(&self, addr: u64) -> Result<String, EvalError> {
+        let mut bytes = Vec::new();
+        while bytes.len() < MAX_ELEMENTS {
+            let byte = self.read_memory(addr + bytes.len() as u64, 1)?[0];
+            if byte == 0 {
+                break;
+            }
+            bytes.push(byte);
+        }
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    fn member_value(&self, value: &Value<'a>, member: &Member) -> Result<Value<'a>, EvalError> {
+        let ty = self.get_type(member.type_id)?;
+        self.slice(value, member.offset, ty)
+    }
+
+    fn slice(&self, value: &Value, offset: usize, ty: &'a Type) -> Result<Value<'a>, EvalError> {
+        match value.bytes.get(offset..offset + ty.size) {
+            Some(bytes) => Ok(Value::new(ty, bytes.to_vec())),
+            None => Err(EvalError::Unsupported(format!(
+                "{} does not fit in {}",
+                ty.name, value.ty.name
+            ))),
+        }
+    }
+
+    fn missing_member(&self, ty: &Type, name: &str) -> EvalError {
+        EvalError::Unsupported(format!("There is no member named {} in {}.", name, ty.name))
+    }
+}
+
+fn is_c_char(ty: &Type) -> bool {
+    ty.size == 1 && ty.name.contains("char")
+}
+
+/// Formats Rust structs and enum variants: `Point {x: 1, y: 2}`, tuples and tuple structs
+/// (whose fields are named `__0`, `__1`, ...) as `Some(5)`, and unit structs by their name.
+fn format_rust_fields(name: &str, fields: Vec<(&str, String)>) -> String {
+    if fields.is_empty() {
+        return name.to_string();
+    }
+    if fields.iter().all(|(field, _)| field.starts_with("__")) {
+        let fields: Vec<String> = fields.into_iter().map(|(_, formatted)| formatted).collect();
+        if name.starts_with('(') {
+            return format!("({})", fields.join(", "));
+        }
+        return format!("{}({})", name, fields.join(", "));
+    }
+    let fields: Vec<String> = fields
+        .into_iter()
+        .map(|(field, formatted)| format!("{}: {}", field, formatted))
+        .collect();
+    format!("{} {{{}}}", name, fields.join(", "))
+}
+
+/// `Vec<T>`: `[1, 2, 3]`
+struct VecPrinter;
+
+impl PrettyPrinter for VecPrinter {
+    fn format(&self, value: &Value, formatter: &ValueFormatter) -> Result<String, EvalError> {
+        let element = formatter.type_param(value.ty, "T").ok_or_else(|| {
+            EvalError::Unsupported(format!("Unknown element type of {}", value.ty.name))
+        })?;
+        let addr = formatter.pointer(&formatter.find_member(value, "ptr")?)?;
+        let len = formatter.integer(&formatter.find_member(value, "len")?)?;
+        formatter.format_elements(addr, element, len.try_into().unwrap())
+    }
+}
+
+/// `String` and `&str`: `"hello"`
+struct StrPrinter;
+
+impl PrettyPrinter for StrPrinter {
+    fn format(&self, value: &Value, formatter: &ValueFormatter) -> Result<String, EvalError> {
+        // a String is a Vec<u8>, and a &str a pointer and length
+        let (addr, len) = match formatter.find_member(value, "data_ptr") {
+            Ok(data_ptr) => (
+                formatter.pointer(&data_ptr)?,
+                formatter.integer(&formatter.find_member(value, "length")?)?,
+            ),
+            Err(_) => (
+                formatter.pointer(&formatter.find_member(value, "ptr")?)?,
+                formatter.integer(&formatter.find_member(value, "len")?)?,
+            ),
+        };
+        let shown = (len as usize).min(MAX_ELEMENTS * 10);
+        let bytes = formatter.read_memory(addr, shown)?;
+        let mut string = format!("{:?}", String::from_utf8_lossy(&bytes));
+        if shown < len as usize {
+            string.push_str("...");
+        }
+        Ok(string)
+    }
+}
+
+/// Slices (`&[T]`): `[1, 2, 3]`
+struct SlicePrinter;
+
+impl PrettyPrinter for SlicePrinter {
+    fn format(&self, value: &Value, formatter: &ValueFormatter) -> Result<String, EvalError> {
+        let data_ptr = formatter.member(value, "data_ptr")?;
+        let element = match formatter.resolve(data_ptr.ty).kind {
+            TypeKind::Pointer(Some(element)) => formatter.get_type(element)?,
+            _ => {
+                return Err(EvalError::Unsupported(format!(
+                    "Unknown element type of {}",
+                    value.ty.name
+                )))
+            }
+        };
+        let len = formatter.integer(&formatter.member(value, "length")?)?;
+        formatter.format_elements(data_ptr.as_u64(), element, len.try_into().unwrap())
+    }
+}
+
+/// `Box<T>`: the value it points to
+struct BoxPrinter;
+
+impl PrettyPrinter for BoxPrinter {
+    fn format(&self, value: &Value, formatter: &ValueFormatter) -> Result<String, EvalError> {
+        let target = match value.ty.kind {
+            TypeKind::Pointer(Some(target)) => formatter.get_type(target)?,
+            // newer versions of Rust describe Box as a structure wrapping a pointer
+            _ => formatter.type_param(value.ty, "T").ok_or_else(|| {
+                EvalError::Unsupported(format!("Unknown target type of {}", value.ty.name))
+            })?,
+        };
+        let addr = formatter.pointer(value)?;
+        formatter.format(&formatter.read(target, addr)?)
+    }
+}
+
+/// `HashMap<K, V>` and `HashSet<T>`: `{1: "one", 2: "two"}` and `{1, 2}`
+struct HashPrinter {
+    set: bool,
+}
+
+impl PrettyPrinter for HashPrinter {
+    fn format(&self, value: &Value, formatter: &ValueFormatter) -> Result<String, EvalError> {
+        // hashbrown keeps the (K, V) tuples right below an array of control bytes, one per
+        // bucket, which have their top bit clear for the buckets in use
+        let table = formatter.find_member(value, "table")?;
+        let entry = formatter.type_param(table.ty, "T").ok_or_else(|| {
+            EvalError::Unsupported(format!("Unknown entry type of {}", value.ty.name))
+        })?;
+        let ctrl = formatter.pointer(&formatter.find_member(&table, "ctrl")?)?;
+        let bucket_mask = formatter.integer(&formatter.find_member(&table, "bucket_mask")?)?;
+        let items = formatter.integer(&formatter.find_member(&table, "items")?)? as usize;
+        if items == 0 {
+            return Ok("{}".to_string());
+        }
+        let buckets = bucket_mask as usize + 1;
+        let control = formatter.read_memory(ctrl, buckets)?;
+        let mut entries = Vec::new();
+        for (i, byte) in control.iter().enumerate() {
+            if byte & 0x80 != 0 {
+                continue;
+            }
+            if entries.len() == MAX_ELEMENTS {
+                entries.push("...".to_string());
+                break;
+            }
+            let addr = ctrl - ((i + 1) * entry.size) as u64;
+            let entry_value = formatter.read(entry, addr)?;
+            let key = formatter.format_inner(&formatter.member(&entry_value, "__0")?);
+            if self.set {
+                entries.push(key);
+            } else {
+                let value = formatter.format_inner(&formatter.member(&entry_value, "__1")?);
+                entries.push(format!("{}: {}", key, value));
+            }
+        }
+        Ok(format!("{{{}}}", entries.join(", ")))
+    }
+}
+
+/// Names a type, spelling out the arrays that DWARF leaves unnamed: `int [3]`, or `[i32; 3]`
+/// in Rust.
+pub fn type_name(debug_data: &DwarfData, ty: &Type) -> String {
+    match &ty.kind {
+        TypeKind::Array { element, count } => {
+            let element = match debug_data.get_type(*element) {
+                Some(element) => type_name(debug_data, element),
+                None => "?".to_string(),
+            };
+            let count = count.map(|count| count.to_string()).unwrap_or_default();
+            if ty.is_rust() {
+                format!("[{}; {}]", element, count)
+            } else {
+                format!("{} [{}]", element, count)
+            }
+        }
+        TypeKind::Struct(struct_type) if ty.name.is_empty() => {
+            format!("{} {{...}}", struct_type.keyword)
+        }
+        TypeKind::Struct(struct_type) if ty.is_c() => {
+            format!("{} {}", struct_type.keyword, ty.name)
+        }
+        TypeKind::Enum(_) if ty.is_c() => format!("enum {}", ty.name),
+        _ if ty.name.is_empty() => "void".to_string(),
+        _ => ty.name.clone(),
+    }
+}
+
+/// Declares `name` with type `ty` in the syntax of the type's language: `char *name`,
+/// `int name[3]` or `name: i32`.
+pub fn declaration(debug_data: &DwarfData, ty: &Type, name: &str) -> String {
+    if ty.is_rust() {
+        return format!("{}: {}", name, type_name(debug_data, ty));
+    }
+    match &ty.kind {
+        TypeKind::Array { element, count } => {
+            let element = match debug_data.get_type(*element) {
+                Some(element) => type_name(debug_data, element),
+                None => "?".to_string(),
+            };
+            let count = count.map(|count| count.to_string()).unwrap_or_default();
+            format!("{} {}[{}]", element, name, count)
+        }
+        _ => {
+            let ty = type_name(debug_data, ty);
+            if ty.ends_with('*') {
+                format!("{}{}", ty, name)
+            } else {
+                format!("{} {}", ty, name)
+            }
+        }
+    }
+}
+
+/// Describes a type for `ptype`. Typedefs are shown as the type they stand for, enums with
+/// their values, and structures with the offset and size of each member.
+pub fn describe_type(debug_data: &DwarfData, ty: &Type) -> String {
+    let mut ty = ty;
+    while let TypeKind::Alias(Some(target)) = ty.kind {
+        match debug_data.get_type(target) {
+            // qualifiers are part of the type rather than another name for it
+            Some(target) if !ty.is_qualified() => ty = target,
+            _ => break,
+        }
+    }
+    match &ty.kind {
+        TypeKind::Struct(struct_type) => describe_struct(debug_data, ty, struct_type),
+        TypeKind::Enum(enumerators) => {
+            let enumerators: Vec<String> = enumerators
+                .iter()
+                .enumerate()
+                .map(|(index, (name, value))| {
+                    // only values that differ from the default numbering are spelled out
+                    if *value == index as i64 {
+                        name.clone()
+                    } else {
+                        format!("{} = {}", name, value)
+                    }
+                })
+                .collect();
+            format!("type = enum {} {{{}}}", ty.name, enumerators.join(", "))
+        }
+        _ => format!("type = {}", type_name(debug_data, ty)),
+    }
+}
+
+fn describe_struct(debug_data: &DwarfData, ty: &Type, struct_type: &StructType) -> String {
+    let mut lines = vec![format!(
+        "/* offset      |    size */  type = {} {} {{",
+        struct_type.keyword, ty.name
+    )];
+    let terminator = if ty.is_rust() { ',' } else { ';' };
+    let mut members: Vec<&Member> = struct_type.members.iter().collect();
+    // Rust enums list the fields of every variant, all of which overlap
+    if let Some(variants) = &struct_type.variants {
+        members.push(&variants.discr);
+        members.extend(variants.variants.iter().map(|(_, member)| member));
+    }
+    for member in members {
+        let (size, declared) = match debug_data.get_type(member.type_id) {
+            Some(member_type) => (
+                member_type.size,
+                declaration(debug_data, member_type, &member.name),
+            ),
+            None => (0, member.name.clone()),
+        };
+        // the discriminant of a Rust enum has no name
+        let declared = match declared.strip_prefix(": ") {
+            Some(ty) => format!("<discriminant>: {}", ty),
+            None => declared,
+        };
+        lines.push(format!(
+            "/* {:>6}      | {:>7} */    {}{}",
+            member.offset, size, declared, terminator
+        ));
+    }
+    lines.push(String::new());
+    lines.push(format!("{:31}/* total size (bytes): {:>4} */", "", ty.size));
+    lines.push(format!("{:29}}}", ""));
+    lines.join("\n")
+}
@@ -0,0 +1,390 @@
+//! Parsing and formatting for the `printf` and `dprintf` commands, which take a C format string
+//! followed by the expressions to fill it with: `printf "x=%d\n", x`.
+
+/// One `%` conversion of a format string, such as `%-08.3lx`.
+pub struct Spec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    space: bool,
+    alternate: bool,
+    width: usize,
+    precision: Option<usize>,
+    conversion: char,
+}
+
+pub enum Piece {
+    Text(String),
+    Conversion(Spec),
+}
+
+/// A value to fill a conversion with, already read from the inferior.
+pub enum Arg {
+    Signed(i64),
+    Unsigned(u64),
+    Float(f64),
+    Str(String),
+}
+
+/// Splits the arguments of `printf` into the pieces of the format string and the expressions
+/// that follow it.
+pub fn parse(args: &str) -> Result<(Vec<Piece>, Vec<String>), String> {
+    let args = args.trim();
+    if !args.starts_with('"') {
+        return Err("Bad format string".to_string());
+    }
+    let mut format = String::new();
+    let mut chars = args[1..].char_indices();
+    let rest = loop {
+        match chars.next() {
+            Some((i, '"')) => break &args[i + 2..],
+            Some((_, '\\')) => match chars.next() {
+                Some((_, 'n')) => format.push('\n'),
+                Some((_, 't')) => format.push('\t'),
+                Some((_, 'a')) => format.push('\x07'),
+                Some((_, c)) => format.push(c),
+                None => return Err("Bad format string, non-terminated '\"'".to_string()),
+            },
+            Some((_, c)) => format.push(c),
+            None => return Err("Bad format string, non-terminated '\"'".to_string()),
+        }
+    };
+    let rest = rest.trim();
+    let exprs: Vec<String> = if rest.is_empty() {
+        Vec::new()
+    } else if let Some(rest) = rest.strip_prefix(',') {
+        rest.split(',')
+            .map(|expr| expr.trim().to_string())
+            .collect()
+    } else {
+        return Err("Invalid argument syntax".to_string());
+    };
+
+    let pieces = parse_format(&format)?;
+    let conversions = pieces
+        .iter()
+        .filter(|piece| matches!(piece, Piece::Conversion(_)))
+        .count();
+    if conversions != exprs.len() {
+        return Err("Wrong number of arguments for specified format-string".to_string());
+    }
+    Ok((pieces, exprs))
+}
+
+fn parse_format(format: &str) -> Result<Vec<Piece>, String> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            text.push(c);
+            continue;
+        }
+        if chars.peek() == Some(&'%') {
+            chars.next();
+            text.push('%');
+            continue;
+        }
+        let mut spec = Spec {
+            left: false,
+            zero: false,
+            plus: false,
+            space: false,
+            alternate: false,
+            width: 0,
+            precision: None,
+            conversion: ' ',
+        };
+        while let Some(&flag) = chars.peek() {
+            match flag {
+                '-' => spec.left = true,
+                '0' => spec.zero = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                '#' => spec.alternate = true,
+                _ => break,
+            }
+            chars.next();
+        }
+        while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+            spec.width = spec.width * 10 + digit as usize;
+            chars.next();
+        }
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            let mut precision = 0;
+            while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                precision = precision * 10 + digit as usize;
+                chars.next();
+            }
+            spec.precision = Some(precision);
+        }
+        // the size of the argument comes from its type, so length modifiers are skipped
+        while let Some('h') | Some('l') | Some('L') | Some('q') | Some('j') | Some('z')
+        | Some('t') = chars.peek()
+        {
+            chars.next();
+        }
+        spec.conversion = match chars.next() {
+            Some(c) if "diouxXcsfFeEgGp".contains(c) => c,
+            Some(c) => return Err(format!("Unrecognized format specifier '{}' in printf", c)),
+            None => return Err("Incomplete format specifier at end of format string".to_string()),
+        };
+        if !text.is_empty() {
+            pieces.push(Piece::Text(std::mem::take(&mut text)));
+        }
+        pieces.push(Piece::Conversion(spec));
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
+
+impl Spec {
+    /// Whether the conversion takes a string (`%s`), which is read through a pointer.
+    pub fn is_string(&self) -> bool {
+        self.conversion == 's'
+    }
+
+    /// Whether the conversion takes a floating point number.
+    pub fn is_float(&self) -> bool {
+        "fFeEgG".contains(self.conversion)
+    }
+
+    /// Whether the conversion takes a signed integer.
+    pub fn is_signed(&self) -> bool {
+        self.conversion == 'd' || self.conversion == 'i'
+    }
+
+    pub fn format(&self, arg: Arg) -> String {
+        // infinities and NaNs are padded with spaces even with the `0` flag
+        let numeric = !matches!(arg, Arg::Float(f) if !f.is_finite());
+        let (sign, digits) = match arg {
+            Arg::Str(s) => {
+                let s = match self.precision {
+                    Some(precision) => s.chars().take(precision).collect(),
+                    None => s,
+                };
+                return self.pad("", s, false);
+            }
+            Arg::Signed(n) if n < 0 => ("-", self.integer(n.unsigned_abs())),
+            Arg::Signed(n) => (self.sign(), self.integer(n as u64)),
+            Arg::Unsigned(n) if self.conversion == 'c' => {
+                return self.pad("", (n as u8 as char).to_string(), false)
+            }
+            Arg::Unsigned(n) => ("", self.integer(n)),
+            Arg::Float(f) if f.is_sign_negative() => ("-", self.float(-f)),
+            Arg::Float(f) => (self.sign(), self.float(f)),
+        };
+        self.pad(sign, digits, numeric)
+    }
+
+    fn sign(&self) -> &'static str {
+        if self.plus {
+            "+"
+        } else if self.space {
+            " "
+        } else {
+            ""
+        }
+    }
+
+    fn integer(&self, n: u64) -> String {
+        let digits = match self.conversion {
+            'o' if self.alternate && n != 0 => format!("0{:o}", n),
+            'o' => format!("{:o}", n),
+            'x' if self.alternate && n != 0 => format!("0x{:x}", n),
+            'x' => format!("{:x}", n),
+            'X' if self.alternate && n != 0 => format!("0X{:X}", n),
+            'X' => format!("{:X}", n),
+            'p' => format!("{:#x}", n),
+            _ => n.to_string(),
+        };
+        match self.precision {
+            Some(precision) if digits.len() < precision => {
+                format!("{}{}", "0".repeat(precision - digits.len()), digits)
+            }
+            _ => digits,
+        }
+    }
+
+    fn float(&self, f: f64) -> String {
+        if !f.is_finite() {
+            let name = if f.is_nan() { "nan" } else { "inf" };
+            return if self.conversion.is_ascii_uppercase() {
+                name.to_uppercase()
+            } else {
+                name.to_string()
+            };
+        }
+        let precision = self.precision.unwrap_or(6);
+        match self.conversion {
+            'e' | 'E' => self.exponential(f, precision),
+            'g' | 'G' => {
+                // the precision counts significant digits, and the exponent `%e` would show
+                // decides between the two forms
+                let precision = precision.max(1);
+                let exponent = format!("{:.*e}", precision - 1, f)
+                    .split_once('e')
+                    .and_then(|(_, exponent)| exponent.parse::<i32>().ok())
+                    .unwrap_or(0);
+                let formatted = if exponent < -4 || exponent >= precision as i32 {
+                    self.exponential(f, precision - 1)
+                } else {
+                    format!("{:.*}", (precision as i32 - 1 - exponent) as usize, f)
+                };
+                if self.alternate {
+                    formatted
+                } else {
+                    strip_trailing_zeros(formatted)
+                }
+            }
+            _ => format!("{:.*}", precision, f),
+        }
+    }
+
+    /// Formats a finite number as `%e` does, with `precision` digits after the point.
+    fn exponential(&self, f: f64, precision: usize) -> String {
+        // Rust writes `1.5e2` where C writes `1.500000e+02`
+        let formatted = format!("{:.*e}", precision, f);
+        let (mantissa, exponent) = match formatted.split_once('e') {
+            Some((mantissa, exponent)) => (mantissa, exponent.parse::<i32>().unwrap_or(0)),
+            None => return formatted,
+        };
+        let sign = if exponent < 0 { '-' } else { '+' };
+        let e = if self.conversion.is_ascii_uppercase() {
+            'E'
+        } else {
+            'e'
+        };
+        format!("{}{}{}{:02}", mantissa, e, sign, exponent.abs())
+    }
+
+    fn pad(&self, sign: &str, body: String, numeric: bool) -> String {
+        let len = sign.len() + body.chars().count();
+        if len >= self.width {
+            return format!("{}{}", sign, body);
+        }
+        let fill = self.width - len;
+        if self.left {
+            format!("{}{}{}", sign, body, " ".repeat(fill))
+        } else if self.zero && numeric && (self.precision.is_none() || self.is_float()) {
+            format!("{}{}{}", sign, "0".repeat(fill), body)
+        } else {
+            format!("{}{}{}", " ".repeat(fill), sign, body)
+        }
+    }
+}
+
+/// Drops the zeros `%g` leaves at the end of the fraction, and the point if nothing is left of it.
+fn strip_trailing_zeros(formatted: String) -> String {
+    let split = formatted.find(['e', 'E']).unwrap_or(formatted.len());
+    let (mantissa, exponent) = formatted.split_at(split);
+    if !mantissa.contains('.') {
+        return formatted;
+    }
+    format!(
+        "{}{}",
+        mantissa.trim_end_matches('0').trim_end_matches('.'),
+        exponent
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(spec: &str, arg: Arg) -> String {
+        match parse_format(spec).unwrap().pop() {
+            Some(Piece::Conversion(spec)) => spec.format(arg),
+            _ => panic!("{} is not a conversion", spec),
+        }
+    }
+
+    #[test]
+    fn parse_arguments() {
+        let (pieces, exprs) = parse(r#""x=%d, %%\t%s\n", x, name"#).unwrap();
+        assert_eq!(exprs, vec!["x", "name"]);
+        assert_eq!(pieces.len(), 5);
+        assert!(matches!(&pieces[0], Piece::Text(text) if text == "x="));
+        assert!(matches!(&pieces[2], Piece::Text(text) if text == ", %\t"));
+        assert!(matches!(&pieces[3], Piece::Conversion(spec) if spec.is_string()));
+        assert!(matches!(&pieces[4], Piece::Text(text) if text == "\n"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse("x").is_err());
+        assert!(parse(r#""%d"#).is_err());
+        assert!(parse(r#""%d" x"#).is_err());
+        assert!(parse(r#""%d %d", x"#).is_err());
+        assert!(parse(r#""%y", x"#).is_err());
+        assert!(parse(r#""%", x"#).is_err());
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(format("%d", Arg::Signed(-42)), "-42");
+        assert_eq!(format("%+5d", Arg::Signed(42)), "  +42");
+        assert_eq!(format("%-5d", Arg::Signed(42)), "42   ");
+        assert_eq!(format("%05d", Arg::Signed(-42)), "-0042");
+        assert_eq!(format("%.3u", Arg::Unsigned(7)), "007");
+        assert_eq!(format("%#x", Arg::Unsigned(255)), "0xff");
+        assert_eq!(format("%#X", Arg::Unsigned(0)), "0");
+        assert_eq!(format("%#o", Arg::Unsigned(8)), "010");
+        assert_eq!(format("%lx", Arg::Unsigned(u64::MAX)), "ffffffffffffffff");
+        assert_eq!(format("%c", Arg::Unsigned(b'a' as u64)), "a");
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(format("%s", Arg::Str("abc".to_string())), "abc");
+        assert_eq!(format("%.2s", Arg::Str("abc".to_string())), "ab");
+        assert_eq!(format("%5s", Arg::Str("abc".to_string())), "  abc");
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(format("%f", Arg::Float(1.5)), "1.500000");
+        assert_eq!(format("%.2f", Arg::Float(-0.125)), "-0.12");
+        assert_eq!(format("%e", Arg::Float(1.5)), "1.500000e+00");
+        assert_eq!(format("%.2e", Arg::Float(12345.0)), "1.23e+04");
+        assert_eq!(format("%E", Arg::Float(0.00015)), "1.500000E-04");
+        assert_eq!(format("%e", Arg::Float(1e100)), "1.000000e+100");
+        // the `0` flag is only ignored with a precision for integers
+        assert_eq!(format("%08.2f", Arg::Float(2.5)), "00002.50");
+        assert_eq!(format("%010.2e", Arg::Float(-2.5)), "-02.50e+00");
+        assert_eq!(format("%08.3d", Arg::Signed(42)), "     042");
+    }
+
+    #[test]
+    fn shortest_floats() {
+        assert_eq!(format("%g", Arg::Float(0.1 + 0.2)), "0.3");
+        assert_eq!(format("%g", Arg::Float(0.0)), "0");
+        assert_eq!(format("%g", Arg::Float(100000.0)), "100000");
+        assert_eq!(format("%g", Arg::Float(1e6)), "1e+06");
+        assert_eq!(format("%g", Arg::Float(123456789.0)), "1.23457e+08");
+        assert_eq!(format("%g", Arg::Float(0.0001)), "0.0001");
+        assert_eq!(format("%g", Arg::Float(0.00001)), "1e-05");
+        assert_eq!(format("%G", Arg::Float(1.5e-10)), "1.5E-10");
+        assert_eq!(format("%.3g", Arg::Float(1234.5)), "1.23e+03");
+        assert_eq!(format("%.0g", Arg::Float(123.0)), "1e+02");
+        // rounding up can carry into the next power of ten
+        assert_eq!(format("%.2g", Arg::Float(99.9)), "1e+02");
+        assert_eq!(format("%g", Arg::Float(-2.5)), "-2.5");
+        assert_eq!(format("%#g", Arg::Float(1.5)), "1.50000");
+        assert_eq!(format("%G", Arg::Float(f64::INFINITY)), "INF");
+    }
+
+    #[test]
+    fn non_finite_floats() {
+        assert_eq!(format("%e", Arg::Float(f64::NAN)), "nan");
+        assert_eq!(format("%E", Arg::Float(f64::INFINITY)), "INF");
+        assert_eq!(format("%e", Arg::Float(f64::NEG_INFINITY)), "-inf");
+        assert_eq!(format("%F", Arg::Float(f64::NAN)), "NAN");
+        assert_eq!(format("%+f", Arg::Float(f64::INFINITY)), "+inf");
+        // the `0` flag pads with spaces
+        assert_eq!(format("%08e", Arg::Float(f64::NEG_INFINITY)), "    -inf");
+    }
+}
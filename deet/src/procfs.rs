@@ -0,0 +1,175 @@
+//! What the kernel tells about a process under `/proc/<pid>`: its memory mappings and the
+//! auxiliary vector it was started with.
+
+use nix::unistd::Pid;
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+
+/// One line of `/proc/<pid>/maps`.
+pub struct Mapping {
+    pub start: usize,
+    pub end: usize,
+    /// Offset into the mapped file
+    pub offset: usize,
+    /// Permissions, as in `r-xp`
+    pub perms: String,
+    /// Mapped file, or a pseudo-name like `[stack]`; empty for anonymous memory
+    pub path: String,
+}
+
+/// How the value of an auxiliary vector entry is shown.
+#[derive(Clone, Copy, PartialEq)]
+pub enum AuxvFormat {
+    Hex,
+    Decimal,
+    /// Address of a NUL-terminated string in the inferior
+    Str,
+}
+
+/// Names, descriptions and formats of the auxiliary vector entry types, as GDB shows them.
+const AUXV_TYPES: [(u64, &str, &str, AuxvFormat); 29] = [
+    (0, "AT_NULL", "End of vector", AuxvFormat::Hex),
+    (1, "AT_IGNORE", "Entry should be ignored", AuxvFormat::Hex),
+    (
+        2,
+        "AT_EXECFD",
+        "File descriptor of program",
+        AuxvFormat::Decimal,
+    ),
+    (3, "AT_PHDR", "Program headers for program", AuxvFormat::Hex),
+    (
+        4,
+        "AT_PHENT",
+        "Size of program header entry",
+        AuxvFormat::Decimal,
+    ),
+    (
+        5,
+        "AT_PHNUM",
+        "Number of program headers",
+        AuxvFormat::Decimal,
+    ),
+    (6, "AT_PAGESZ", "System page size", AuxvFormat::Decimal),
+    (7, "AT_BASE", "Base address of interpreter", AuxvFormat::Hex),
+    (8, "AT_FLAGS", "Flags", AuxvFormat::Hex),
+    (9, "AT_ENTRY", "Entry point of program", AuxvFormat::Hex),
+    (10, "AT_NOTELF", "Program is not ELF", AuxvFormat::Decimal),
+    (11, "AT_UID", "Real user ID", AuxvFormat::Decimal),
+    (12, "AT_EUID", "Effective user ID", AuxvFormat::Decimal),
+    (13, "AT_GID", "Real group ID", AuxvFormat::Decimal),
+    (14, "AT_EGID", "Effective group ID", AuxvFormat::Decimal),
+    (
+        15,
+        "AT_PLATFORM",
+        "String identifying platform",
+        AuxvFormat::Str,
+    ),
+    (
+        16,
+        "AT_HWCAP",
+        "Machine-dependent CPU capability hints",
+        AuxvFormat::Hex,
+    ),
+    (17, "AT_CLKTCK", "Frequency of times()", AuxvFormat::Decimal),
+    (
+        23,
+        "AT_SECURE",
+        "Boolean, was exec setuid-like?",
+        AuxvFormat::Decimal,
+    ),
+    (
+        24,
+        "AT_BASE_PLATFORM",
+        "String identifying base platform",
+        AuxvFormat::Str,
+    ),
+    (
+        25,
+        "AT_RANDOM",
+        "Address of 16 random bytes",
+        AuxvFormat::Hex,
+    ),
+    (26, "AT_HWCAP2", "Extension of AT_HWCAP", AuxvFormat::Hex),
+    (
+        27,
+        "AT_RSEQ_FEATURE_SIZE",
+        "rseq supported feature size",
+        AuxvFormat::Decimal,
+    ),
+    (
+        28,
+        "AT_RSEQ_ALIGN",
+        "rseq allocation alignment",
+        AuxvFormat::Decimal,
+    ),
+    (29, "AT_HWCAP3", "Extension of AT_HWCAP", AuxvFormat::Hex),
+    (31, "AT_EXECFN", "File name of executable", AuxvFormat::Str),
+    (
+        32,
+        "AT_SYSINFO",
+        "Special system info/entry points",
+        AuxvFormat::Hex,
+    ),
+    (
+        33,
+        "AT_SYSINFO_EHDR",
+        "System-supplied DSO's ELF header",
+        AuxvFormat::Hex,
+    ),
+    (
+        51,
+        "AT_MINSIGSTKSZ",
+        "Minimal stack size for signal delivery",
+        AuxvFormat::Hex,
+    ),
+];
+
+/// Reads the memory mappings of process `pid`, in address order.
+pub fn mappings(pid: Pid) -> io::Result<Vec<Mapping>> {
+    let maps = fs::read_to_string(format!("/proc/{}/maps", pid))?;
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed /proc/<pid>/maps");
+    maps.lines()
+        .map(|line| {
+            // start-end perms offset dev inode [path], where the path may contain spaces
+            let mut fields = line.splitn(6, ' ');
+            let range = fields.next().ok_or_else(invalid)?;
+            let perms = fields.next().ok_or_else(invalid)?;
+            let offset = fields.next().ok_or_else(invalid)?;
+            let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+            let hex = |field: &str| usize::from_str_radix(field, 16).map_err(|_| invalid());
+            Ok(Mapping {
+                start: hex(start)?,
+                end: hex(end)?,
+                offset: hex(offset)?,
+                perms: perms.to_string(),
+                path: fields.nth(2).unwrap_or("").trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Reads the auxiliary vector of process `pid` as `(type, value)` pairs, up to and including
+/// the terminating AT_NULL.
+pub fn auxv(pid: Pid) -> io::Result<Vec<(u64, u64)>> {
+    let data = fs::read(format!("/proc/{}/auxv", pid))?;
+    let mut entries = Vec::new();
+    for entry in data.chunks_exact(16) {
+        let kind = u64::from_ne_bytes(entry[..8].try_into().unwrap());
+        let value = u64::from_ne_bytes(entry[8..].try_into().unwrap());
+        entries.push((kind, value));
+        if kind == 0 {
+            break;
+        }
+    }
+    Ok(entries)
+}
+
+/// Returns the name, description and format of an auxiliary vector entry type.
+pub fn auxv_type(kind: u64) -> (&'static str, &'static str, AuxvFormat) {
+    AUXV_TYPES
+        .iter()
+        .find(|(number, ..)| *number == kind)
+        .map(|(_, name, description, format)| (*name, *description, *format))
+        .unwrap_or(("???", "", AuxvFormat::Hex))
+}
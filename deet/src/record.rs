@@ -0,0 +1,132 @@
+use nix::sys::ptrace::{getregs, setregs};
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::debugger::BreakPoint;
+use crate::disassembler::DisassembleObject;
+use crate::inferior::{self, Inferior, Status};
+
+/// Default number of instructions kept in the execution log.
+pub const DEFAULT_RECORD_LIMIT: usize = 200_000;
+
+/// Longest possible x86-64 instruction.
+const MAX_INSN_LEN: usize = 15;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The execution log holds the given number of instructions and cannot grow any further.
+    LogFull(usize),
+    Nix(nix::Error),
+}
+
+impl From<nix::Error> for Error {
+    fn from(err: nix::Error) -> Self {
+        Error::Nix(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::LogFull(limit) => write!(
+                f,
+                "Record log is full ({} instructions), use \"record stop\" to discard it",
+                limit
+            ),
+            Error::Nix(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Machine state from just before an instruction was executed.
+struct Entry {
+    regs: libc::user_regs_struct,
+    fpregs: libc::user_fpregs_struct,
+    /// Memory the instruction was about to overwrite, with its old contents
+    memory: Vec<(usize, Vec<u8>)>,
+}
+
+/// Log of executed instructions that allows undoing them, one at a time. Forward execution while
+/// recording is done by single stepping; reverse execution pops the log and puts registers and
+/// memory back. Effects of system calls are not undone.
+pub struct Recorder {
+    entries: Vec<Entry>,
+    limit: usize,
+}
+
+impl Recorder {
+    pub fn new(limit: usize) -> Recorder {
+        Recorder {
+            entries: Vec::new(),
+            limit,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Registers the inferior had before the most recently recorded instruction.
+    pub fn last_regs(&self) -> Option<&libc::user_regs_struct> {
+        self.entries.last().map(|entry| &entry.regs)
+    }
+
+    /// Executes and logs a single instruction.
+    pub fn step(
+        &mut self,
+        inferior: &mut Inferior,
+        disassemble: &DisassembleObject,
+        breakpoint_set: &HashMap<usize, BreakPoint>,
+    ) -> Result<Status, Error> {
+        if self.entries.len() >= self.limit {
+            return Err(Error::LogFull(self.limit));
+        }
+
+        let regs = getregs(inferior.pid())?;
+        let fpregs = inferior::getfpregs(inferior.pid())?;
+
+        // decode the instruction as the program sees it, without our breakpoints
+        let rip = regs.rip as usize;
+        let mut code = inferior
+            .read_memory(rip, MAX_INSN_LEN)
+            .or_else(|_| inferior.read_memory(rip, 1))?;
+        for (offset, byte) in code.iter_mut().enumerate() {
+            if let Some(bp) = breakpoint_set.get(&(rip + offset)) {
+                *byte = bp.orig_byte;
+            }
+        }
+
+        let mut memory = Vec::new();
+        for (addr, len) in disassemble.memory_writes(&code, &regs) {
+            // unreadable memory means the store is going to fault, so nothing changes
+            if let Ok(old) = inferior.read_memory(addr, len) {
+                memory.push((addr, old));
+            }
+        }
+
+        self.entries.push(Entry {
+            regs,
+            fpregs,
+            memory,
+        });
+        Ok(inferior.step_instruction(breakpoint_set)?)
+    }
+
+    /// Undoes the most recently recorded instruction. Returns false if the log is empty.
+    pub fn reverse_step(&mut self, inferior: &mut Inferior) -> Result<bool, Error> {
+        let entry = match self.entries.pop() {
+            Some(entry) => entry,
+            None => return Ok(false),
+        };
+        for (addr, old) in &entry.memory {
+            inferior.write_memory(*addr, old)?;
+        }
+        setregs(inferior.pid(), entry.regs)?;
+        inferior::setfpregs(inferior.pid(), entry.fpregs)?;
+        Ok(true)
+    }
+}
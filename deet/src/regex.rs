@@ -0,0 +1,261 @@
+//! The regular expressions `info functions`, `info variables` and `info types` filter names
+//! with. Like GDB's, these are POSIX basic regular expressions with the GNU extensions: `.`,
+//! `*`, `^`, `$`, bracket expressions, and `\+`, `\?` and `\|`. Everything else (including
+//! `(`, `+` and `|`) matches itself, so C++ names like `operator()` need no escaping.
+
+enum Atom {
+    Char(char),
+    Any,
+    /// A bracket expression: whether it is negated, and the ranges of characters it lists
+    Class(bool, Vec<(char, char)>),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Repeat {
+    Once,
+    Optional,
+    ZeroOrMore,
+    OneOrMore,
+}
+
+struct Branch {
+    anchored_start: bool,
+    anchored_end: bool,
+    pieces: Vec<(Atom, Repeat)>,
+}
+
+pub struct Regex {
+    branches: Vec<Branch>,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let mut branches = Vec::new();
+        let mut chars = pattern.chars().peekable();
+        let mut branch = Branch {
+            anchored_start: chars.peek() == Some(&'^'),
+            anchored_end: false,
+            pieces: Vec::new(),
+        };
+        if branch.anchored_start {
+            chars.next();
+        }
+        while let Some(c) = chars.next() {
+            let atom = match c {
+                '.' => Atom::Any,
+                // `$` anchors only at the end of a branch
+                '$' if chars.peek().is_none() || chars.clone().take(2).eq("\\|".chars()) => {
+                    branch.anchored_end = true;
+                    continue;
+                }
+                '*' if !branch.pieces.is_empty() => {
+                    set_repeat(&mut branch, Repeat::ZeroOrMore);
+                    continue;
+                }
+                '[' => parse_class(&mut chars)?,
+                '\\' => match chars.next() {
+                    Some('+') if !branch.pieces.is_empty() => {
+                        set_repeat(&mut branch, Repeat::OneOrMore);
+                        continue;
+                    }
+                    Some('?') if !branch.pieces.is_empty() => {
+                        set_repeat(&mut branch, Repeat::Optional);
+                        continue;
+                    }
+                    Some('|') => {
+                        let anchored_start = chars.peek() == Some(&'^');
+                        if anchored_start {
+                            chars.next();
+                        }
+                        branches.push(std::mem::replace(
+                            &mut branch,
+                            Branch {
+                                anchored_start,
+                                anchored_end: false,
+                                pieces: Vec::new(),
+                            },
+                        ));
+                        continue;
+                    }
+                    Some(c) => Atom::Char(c),
+                    None => return Err("Trailing backslash".to_string()),
+                },
+                c => Atom::Char(c),
+            };
+            branch.pieces.push((atom, Repeat::Once));
+        }
+        branches.push(branch);
+        Ok(Regex { branches })
+    }
+
+    /// Whether the expression matches anywhere in `text`.
+    pub fn is_match(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        self.branches.iter().any(|branch| {
+            let starts = if branch.anchored_start {
+                0..1
+            } else {
+                0..text.len() + 1
+            };
+            starts
+                .into_iter()
+                .any(|start| match_here(&branch.pieces, &text[start..], branch.anchored_end))
+        })
+    }
+}
+
+fn set_repeat(branch: &mut Branch, repeat: Repeat) {
+    let last = branch.pieces.last_mut().unwrap();
+    // `a**` is the same as `a*`, and `a\+*` is too
+    last.1 = if last.1 == Repeat::Once {
+        repeat
+    } else {
+        Repeat::ZeroOrMore
+    };
+}
+
+/// Parses a bracket expression such as `[a-z_]` or `[^0-9]`, after its `[`.
+fn parse_class(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Atom, String> {
+    let negated = chars.peek() == Some(&'^');
+    if negated {
+        chars.next();
+    }
+    let mut ranges = Vec::new();
+    // a `]` right at the start is part of the list rather than its end
+    let mut first = true;
+    loop {
+        let c = match chars.next() {
+            Some(']') if !first => break,
+            Some(c) => c,
+            None => return Err("Unmatched [ or [^".to_string()),
+        };
+        first = false;
+        let mut lookahead = chars.clone();
+        match (lookahead.next(), lookahead.next()) {
+            (Some('-'), Some(end)) if end != ']' => {
+                chars.next();
+                chars.next();
+                ranges.push((c, end));
+            }
+            _ => ranges.push((c, c)),
+        }
+    }
+    Ok(Atom::Class(negated, ranges))
+}
+
+fn atom_matches(atom: &Atom, c: char) -> bool {
+    match atom {
+        Atom::Char(expected) => *expected == c,
+        Atom::Any => true,
+        Atom::Class(negated, ranges) => {
+            ranges.iter().any(|(low, high)| *low <= c && c <= *high) != *negated
+        }
+    }
+}
+
+fn match_here(pieces: &[(Atom, Repeat)], text: &[char], anchored_end: bool) -> bool {
+    let (atom, repeat) = match pieces.first() {
+        Some(piece) => piece,
+        None => return !anchored_end || text.is_empty(),
+    };
+    let (min, max) = match repeat {
+        Repeat::Once => (1, 1),
+        Repeat::Optional => (0, 1),
+        Repeat::ZeroOrMore => (0, usize::MAX),
+        Repeat::OneOrMore => (1, usize::MAX),
+    };
+    let available = text
+        .iter()
+        .take_while(|c| atom_matches(atom, **c))
+        .count()
+        .min(max);
+    // repetitions are greedy, backing off one at a time
+    (min..=available)
+        .rev()
+        .any(|count| match_here(&pieces[1..], &text[count..], anchored_end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn literals_and_wildcards() {
+        assert!(matches("", "anything"));
+        assert!(matches("ain", "main"));
+        assert!(!matches("mian", "main"));
+        assert!(matches("m..n", "main"));
+        assert!(matches("operator()", "Foo::operator()"));
+        assert!(matches("a+b", "a+b"));
+        assert!(!matches("a+b", "aab"));
+    }
+
+    #[test]
+    fn anchors() {
+        assert!(matches("^main$", "main"));
+        assert!(!matches("^main$", "domain"));
+        assert!(!matches("^main", "domain"));
+        assert!(matches("main$", "domain"));
+        // `^` and `$` are only anchors at the edges of a branch
+        assert!(matches("a^b", "a^b"));
+        assert!(matches("a$b", "a$b"));
+    }
+
+    #[test]
+    fn repetition() {
+        assert!(matches("^ab*c$", "ac"));
+        assert!(matches("^ab*c$", "abbbc"));
+        assert!(!matches("^ab\\+c$", "ac"));
+        assert!(matches("^ab\\+c$", "abbc"));
+        assert!(matches("^ab\\?c$", "abc"));
+        assert!(!matches("^ab\\?c$", "abbc"));
+        assert!(matches("^a.*b.*c$", "axxbxxbxc"));
+        assert!(matches("^a**$", "aaa"));
+    }
+
+    #[test]
+    fn leading_repetition_is_literal() {
+        assert!(matches("*x", "a*x"));
+        assert!(!matches("*x", "ax"));
+        assert!(matches("^*", "*a"));
+        assert!(!matches("^*", "a"));
+        assert!(matches("\\+", "a+"));
+        assert!(!matches("\\+", "a"));
+        assert!(matches("a\\|*b", "*b"));
+        assert!(!matches("a\\|*b", "b"));
+    }
+
+    #[test]
+    fn alternation() {
+        assert!(matches("^foo$\\|^bar$", "foo"));
+        assert!(matches("^foo$\\|^bar$", "bar"));
+        assert!(!matches("^foo$\\|^bar$", "foobar"));
+        assert!(!matches("^foo\\|bar$", "xfoo"));
+        // an empty branch matches everything
+        assert!(matches("\\|foo", "bar"));
+        assert!(matches("foo\\|", "bar"));
+        assert!(!matches("foo\\|^$", "bar"));
+        assert!(matches("foo\\|^$", ""));
+    }
+
+    #[test]
+    fn bracket_expressions() {
+        assert!(matches("^[a-c_]*$", "ab_c"));
+        assert!(!matches("^[a-c_]*$", "abd"));
+        assert!(matches("^[^0-9]*$", "abc"));
+        assert!(!matches("^[^0-9]*$", "a1"));
+        assert!(matches("[]x]", "]"));
+        assert!(matches("^[a-]$", "-"));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(Regex::new("a\\").is_err());
+        assert!(Regex::new("[a-z").is_err());
+        assert!(Regex::new("[]").is_err());
+    }
+}
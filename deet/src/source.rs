@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Finds and caches the source files of the program being debugged. Paths recorded in the debug
+/// info can be rewritten with substitution rules and searched for in extra directories, for
+/// binaries that were built on another machine.
+pub struct SourceFiles {
+    directories: Vec<PathBuf>,
+    substitutions: Vec<(String, String)>,
+    /// Lines of each file looked up so far, or `None` for those that could not be found
+    cache: HashMap<String, Option<Vec<String>>>,
+}
+
+impl SourceFiles {
+    pub fn new() -> SourceFiles {
+        SourceFiles {
+            directories: Vec::new(),
+            substitutions: Vec::new(),
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Adds a directory to search for source files in. Directories added later are searched
+    /// first.
+    pub fn add_directory(&mut self, dir: &str) {
+        self.directories.insert(0, PathBuf::from(dir));
+        self.cache.clear();
+    }
+
+    pub fn directories(&self) -> &[PathBuf] {
+        &self.directories
+    }
+
+    /// Rewrites source paths starting with `from` to start with `to` instead.
+    pub fn add_substitution(&mut self, from: &str, to: &str) {
+        self.substitutions.retain(|(f, _)| f != from);
+        self.substitutions.push((from.to_string(), to.to_string()));
+        self.cache.clear();
+    }
+
+    pub fn substitutions(&self) -> &[(String, String)] {
+        &self.substitutions
+    }
+
+    /// Works out where `file` is on this machine. Relative paths are taken relative to
+    /// `comp_dir`, the compilation directory recorded in the debug info.
+    pub fn resolve(&self, file: &str, comp_dir: Option<&str>) -> Option<PathBuf> {
+        let mut path = PathBuf::from(file);
+        if path.is_relative() {
+            if let Some(dir) = comp_dir {
+                path = Path::new(dir).join(path);
+            }
+        }
+        let path = self.substitute(&path.to_string_lossy());
+        if path.is_file() {
+            return Some(path);
+        }
+
+        for dir in &self.directories {
+            let candidate = dir.join(file.trim_start_matches('/'));
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+            let candidate = dir.join(path.file_name()?);
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+        }
+        None
+    }
+
+    /// Returns the lines of `file`, reading it from disk the first time. A file that cannot be
+    /// found is not searched for again until the search settings change.
+    pub fn lines(&mut self, file: &str, comp_dir: Option<&str>) -> Option<&Vec<String>> {
+        if !self.cache.contains_key(file) {
+            let lines = self.read(file, comp_dir);
+            self.cache.insert(file.to_string(), lines);
+        }
+        self.cache.get(file)?.as_ref()
+    }
+
+    /// Whether `file` has already been looked for without being found.
+    pub fn is_missing(&self, file: &str) -> bool {
+        matches!(self.cache.get(file), Some(None))
+    }
+
+    fn read(&self, file: &str, comp_dir: Option<&str>) -> Option<Vec<String>> {
+        let path = self.resolve(file, comp_dir)?;
+        let contents = fs::read(path).ok()?;
+        Some(
+            String::from_utf8_lossy(&contents)
+                .lines()
+                .map(|line| line.to_string())
+                .collect(),
+        )
+    }
+
+    fn substitute(&self, path: &str) -> PathBuf {
+        for (from, to) in &self.substitutions {
+            if let Some(rest) = path.strip_prefix(from.as_str()) {
+                if rest.is_empty() || rest.starts_with('/') || from.ends_with('/') {
+                    return PathBuf::from(format!("{}{}", to, rest));
+                }
+            }
+        }
+        PathBuf::from(path)
+    }
+}
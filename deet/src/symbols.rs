@@ -0,0 +1,150 @@
+//! Function names from the ELF symbol tables (`.symtab`, and `.dynsym` for stripped programs)
+//! and the PLT, which is all there is to go on for programs built without debugging information.
+
+use crate::gimli_wrapper;
+use object::{Object, ObjectSection, SymbolKind, SymbolSection};
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+/// Size of an x86-64 PLT entry, and of the header that starts `.plt`.
+const PLT_ENTRY_SIZE: usize = 16;
+
+/// Size of an `Elf64_Rela` relocation entry.
+const RELA_SIZE: usize = 24;
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    /// Demangled name; PLT entries are called `puts@plt`
+    pub name: String,
+    pub address: usize,
+    /// Size in bytes
+    pub size: usize,
+    /// Name of the section the code is in
+    pub section: String,
+}
+
+/// Function symbols of a program, sorted by address.
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    /// Collects the symbols of `objects`: a program, and the separate debug file that has its
+    /// `.symtab` if it has been stripped.
+    pub fn new(objects: &[&object::File]) -> SymbolTable {
+        let mut symbols: Vec<Symbol> = objects
+            .iter()
+            .flat_map(|object| function_symbols(object))
+            .collect();
+        for object in objects {
+            symbols.extend(plt_symbols(object));
+        }
+        symbols.sort_by_key(|symbol| symbol.address);
+        // `.dynsym` repeats what `.symtab` already has, and aliases share an address
+        symbols.dedup_by_key(|symbol| symbol.address);
+        SymbolTable { symbols }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols.iter()
+    }
+
+    /// Returns the symbol whose code contains `addr`, along with the offset of `addr` into it.
+    pub fn symbol_at(&self, addr: usize) -> Option<(&Symbol, usize)> {
+        let index = match self
+            .symbols
+            .binary_search_by_key(&addr, |symbol| symbol.address)
+        {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+        let symbol = &self.symbols[index];
+        let offset = addr - symbol.address;
+        if offset >= symbol.size {
+            return None;
+        }
+        Some((symbol, offset))
+    }
+
+    /// Describes `addr` as `func+0x1a`, or just `func` at its first instruction.
+    pub fn describe(&self, addr: usize) -> Option<String> {
+        let (symbol, offset) = self.symbol_at(addr)?;
+        if offset == 0 {
+            Some(symbol.name.clone())
+        } else {
+            Some(format!("{}+{:#x}", symbol.name, offset))
+        }
+    }
+}
+
+/// Function symbols defined in `.symtab` and `.dynsym`.
+fn function_symbols(object: &object::File) -> Vec<Symbol> {
+    object
+        .symbols()
+        .chain(object.dynamic_symbols())
+        .filter(|(_, symbol)| symbol.kind() == SymbolKind::Text && symbol.address() != 0)
+        .filter_map(|(_, symbol)| {
+            let name = symbol.name()?;
+            let section = match symbol.section() {
+                SymbolSection::Section(index) => object.section_by_index(index)?,
+                _ => return None,
+            };
+            // symbols without a size (like `_init`) are taken to reach to the end of their
+            // section, unless another symbol follows them
+            let size = match symbol.size() {
+                0 => section.address() + section.size() - symbol.address(),
+                size => size,
+            };
+            Some(Symbol {
+                name: gimli_wrapper::demangle(name, None).unwrap_or_else(|| name.to_string()),
+                address: symbol.address() as usize,
+                size: size as usize,
+                section: section.name().unwrap_or("").to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Names the entries of the PLT after the functions they jump to. Each relocation in
+/// `.rela.plt` belongs to the PLT entry at the same position, in `.plt.sec` when the program
+/// was linked with indirect branch tracking, or else in `.plt` after its header.
+fn plt_symbols(object: &object::File) -> Vec<Symbol> {
+    let relocations = match object.section_by_name(".rela.plt") {
+        Some(section) => section.data().into_owned(),
+        None => return Vec::new(),
+    };
+    let (section, first_entry) = match (
+        object.section_by_name(".plt.sec"),
+        object.section_by_name(".plt"),
+    ) {
+        (Some(plt_sec), _) => (".plt.sec", plt_sec.address() as usize),
+        (None, Some(plt)) => (".plt", plt.address() as usize + PLT_ENTRY_SIZE),
+        (None, None) => return Vec::new(),
+    };
+    // relocations refer to symbols by their index in `.dynsym`
+    let names: HashMap<usize, &str> = object
+        .dynamic_symbols()
+        .filter_map(|(index, symbol)| Some((index.0, symbol.name()?)))
+        .collect();
+    let little_endian = object.is_little_endian();
+    relocations
+        .chunks_exact(RELA_SIZE)
+        .enumerate()
+        .filter_map(|(i, rela)| {
+            let info: [u8; 8] = rela[8..16].try_into().unwrap();
+            let info = if little_endian {
+                u64::from_le_bytes(info)
+            } else {
+                u64::from_be_bytes(info)
+            };
+            let name = names.get(&((info >> 32) as usize))?;
+            Some(Symbol {
+                name: format!("{}@plt", name),
+                address: first_entry + i * PLT_ENTRY_SIZE,
+                size: PLT_ENTRY_SIZE,
+                section: section.to_string(),
+            })
+        })
+        .collect()
+}
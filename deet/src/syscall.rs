@@ -0,0 +1,577 @@
+use nix::errno::Errno;
+use nix::sys::ptrace::getregs;
+use nix::sys::signal::Signal;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::Write;
+
+use crate::inferior::{Inferior, Launch, Status, SyscallStop};
+
+/// Longest string or buffer shown in a decoded syscall, as strace does.
+const MAX_STRING_LEN: usize = 32;
+
+/// Names of the x86-64 system calls 0 to 334, indexed by syscall number.
+#[rustfmt::skip]
+const SYSCALL_NAMES: [&str; 335] = [
+    "read", "write", "open", "close", "stat", "fstat", "lstat", "poll", "lseek", "mmap", "mprotect",
+    "munmap", "brk", "rt_sigaction", "rt_sigprocmask", "rt_sigreturn", "ioctl", "pread64",
+    "pwrite64", "readv", "writev", "access", "pipe", "select", "sched_yield", "mremap", "msync",
+    "mincore", "madvise", "shmget", "shmat", "shmctl", "dup", "dup2", "pause", "nanosleep",
+    "getitimer", "alarm", "setitimer", "getpid", "sendfile", "socket", "connect", "accept",
+    "sendto", "recvfrom", "sendmsg", "recvmsg", "shutdown", "bind", "listen", "getsockname",
+    "getpeername", "socketpair", "setsockopt", "getsockopt", "clone", "fork", "vfork", "execve",
+    "exit", "wait4", "kill", "uname", "semget", "semop", "semctl", "shmdt", "msgget", "msgsnd",
+    "msgrcv", "msgctl", "fcntl", "flock", "fsync", "fdatasync", "truncate", "ftruncate", "getdents",
+    "getcwd", "chdir", "fchdir", "rename", "mkdir", "rmdir", "creat", "link", "unlink", "symlink",
+    "readlink", "chmod", "fchmod", "chown", "fchown", "lchown", "umask", "gettimeofday",
+    "getrlimit", "getrusage", "sysinfo", "times", "ptrace", "getuid", "syslog", "getgid", "setuid",
+    "setgid", "geteuid", "getegid", "setpgid", "getppid", "getpgrp", "setsid", "setreuid",
+    "setregid", "getgroups", "setgroups", "setresuid", "getresuid", "setresgid", "getresgid",
+    "getpgid", "setfsuid", "setfsgid", "getsid", "capget", "capset", "rt_sigpending",
+    "rt_sigtimedwait", "rt_sigqueueinfo", "rt_sigsuspend", "sigaltstack", "utime", "mknod",
+    "uselib", "personality", "ustat", "statfs", "fstatfs", "sysfs", "getpriority", "setpriority",
+    "sched_setparam", "sched_getparam", "sched_setscheduler", "sched_getscheduler",
+    "sched_get_priority_max", "sched_get_priority_min", "sched_rr_get_interval", "mlock", "munlock",
+    "mlockall", "munlockall", "vhangup", "modify_ldt", "pivot_root", "_sysctl", "prctl",
+    "arch_prctl", "adjtimex", "setrlimit", "chroot", "sync", "acct", "settimeofday", "mount",
+    "umount2", "swapon", "swapoff", "reboot", "sethostname", "setdomainname", "iopl", "ioperm",
+    "create_module", "init_module", "delete_module", "get_kernel_syms", "query_module", "quotactl",
+    "nfsservctl", "getpmsg", "putpmsg", "afs_syscall", "tuxcall", "security", "gettid", "readahead",
+    "setxattr", "lsetxattr", "fsetxattr", "getxattr", "lgetxattr", "fgetxattr", "listxattr",
+    "llistxattr", "flistxattr", "removexattr", "lremovexattr", "fremovexattr", "tkill", "time",
+    "futex", "sched_setaffinity", "sched_getaffinity", "set_thread_area", "io_setup", "io_destroy",
+    "io_getevents", "io_submit", "io_cancel", "get_thread_area", "lookup_dcookie", "epoll_create",
+    "epoll_ctl_old", "epoll_wait_old", "remap_file_pages", "getdents64", "set_tid_address",
+    "restart_syscall", "semtimedop", "fadvise64", "timer_create", "timer_settime", "timer_gettime",
+    "timer_getoverrun", "timer_delete", "clock_settime", "clock_gettime", "clock_getres",
+    "clock_nanosleep", "exit_group", "epoll_wait", "epoll_ctl", "tgkill", "utimes", "vserver",
+    "mbind", "set_mempolicy", "get_mempolicy", "mq_open", "mq_unlink", "mq_timedsend",
+    "mq_timedreceive", "mq_notify", "mq_getsetattr", "kexec_load", "waitid", "add_key",
+    "request_key", "keyctl", "ioprio_set", "ioprio_get", "inotify_init", "inotify_add_watch",
+    "inotify_rm_watch", "migrate_pages", "openat", "mkdirat", "mknodat", "fchownat", "futimesat",
+    "newfstatat", "unlinkat", "renameat", "linkat", "symlinkat", "readlinkat", "fchmodat",
+    "faccessat", "pselect6", "ppoll", "unshare", "set_robust_list", "get_robust_list", "splice",
+    "tee", "sync_file_range", "vmsplice", "move_pages", "utimensat", "epoll_pwait", "signalfd",
+    "timerfd_create", "eventfd", "fallocate", "timerfd_settime", "timerfd_gettime", "accept4",
+    "signalfd4", "eventfd2", "epoll_create1", "dup3", "pipe2", "inotify_init1", "preadv", "pwritev",
+    "rt_tgsigqueueinfo", "perf_event_open", "recvmmsg", "fanotify_init", "fanotify_mark",
+    "prlimit64", "name_to_handle_at", "open_by_handle_at", "clock_adjtime", "syncfs", "sendmmsg",
+    "setns", "getcpu", "process_vm_readv", "process_vm_writev", "kcmp", "finit_module",
+    "sched_setattr", "sched_getattr", "renameat2", "seccomp", "getrandom", "memfd_create",
+    "kexec_file_load", "bpf", "execveat", "userfaultfd", "membarrier", "mlock2", "copy_file_range",
+    "preadv2", "pwritev2", "pkey_mprotect", "pkey_alloc", "pkey_free", "statx", "io_pgetevents",
+    "rseq",
+];
+
+/// Syscalls numbered from 424 on, added after the gap left for other architectures.
+const NEWER_SYSCALLS_START: u64 = 424;
+#[rustfmt::skip]
+const NEWER_SYSCALL_NAMES: [&str; 29] = [
+    "pidfd_send_signal", "io_uring_setup", "io_uring_enter", "io_uring_register", "open_tree",
+    "move_mount", "fsopen", "fsconfig", "fsmount", "fspick", "pidfd_open", "clone3", "close_range",
+    "openat2", "pidfd_getfd", "faccessat2", "process_madvise", "epoll_pwait2", "mount_setattr",
+    "quotactl_fd", "landlock_create_ruleset", "landlock_add_rule", "landlock_restrict_self",
+    "memfd_secret", "process_mrelease", "futex_waitv", "set_mempolicy_home_node", "cachestat",
+    "fchmodat2",
+];
+
+/// Returns the name of a system call, or None if the number is unknown.
+pub fn syscall_name(nr: u64) -> Option<&'static str> {
+    if nr < SYSCALL_NAMES.len() as u64 {
+        Some(SYSCALL_NAMES[nr as usize])
+    } else if nr >= NEWER_SYSCALLS_START {
+        NEWER_SYSCALL_NAMES
+            .get((nr - NEWER_SYSCALLS_START) as usize)
+            .copied()
+    } else {
+        None
+    }
+}
+
+/// Parses a syscall given by name (`write`) or number (`1`).
+pub fn parse_syscall(arg: &str) -> Option<u64> {
+    if let Ok(nr) = arg.parse::<u64>() {
+        return Some(nr);
+    }
+    if let Some(nr) = SYSCALL_NAMES.iter().position(|name| *name == arg) {
+        return Some(nr as u64);
+    }
+    NEWER_SYSCALL_NAMES
+        .iter()
+        .position(|name| *name == arg)
+        .map(|nr| nr as u64 + NEWER_SYSCALLS_START)
+}
+
+/// How to show a syscall argument.
+#[derive(Clone, Copy)]
+enum Arg {
+    Int,
+    Uint,
+    Hex,
+    Ptr,
+    Fd,
+    /// A directory file descriptor, which may be AT_FDCWD
+    DirFd,
+    /// A NUL terminated string
+    Path,
+    /// A buffer read by the kernel; its length is the argument with the given index
+    InBuf(usize),
+    /// A buffer filled in by the kernel; its length is the syscall's return value
+    OutBuf,
+    OpenFlags,
+    Mode,
+    Prot,
+    MapFlags,
+    Signal,
+}
+
+/// Argument types of the syscalls deet knows how to decode.
+fn signature(name: &str) -> Option<&'static [Arg]> {
+    use Arg::*;
+    Some(match name {
+        "read" => &[Fd, OutBuf, Uint],
+        "write" => &[Fd, InBuf(2), Uint],
+        "pread64" => &[Fd, OutBuf, Uint, Int],
+        "pwrite64" => &[Fd, InBuf(2), Uint, Int],
+        "open" => &[Path, OpenFlags, Mode],
+        "openat" => &[DirFd, Path, OpenFlags, Mode],
+        "creat" => &[Path, Mode],
+        "close" | "dup" | "fsync" | "fdatasync" | "fchdir" => &[Fd],
+        "dup2" => &[Fd, Fd],
+        "dup3" => &[Fd, Fd, Hex],
+        "stat" | "lstat" => &[Path, Ptr],
+        "fstat" => &[Fd, Ptr],
+        "newfstatat" => &[DirFd, Path, Ptr, Hex],
+        "statx" => &[DirFd, Path, Hex, Hex, Ptr],
+        "access" => &[Path, Int],
+        "faccessat" | "faccessat2" => &[DirFd, Path, Int, Hex],
+        "lseek" => &[Fd, Int, Int],
+        "mmap" => &[Ptr, Uint, Prot, MapFlags, Fd, Hex],
+        "mprotect" => &[Ptr, Uint, Prot],
+        "munmap" => &[Ptr, Uint],
+        "brk" | "set_tid_address" | "uname" | "pipe" => &[Ptr],
+        "pipe2" => &[Ptr, Hex],
+        "ioctl" | "fcntl" => &[Fd, Hex, Hex],
+        "getdents64" => &[Fd, Ptr, Uint],
+        "execve" => &[Path, Ptr, Ptr],
+        "exit" | "exit_group" => &[Int],
+        "kill" => &[Int, Signal],
+        "tgkill" => &[Int, Int, Signal],
+        "wait4" => &[Int, Ptr, Hex, Ptr],
+        "chdir" | "rmdir" | "unlink" => &[Path],
+        "mkdir" | "chmod" => &[Path, Mode],
+        "mkdirat" => &[DirFd, Path, Mode],
+        "unlinkat" => &[DirFd, Path, Hex],
+        "rename" | "link" | "symlink" => &[Path, Path],
+        "readlink" => &[Path, OutBuf, Uint],
+        "readlinkat" => &[DirFd, Path, OutBuf, Uint],
+        "getcwd" => &[OutBuf, Uint],
+        "arch_prctl" => &[Hex, Hex],
+        "rt_sigaction" => &[Signal, Ptr, Ptr, Uint],
+        "rt_sigprocmask" => &[Int, Ptr, Ptr, Uint],
+        "nanosleep" => &[Ptr, Ptr],
+        "clock_nanosleep" => &[Int, Hex, Ptr, Ptr],
+        "clock_gettime" => &[Int, Ptr],
+        "getrandom" => &[Ptr, Uint, Hex],
+        "prlimit64" => &[Int, Int, Ptr, Ptr],
+        "set_robust_list" => &[Ptr, Uint],
+        "rseq" => &[Ptr, Uint, Hex, Hex],
+        "futex" => &[Ptr, Int, Int, Ptr, Ptr, Int],
+        "socket" => &[Int, Int, Int],
+        "connect" | "bind" => &[Fd, Ptr, Uint],
+        "clone" => &[Hex, Ptr, Ptr, Ptr, Hex],
+        "getpid" | "getppid" | "gettid" | "getuid" | "geteuid" | "getgid" | "getegid" | "fork"
+        | "vfork" | "sched_yield" | "pause" | "setsid" | "getpgrp" => &[],
+        _ => return None,
+    })
+}
+
+/// A system call, as seen at its entry or exit.
+pub struct Syscall {
+    pub nr: u64,
+    pub args: [u64; 6],
+}
+
+impl Syscall {
+    /// Reads the syscall number and arguments from the registers of a tracee stopped in a
+    /// syscall.
+    pub fn from_regs(regs: &libc::user_regs_struct) -> Syscall {
+        Syscall {
+            nr: regs.orig_rax,
+            args: [regs.rdi, regs.rsi, regs.rdx, regs.r10, regs.r8, regs.r9],
+        }
+    }
+
+    /// Returns whether the kernel writes the syscall's result into a buffer given by the caller.
+    pub fn fills_buffer(&self) -> bool {
+        signature(&self.name())
+            .map(|signature| signature.iter().any(|arg| matches!(arg, Arg::OutBuf)))
+            .unwrap_or(false)
+    }
+
+    pub fn name(&self) -> String {
+        match syscall_name(self.nr) {
+            Some(name) => name.to_string(),
+            None => format!("syscall_{}", self.nr),
+        }
+    }
+
+    /// Formats the call as `name(arg, ...)`. `ret` is the return value once the syscall has
+    /// finished; buffers filled in by the kernel are only shown then.
+    pub fn format_call(&self, inferior: &Inferior, ret: Option<i64>) -> String {
+        let name = self.name();
+        let args: Vec<String> = match signature(&name) {
+            Some(signature) => signature
+                .iter()
+                .enumerate()
+                // like open(2) itself, ignore the mode unless a file may be created
+                .take_while(|(i, arg)| match (arg, signature.get(i.wrapping_sub(1))) {
+                    (Arg::Mode, Some(Arg::OpenFlags)) => {
+                        self.args[i - 1] & (libc::O_CREAT | libc::O_TMPFILE) as u64 != 0
+                    }
+                    _ => true,
+                })
+                .map(|(i, arg)| self.format_arg(inferior, *arg, self.args[i], ret))
+                .collect(),
+            None => self.args.iter().map(|arg| format!("{:#x}", arg)).collect(),
+        };
+        format!("{}({})", name, args.join(", "))
+    }
+
+    fn format_arg(&self, inferior: &Inferior, arg: Arg, value: u64, ret: Option<i64>) -> String {
+        match arg {
+            Arg::Int | Arg::Fd => format!("{}", value as i64 as i32),
+            Arg::Uint => format!("{}", value),
+            Arg::Hex if value == 0 => "0".to_string(),
+            Arg::Hex => format!("{:#x}", value),
+            Arg::Ptr if value == 0 => "NULL".to_string(),
+            Arg::Ptr => format!("{:#x}", value),
+            Arg::DirFd if value as i32 == libc::AT_FDCWD => "AT_FDCWD".to_string(),
+            Arg::DirFd => format!("{}", value as i32),
+            Arg::Path => format_string(inferior, value as usize, None),
+            Arg::InBuf(len_arg) => {
+                format_string(inferior, value as usize, Some(self.args[len_arg] as usize))
+            }
+            Arg::OutBuf => match ret {
+                Some(len) if len >= 0 => {
+                    format_string(inferior, value as usize, Some(len as usize))
+                }
+                _ => format!("{:#x}", value),
+            },
+            Arg::OpenFlags => format_open_flags(value),
+            Arg::Mode => format!("0{:o}", value),
+            Arg::Prot if value == 0 => "PROT_NONE".to_string(),
+            Arg::Prot => format_flags(
+                value,
+                &[
+                    (libc::PROT_READ as u64, "PROT_READ"),
+                    (libc::PROT_WRITE as u64, "PROT_WRITE"),
+                    (libc::PROT_EXEC as u64, "PROT_EXEC"),
+                ],
+            ),
+            Arg::MapFlags => format_flags(
+                value,
+                &[
+                    (libc::MAP_SHARED as u64, "MAP_SHARED"),
+                    (libc::MAP_PRIVATE as u64, "MAP_PRIVATE"),
+                    (libc::MAP_FIXED as u64, "MAP_FIXED"),
+                    (libc::MAP_ANONYMOUS as u64, "MAP_ANONYMOUS"),
+                    (libc::MAP_GROWSDOWN as u64, "MAP_GROWSDOWN"),
+                    (libc::MAP_DENYWRITE as u64, "MAP_DENYWRITE"),
+                    (libc::MAP_NORESERVE as u64, "MAP_NORESERVE"),
+                    (libc::MAP_POPULATE as u64, "MAP_POPULATE"),
+                    (libc::MAP_STACK as u64, "MAP_STACK"),
+                    (libc::MAP_FIXED_NOREPLACE as u64, "MAP_FIXED_NOREPLACE"),
+                ],
+            ),
+            Arg::Signal => match Signal::try_from(value as i32) {
+                Ok(sig) => sig.as_str().to_string(),
+                Err(_) => format!("{}", value as i32),
+            },
+        }
+    }
+
+    /// Formats the value the syscall returned, with the errno name for failures.
+    pub fn format_return(&self, ret: i64) -> String {
+        if ret < 0 && ret > -4096 {
+            let errno = Errno::from_i32(-ret as i32);
+            return format!("-1 {:?} ({})", errno, errno.desc());
+        }
+        match syscall_name(self.nr) {
+            Some("mmap") | Some("mremap") | Some("brk") => format!("{:#x}", ret),
+            _ => format!("{}", ret),
+        }
+    }
+}
+
+/// Joins the names of the flags set in `value` with `|`, followed by any unknown bits.
+fn format_flags(value: u64, names: &[(u64, &str)]) -> String {
+    let mut parts = Vec::new();
+    let mut rest = value;
+    for (flag, name) in names {
+        if rest & flag == *flag && *flag != 0 {
+            parts.push(name.to_string());
+            rest &= !flag;
+        }
+    }
+    if rest != 0 || parts.is_empty() {
+        parts.push(format!("{:#x}", rest));
+    }
+    parts.join("|")
+}
+
+fn format_open_flags(value: u64) -> String {
+    let access = match value as i32 & libc::O_ACCMODE {
+        libc::O_RDONLY => "O_RDONLY",
+        libc::O_WRONLY => "O_WRONLY",
+        _ => "O_RDWR",
+    };
+    let rest = value & !(libc::O_ACCMODE as u64);
+    if rest == 0 {
+        return access.to_string();
+    }
+    let flags = format_flags(
+        rest,
+        &[
+            (libc::O_CREAT as u64, "O_CREAT"),
+            (libc::O_EXCL as u64, "O_EXCL"),
+            (libc::O_NOCTTY as u64, "O_NOCTTY"),
+            (libc::O_TRUNC as u64, "O_TRUNC"),
+            (libc::O_APPEND as u64, "O_APPEND"),
+            (libc::O_NONBLOCK as u64, "O_NONBLOCK"),
+            (libc::O_SYNC as u64, "O_SYNC"),
+            (libc::O_DSYNC as u64, "O_DSYNC"),
+            (libc::O_DIRECT as u64, "O_DIRECT"),
+            (libc::O_LARGEFILE as u64, "O_LARGEFILE"),
+            (libc::O_DIRECTORY as u64, "O_DIRECTORY"),
+            (libc::O_NOFOLLOW as u64, "O_NOFOLLOW"),
+            (libc::O_NOATIME as u64, "O_NOATIME"),
+            (libc::O_CLOEXEC as u64, "O_CLOEXEC"),
+            (libc::O_PATH as u64, "O_PATH"),
+        ],
+    );
+    format!("{}|{}", access, flags)
+}
+
+/// Reads a string out of the inferior and quotes it. `len` is the length of a buffer, or None
+/// for a NUL terminated string. Long strings are cut short and followed by `...`.
+fn format_string(inferior: &Inferior, addr: usize, len: Option<usize>) -> String {
+    if addr == 0 {
+        return "NULL".to_string();
+    }
+    let limit = len.unwrap_or(usize::MAX).min(MAX_STRING_LEN);
+    let mut bytes = Vec::new();
+    let mut terminated = false;
+    // read word by word, so that a string ending just before unmapped memory still works
+    while bytes.len() < limit {
+        let chunk_addr = addr + bytes.len();
+        let chunk_len = std::mem::size_of::<usize>() - chunk_addr % std::mem::size_of::<usize>();
+        let chunk = match inferior.read_memory(chunk_addr, chunk_len) {
+            Ok(chunk) => chunk,
+            Err(_) if bytes.is_empty() => return format!("{:#x}", addr),
+            Err(_) => break,
+        };
+        for byte in chunk {
+            if len.is_none() && byte == 0 {
+                terminated = true;
+                break;
+            }
+            bytes.push(byte);
+        }
+        if terminated {
+            break;
+        }
+    }
+
+    let truncated = match len {
+        Some(len) => len > bytes.len(),
+        None => !terminated,
+    };
+    bytes.truncate(limit);
+    let mut quoted = String::from("\"");
+    for byte in bytes {
+        match byte {
+            b'\n' => quoted.push_str("\\n"),
+            b'\t' => quoted.push_str("\\t"),
+            b'\r' => quoted.push_str("\\r"),
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            0x20..=0x7e => quoted.push(byte as char),
+            _ => quoted.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    quoted.push('"');
+    if truncated {
+        quoted.push_str("...");
+    }
+    quoted
+}
+
+/// Runs `target` to completion, logging every system call it makes to `log` in the style of
+/// strace. Returns the exit status to leave deet with.
+pub fn trace(target: &str, args: &Vec<String>, log: &mut dyn Write) -> i32 {
+    let mut breakpoint_set = HashMap::new();
+    let mut inferior = match Inferior::new(
+        target,
+        args,
+        Launch::default(),
+        &Vec::new(),
+        &mut breakpoint_set,
+    ) {
+        Some(inferior) => inferior,
+        None => {
+            eprintln!("Error starting subprocess");
+            return 1;
+        }
+    };
+
+    // the call in progress, printed once it returns, and how it looked on entry
+    let mut pending: Option<(Syscall, String)> = None;
+    let mut deliver: Option<Signal> = None;
+    loop {
+        let status = match inferior.syscall_exec(&breakpoint_set, deliver.take()) {
+            Ok(status) => status,
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        };
+
+        match status {
+            Status::Syscall(stop, _) => {
+                let regs = match getregs(inferior.pid()) {
+                    Ok(regs) => regs,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return 1;
+                    }
+                };
+                if stop == SyscallStop::Entry {
+                    let syscall = Syscall::from_regs(&regs);
+                    let text = syscall.format_call(&inferior, None);
+                    pending = Some((syscall, text));
+                    continue;
+                }
+                let ret = regs.rax as i64;
+                let (syscall, text) = match pending.take() {
+                    Some((syscall, text)) if !syscall.fills_buffer() => (syscall, text),
+                    // buffers the kernel filled in can only be shown now
+                    _ => {
+                        let syscall = Syscall::from_regs(&regs);
+                        let text = syscall.format_call(&inferior, Some(ret));
+                        (syscall, text)
+                    }
+                };
+                writeln!(log, "{} = {}", text, syscall.format_return(ret)).ok();
+            }
+            Status::Stopped(sig, _) => {
+                writeln!(log, "--- {} ---", sig.as_str()).ok();
+                // hand the signal on to the program, except for the traps caused by tracing
+                if sig != Signal::SIGTRAP {
+                    deliver = Some(sig);
+                }
+            }
+            Status::Exited(code) => {
+                if let Some((_, text)) = pending.take() {
+                    writeln!(log, "{} = ?", text).ok();
+                }
+                writeln!(log, "+++ exited with {} +++", code).ok();
+                return code;
+            }
+            Status::Signaled(sig) => {
+                if let Some((_, text)) = pending.take() {
+                    writeln!(log, "{} = ?", text).ok();
+                }
+                writeln!(log, "+++ killed by {} +++", sig.as_str()).ok();
+                return 128 + sig as i32;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn syscall(name: &str) -> Syscall {
+        Syscall {
+            nr: parse_syscall(name).unwrap(),
+            args: [0; 6],
+        }
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(syscall_name(0), Some("read"));
+        assert_eq!(syscall_name(334), Some("rseq"));
+        assert_eq!(syscall_name(335), None);
+        assert_eq!(syscall_name(423), None);
+        assert_eq!(syscall_name(424), Some("pidfd_send_signal"));
+        assert_eq!(syscall_name(452), Some("fchmodat2"));
+        assert_eq!(syscall_name(453), None);
+        assert_eq!(
+            Syscall {
+                nr: 999,
+                args: [0; 6]
+            }
+            .name(),
+            "syscall_999"
+        );
+    }
+
+    #[test]
+    fn parse_names_and_numbers() {
+        assert_eq!(parse_syscall("write"), Some(1));
+        assert_eq!(parse_syscall("60"), Some(60));
+        assert_eq!(parse_syscall("clone3"), Some(435));
+        assert_eq!(parse_syscall("nosuchcall"), None);
+    }
+
+    #[test]
+    fn flags() {
+        let names = [(1, "A"), (2, "B")];
+        assert_eq!(format_flags(0, &names), "0x0");
+        assert_eq!(format_flags(3, &names), "A|B");
+        assert_eq!(format_flags(0x12, &names), "B|0x10");
+    }
+
+    #[test]
+    fn open_flags() {
+        assert_eq!(format_open_flags(libc::O_RDONLY as u64), "O_RDONLY");
+        assert_eq!(
+            format_open_flags((libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC) as u64),
+            "O_WRONLY|O_CREAT|O_TRUNC"
+        );
+        assert_eq!(
+            format_open_flags((libc::O_RDWR | libc::O_CLOEXEC) as u64),
+            "O_RDWR|O_CLOEXEC"
+        );
+        // O_SYNC includes the bits of O_DSYNC
+        assert_eq!(
+            format_open_flags((libc::O_WRONLY | libc::O_SYNC) as u64),
+            "O_WRONLY|O_SYNC"
+        );
+    }
+
+    #[test]
+    fn return_values() {
+        assert_eq!(
+            syscall("open").format_return(-2),
+            "-1 ENOENT (No such file or directory)"
+        );
+        assert_eq!(syscall("write").format_return(5), "5");
+        assert_eq!(
+            syscall("mmap").format_return(0x7f0000001000),
+            "0x7f0000001000"
+        );
+        // only -4095 to -1 are errors
+        assert_eq!(syscall("mmap").format_return(-4096), "0xfffffffffffff000");
+    }
+
+    #[test]
+    fn output_buffers() {
+        assert!(syscall("read").fills_buffer());
+        assert!(!syscall("write").fills_buffer());
+        assert!(!syscall("getpid").fills_buffer());
+    }
+}
@@ -0,0 +1,176 @@
+use std::io::{self, Write};
+
+/// A line of one of the TUI's windows. Highlighted lines are drawn in reverse video.
+pub struct PaneLine {
+    pub text: String,
+    pub highlight: bool,
+}
+
+impl PaneLine {
+    pub fn new(text: String, highlight: bool) -> PaneLine {
+        PaneLine { text, highlight }
+    }
+}
+
+/// Minimum number of terminal rows kept for the command prompt and its output.
+const MIN_COMMAND_ROWS: usize = 6;
+
+/// Text user interface drawn with plain ANSI escape sequences. The top of the terminal holds a
+/// source window, with disassembly and registers windows side by side below it; the remaining
+/// rows at the bottom form a scrolling region for the `(deet)` prompt and command output.
+pub struct Tui {
+    rows: usize,
+    cols: usize,
+}
+
+/// Returns whether standard output is a terminal.
+pub fn stdout_is_tty() -> bool {
+    unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 }
+}
+
+fn terminal_size() -> (usize, usize) {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let res = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    if res == 0 && size.ws_row > 0 && size.ws_col > 0 {
+        (size.ws_row as usize, size.ws_col as usize)
+    } else {
+        (24, 80)
+    }
+}
+
+/// Cuts `text` down to `width` characters, padding it with spaces if it is shorter.
+fn fit(text: &str, width: usize) -> String {
+    let text = text.replace('\t', "    ");
+    let mut fitted: String = text.chars().take(width).collect();
+    let len = fitted.chars().count();
+    fitted.extend(std::iter::repeat_n(' ', width - len));
+    fitted
+}
+
+impl Tui {
+    /// Takes over the terminal. Returns None if standard output is not a terminal, in which case
+    /// deet stays in plain mode.
+    pub fn enable() -> Option<Tui> {
+        if !stdout_is_tty() {
+            return None;
+        }
+        let (rows, cols) = terminal_size();
+        let tui = Tui { rows, cols };
+        // clear the screen and park the cursor at the bottom of the command area
+        print!(
+            "\x1b[2J\x1b[{};{}r\x1b[{};1H",
+            tui.command_top(),
+            rows,
+            rows
+        );
+        io::stdout().flush().ok();
+        Some(tui)
+    }
+
+    /// Gives the whole terminal back to the command prompt.
+    pub fn disable(self) {
+        print!("\x1b[r\x1b[2J\x1b[{};1H", self.rows);
+        io::stdout().flush().ok();
+    }
+
+    /// Number of rows taken by the windows, above the command area.
+    fn window_rows(&self) -> usize {
+        let command_rows = MIN_COMMAND_ROWS.max(self.rows / 4);
+        self.rows.saturating_sub(command_rows)
+    }
+
+    /// Number of rows the source window takes, including its title.
+    pub fn source_rows(&self) -> usize {
+        self.window_rows() / 2
+    }
+
+    /// Number of rows the disassembly and register windows take, including their titles.
+    pub fn lower_rows(&self) -> usize {
+        self.window_rows() - self.source_rows()
+    }
+
+    /// First row (1-based) of the command area.
+    fn command_top(&self) -> usize {
+        self.window_rows() + 1
+    }
+
+    /// Redraws all windows, leaving the command area and the cursor in it untouched.
+    pub fn draw(&mut self, source: &[PaneLine], disassembly: &[PaneLine], registers: &[PaneLine]) {
+        let (rows, cols) = terminal_size();
+        let resized = (rows, cols) != (self.rows, self.cols);
+        self.rows = rows;
+        self.cols = cols;
+
+        let mut screen = String::new();
+        // save the cursor, since everything below uses absolute positioning
+        screen.push_str("\x1b7");
+        if resized {
+            screen.push_str(&format!("\x1b[{};{}r", self.command_top(), self.rows));
+        }
+
+        let source_rows = self.source_rows();
+        let lower_rows = self.lower_rows();
+        self.draw_window(
+            &mut screen,
+            (1, 1, self.cols, source_rows),
+            "Source",
+            source,
+        );
+
+        let registers_cols = (self.cols / 3).max(24).min(self.cols);
+        let disassembly_cols = self.cols - registers_cols;
+        self.draw_window(
+            &mut screen,
+            (source_rows + 1, 1, disassembly_cols, lower_rows),
+            "Disassembly",
+            disassembly,
+        );
+        self.draw_window(
+            &mut screen,
+            (
+                source_rows + 1,
+                disassembly_cols + 1,
+                registers_cols,
+                lower_rows,
+            ),
+            "Registers",
+            registers,
+        );
+
+        screen.push_str("\x1b8");
+        print!("{}", screen);
+        io::stdout().flush().ok();
+    }
+
+    /// Draws a window with a title line into `screen`. `area` is the window's top row, left
+    /// column, width and height, all in terminal cells.
+    fn draw_window(
+        &self,
+        screen: &mut String,
+        area: (usize, usize, usize, usize),
+        title: &str,
+        lines: &[PaneLine],
+    ) {
+        let (top, left, width, height) = area;
+        if width == 0 || height == 0 {
+            return;
+        }
+        let title = format!("-- {} ", title);
+        screen.push_str(&format!(
+            "\x1b[{};{}H\x1b[1m{}\x1b[0m",
+            top,
+            left,
+            fit(&format!("{:-<width$}", title, width = width), width)
+        ));
+        for row in 0..height - 1 {
+            screen.push_str(&format!("\x1b[{};{}H", top + 1 + row, left));
+            match lines.get(row) {
+                Some(line) if line.highlight => {
+                    screen.push_str(&format!("\x1b[7m{}\x1b[0m", fit(&line.text, width)))
+                }
+                Some(line) => screen.push_str(&fit(&line.text, width)),
+                None => screen.push_str(&fit("", width)),
+            }
+        }
+    }
+}